@@ -0,0 +1,45 @@
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+
+/// Where the session is downloaded from: the OpenF1 API, or a server that
+/// stands in for it, such as the one of the `mock-api` command.
+#[derive(Debug, Clone)]
+pub struct Api {
+    client: Client,
+    base_url: String,
+    session_key: String,
+}
+
+impl Api {
+    pub fn new(base_url: &str, session_key: &str) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            session_key: session_key.to_string(),
+        }
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// The URL of an endpoint for the session, with `query` appended to
+    /// the session filter, e.g. `&driver_number=1`.
+    pub fn url(&self, endpoint: &str, query: &str) -> String {
+        format!(
+            "{}/v1/{}?session_key={}{}",
+            self.base_url, endpoint, self.session_key, query,
+        )
+    }
+
+    pub async fn json<T: DeserializeOwned>(&self, endpoint: &str, query: &str) -> Result<Vec<T>, String> {
+        let url = self.url(endpoint, query);
+        log::debug!("GET {}", url);
+        let resp = self.client.get(&url).send().await.map_err(|e| e.to_string())?;
+        if !resp.status().is_success() {
+            return Err(format!("HTTP {}", resp.status()));
+        }
+
+        resp.json().await.map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+use crate::circuit;
+use crate::effects;
+use crate::filter::DriverFilter;
+use crate::layout::Layout;
+use crate::led_data::UpdateFrame;
+use crate::physical::LedModel;
+use crate::pit::PitStops;
+use crate::power::PowerBudget;
+use crate::race_control::RaceControl;
+use crate::replay::{frame_from_positions, DriverPosition, Replay};
+use crate::trails::Trails;
+
+/// Everything that goes into the board at a moment of the replay, borrowed
+/// from wherever the session is kept, so the window and the command line
+/// build their frames the same way.
+pub struct Board<'a> {
+    pub replay: &'a Replay,
+    pub race_control: &'a RaceControl,
+    pub pit_stops: &'a PitStops,
+    pub filter: &'a DriverFilter,
+    pub pit_lane_leds: bool,
+}
+
+impl Board<'_> {
+    /// The board for `elapsed` into the replay: the drivers, with the race
+    /// control effects composited underneath, and the positions it was
+    /// built from. `trails` is moved on to `elapsed`.
+    pub fn frame_at(&self, elapsed: Duration, trails: &mut Trails) -> Option<(UpdateFrame, Vec<DriverPosition>)> {
+        let time = self.replay.time_at(elapsed)?;
+
+        let mut positions = self.replay.positions_at(elapsed);
+        self.pit_stops.place(&mut positions, time, self.pit_lane_leds);
+
+        let timestamp = elapsed.as_millis() as u64;
+        trails.advance(&positions, self.filter, timestamp);
+
+        let frame = frame_from_positions(timestamp, &positions, self.filter);
+        let frame = trails.composite(&frame);
+        Some((effects::composite(&frame, &self.race_control.status_at(time)), positions))
+    }
+
+    /// The board for `elapsed` as it is shown and sent to the hardware:
+    /// `frame_at`'s, dimmed to stay within `budget` on `layout`.
+    pub fn limited_frame_at(
+        &self,
+        elapsed: Duration,
+        trails: &mut Trails,
+        budget: &PowerBudget,
+        model: &LedModel,
+        layout: &Layout,
+    ) -> Option<(UpdateFrame, Vec<DriverPosition>)> {
+        let (frame, positions) = self.frame_at(elapsed, trails)?;
+        Some((budget.limit(&frame, model, layout), positions))
+    }
+}
+
+/// A session loaded in one go, for the commands that run without a window.
+pub struct Session {
+    pub replay: Replay,
+    pub race_control: RaceControl,
+    pub pit_stops: PitStops,
+    pub reference_lap: Option<Vec<(f32, f32)>>,
+}
+
+impl Session {
+    /// Falls back to pit stops detected from the replay when none were
+    /// downloaded, as the window does.
+    pub fn new(replay: Replay, race_control: RaceControl, pit_stops: PitStops) -> Self {
+        let pit_stops = if pit_stops.is_empty() {
+            PitStops::from_replay(&replay)
+        } else {
+            pit_stops
+        };
+        let reference_lap = circuit::reference_lap(&replay);
+
+        Self {
+            replay,
+            race_control,
+            pit_stops,
+            reference_lap,
+        }
+    }
+
+    pub fn board<'a>(&'a self, filter: &'a DriverFilter, pit_lane_leds: bool) -> Board<'a> {
+        Board {
+            replay: &self.replay,
+            race_control: &self.race_control,
+            pit_stops: &self.pit_stops,
+            filter,
+            pit_lane_leds,
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use serde::Deserialize;
+use std::path::Path;
+
+use crate::led_data::{UpdateFrame, LED_DATA, PIT_LANE_LED_DATA};
+
+/// One strip of the chain: a run of logical LEDs wired one after the
+/// other.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Strip {
+    /// First and last logical LED on the strip, in track order.
+    pub leds: (u32, u32),
+    /// Whether the strip's data input is at its last LED rather than its
+    /// first.
+    #[serde(default)]
+    pub reversed: bool,
+    /// Chain index of the strip's first pixel. Defaults to right after the
+    /// previous strip.
+    pub offset: Option<u32>,
+    /// Pixels of the strip, counted from its data input, that show no LED:
+    /// dead ones, or ones hidden under the frame. They are kept dark and
+    /// the LEDs carry on past them.
+    #[serde(default)]
+    pub skip: Vec<u32>,
+}
+
+impl Strip {
+    fn run(first: u32, last: u32) -> Self {
+        Strip {
+            leds: (first, last),
+            reversed: false,
+            offset: None,
+            skip: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ChainFile {
+    strip: Vec<Strip>,
+}
+
+/// How the board's LEDs are wired: which pixel of the daisy chain each
+/// logical LED number is, for the outputs that drive real strips.
+#[derive(Debug, Clone)]
+pub struct ChainMap {
+    /// Chain index of each logical LED, indexed by LED number.
+    indexes: Vec<Option<u32>>,
+    /// Pixels on the chain, mapped or not.
+    len: u32,
+}
+
+/// Most pixels a chain may have. Far more than any board needs, it keeps a
+/// mistyped offset from laying out billions of pixels.
+pub const MAX_PIXELS: u32 = 1 << 16;
+
+impl Default for ChainMap {
+    /// The board as built: the track LEDs on one strip in track order, then
+    /// the pit lane LEDs on a second one.
+    fn default() -> Self {
+        let track = LED_DATA.len() as u32;
+        let pit_lane = PIT_LANE_LED_DATA.len() as u32;
+        ChainMap::new(&[Strip::run(1, track), Strip::run(track + 1, track + pit_lane)], track + pit_lane)
+            .expect("the board's own chain is valid")
+    }
+}
+
+impl ChainMap {
+    /// Lays `strips` out on the chain, checking that they only hold LEDs up
+    /// to `max_led_number`, that the chain stays within [`MAX_PIXELS`], and
+    /// that no LED is on two pixels and no pixel carries two LEDs.
+    pub fn new(strips: &[Strip], max_led_number: u32) -> Result<Self, String> {
+        let mut indexes: Vec<Option<u32>> = Vec::new();
+        let mut taken: Vec<bool> = Vec::new();
+        let mut next = 0;
+
+        for (i, strip) in strips.iter().enumerate() {
+            let (first, last) = strip.leds;
+            if first == 0 || last < first {
+                return Err(format!("strip {}: LEDs {}..={} are not a run of LED numbers", i + 1, first, last));
+            }
+            if last > max_led_number {
+                return Err(format!(
+                    "strip {}: LED {} is not on the board, which ends at LED {}",
+                    i + 1,
+                    last,
+                    max_led_number
+                ));
+            }
+            let mut leds: Vec<u32> = (first..=last).collect();
+            if strip.reversed {
+                leds.reverse();
+            }
+            let len = leds.len() as u32 + strip.skip.len() as u32;
+            if let Some(skipped) = strip.skip.iter().find(|&&pixel| pixel >= len) {
+                return Err(format!("strip {}: skipped pixel {} is past its {} pixels", i + 1, skipped, len));
+            }
+            if let Some(twice) = strip.skip.iter().enumerate().find(|(j, pixel)| strip.skip[..*j].contains(pixel)) {
+                return Err(format!("strip {}: pixel {} is skipped twice", i + 1, twice.1));
+            }
+
+            let start = strip.offset.unwrap_or(next);
+            let end = match start.checked_add(len) {
+                Some(end) if end <= MAX_PIXELS => end,
+                _ => {
+                    return Err(format!(
+                        "strip {}: pixels {}..{} are past the end of the chain, which holds at most {}",
+                        i + 1,
+                        start,
+                        start as u64 + len as u64,
+                        MAX_PIXELS
+                    ))
+                }
+            };
+            let pixels = (0..len).filter(|pixel| !strip.skip.contains(pixel));
+            for (led, pixel) in leds.into_iter().zip(pixels) {
+                let index = start + pixel;
+                if indexes.len() <= led as usize {
+                    indexes.resize(led as usize + 1, None);
+                }
+                if indexes[led as usize].is_some() {
+                    return Err(format!("strip {}: LED {} is already on another strip", i + 1, led));
+                }
+                indexes[led as usize] = Some(index);
+            }
+            for index in start..end {
+                if taken.len() <= index as usize {
+                    taken.resize(index as usize + 1, false);
+                }
+                if std::mem::replace(&mut taken[index as usize], true) {
+                    return Err(format!("strip {}: pixel {} of the chain is already on another strip", i + 1, index));
+                }
+            }
+            next = end;
+        }
+
+        Ok(ChainMap {
+            indexes,
+            len: taken.len() as u32,
+        })
+    }
+
+    /// Reads strips from a TOML file of `[[strip]]` tables, laid out as by
+    /// [`ChainMap::new`].
+    pub fn from_file(path: &Path, max_led_number: u32) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let file: ChainFile = toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        ChainMap::new(&file.strip, max_led_number).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Chain index of logical LED `led_number`, if it is wired.
+    pub fn index(&self, led_number: u32) -> Option<u32> {
+        self.indexes.get(led_number as usize).copied().flatten()
+    }
+
+    pub fn pixel_count(&self) -> u32 {
+        self.len
+    }
+
+    /// The color of every pixel of the chain in `frame`, in wire order.
+    /// Skipped pixels and LEDs the frame leaves unlit are black, and LEDs
+    /// that are not wired are left out.
+    pub fn pixels(&self, frame: &UpdateFrame) -> Vec<(u8, u8, u8)> {
+        let mut pixels = vec![(0, 0, 0); self.len as usize];
+        let colors = frame.led_colors(self.indexes.len().saturating_sub(1) as u32);
+        for (led_number, color) in colors.into_iter().enumerate() {
+            if let (Some(index), Some(color)) = (self.index(led_number as u32), color) {
+                pixels[index as usize] = color;
+            }
+        }
+        pixels
+    }
+
+    /// The frame a chain showing `pixels` lights up, the other way round
+    /// from `pixels`: the LED of every lit pixel in its color.
+    pub fn frame(&self, pixels: &[(u8, u8, u8)]) -> UpdateFrame {
+        let mut frame = UpdateFrame::new(0);
+        for (led_number, index) in self.indexes.iter().enumerate() {
+            let color = index.and_then(|index| pixels.get(index as usize));
+            if let Some(&color) = color.filter(|&&color| color != (0, 0, 0)) {
+                frame.set_led_state(led_number as u32, color);
+            }
+        }
+        frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<ChainMap, String> {
+        let file: ChainFile = toml::from_str(text).map_err(|e| e.to_string())?;
+        ChainMap::new(&file.strip, 100)
+    }
+
+    #[test]
+    fn leds_past_the_board_are_rejected() {
+        let error = parse("[[strip]]\nleds = [1, 4000000000]").unwrap_err();
+        assert!(error.contains("LED 4000000000 is not on the board"), "{}", error);
+        assert!(parse("[[strip]]\nleds = [1, 100]").is_ok());
+        assert!(parse("[[strip]]\nleds = [1, 101]").is_err());
+    }
+
+    #[test]
+    fn strips_past_the_end_of_the_chain_are_rejected() {
+        let error = parse("[[strip]]\nleds = [1, 10]\noffset = 4000000000").unwrap_err();
+        assert!(error.contains("past the end of the chain"), "{}", error);
+        // Would overflow a u32 rather than just run long.
+        assert!(parse("[[strip]]\nleds = [1, 10]\noffset = 4294967290").is_err());
+        assert!(parse(&format!("[[strip]]\nleds = [1, 10]\noffset = {}", MAX_PIXELS - 10)).is_ok());
+        assert!(parse(&format!("[[strip]]\nleds = [1, 10]\noffset = {}", MAX_PIXELS - 9)).is_err());
+    }
+
+    #[test]
+    fn chain_skips_pixels_and_reverses_strips() {
+        let strips = [
+            Strip {
+                leds: (1, 4),
+                reversed: false,
+                offset: None,
+                skip: vec![1],
+            },
+            Strip {
+                leds: (5, 7),
+                reversed: true,
+                offset: None,
+                skip: Vec::new(),
+            },
+        ];
+        let chain = ChainMap::new(&strips, 7).unwrap();
+        assert_eq!(chain.pixel_count(), 8);
+
+        let mut frame = UpdateFrame::new(0);
+        frame.set_led_state(2, (255, 0, 0));
+        frame.set_led_state(5, (0, 0, 255));
+        let pixels = chain.pixels(&frame);
+        // LED 2 is past the skipped pixel, and LED 5 is at the far end of
+        // its reversed strip.
+        assert_eq!(pixels[1], (0, 0, 0));
+        assert_eq!(pixels[2], (255, 0, 0));
+        assert_eq!(pixels[7], (0, 0, 255));
+        assert_eq!(chain.frame(&pixels).led_colors(7), frame.led_colors(7));
+    }
+}
@@ -0,0 +1,89 @@
+use crate::led_data::on_pit_lane;
+use crate::replay::{Replay, Sample};
+
+/// Corner numbers of the circuit and the LED at the apex of each.
+///
+/// Picked by eye from the shape of the LED outline rather than measured, so
+/// a label can sit an LED or two off the real apex.
+pub const CORNERS: &[(u32, u32)] = &[
+    (1, 57),
+    (2, 62),
+    (3, 65),
+    (4, 68),
+    (5, 73),
+    (6, 77),
+    (7, 81),
+    (8, 87),
+    (9, 95),
+    (10, 4),
+    (11, 10),
+    (12, 24),
+    (13, 33),
+    (14, 38),
+];
+
+/// The start/finish line lies between this LED and the next one, as near
+/// as the LED spacing on the main straight allows to place it by eye.
+pub const START_FINISH_AFTER_LED: u32 = 50;
+
+/// The timing sector boundaries after the start/finish line, each between
+/// the given LED and the next one.
+///
+/// Estimates: the official boundaries are timing loops whose positions are
+/// not in the data, so these are placed where the circuit map draws them.
+pub const SECTOR_BOUNDARIES_AFTER_LED: &[u32] = &[83, 16];
+
+#[derive(Debug, Clone)]
+pub struct DrsZone {
+    pub first_led: u32,
+    pub last_led: u32,
+}
+
+/// The DRS zones of the circuit. Like the corners, these are approximate
+/// and drawn for orientation, not taken from published zone positions.
+pub const DRS_ZONES: &[DrsZone] = &[
+    DrsZone { first_led: 41, last_led: 56 },
+    DrsZone { first_led: 12, last_led: 22 },
+];
+
+/// Distance a car has to get from where the lap started before coming back
+/// counts as completing it, in track units.
+const LAP_MIN_EXCURSION: f32 = 1000.0;
+/// How close to the start of the lap a car has to come back.
+const LAP_CLOSE_DISTANCE: f32 = 400.0;
+
+/// One clean lap of location samples, to draw the circuit outline from.
+///
+/// The lap is taken from a third of the way into the session, past the
+/// formation lap and the start, and is skipped if it goes through the pit
+/// lane.
+pub fn reference_lap(replay: &Replay) -> Option<Vec<(f32, f32)>> {
+    replay.tracks().find_map(|(_, samples)| {
+        let mut start = samples.len() / 3;
+        while start < samples.len() {
+            match find_lap(&samples[start..]) {
+                Some(lap) if lap.iter().any(|s| on_pit_lane(s.x, s.y)) => start += lap.len(),
+                Some(lap) => return Some(lap.iter().map(|s| (s.x, s.y)).collect()),
+                None => return None,
+            }
+        }
+        None
+    })
+}
+
+/// The samples from the first one until the car is back at the same spot.
+fn find_lap(samples: &[Sample]) -> Option<&[Sample]> {
+    let origin = samples.first()?;
+    let distance = |s: &Sample| ((s.x - origin.x).powi(2) + (s.y - origin.y).powi(2)).sqrt();
+
+    let away = samples.iter().position(|s| distance(s) > LAP_MIN_EXCURSION)?;
+    let back = away + samples[away..].iter().position(|s| distance(s) < LAP_CLOSE_DISTANCE)?;
+
+    // Stop at the sample closest to the origin, not the first one in range.
+    let mut end = back;
+    while end + 1 < samples.len() && distance(&samples[end + 1]) < distance(&samples[end]) {
+        end += 1;
+    }
+
+    Some(&samples[..=end])
+}
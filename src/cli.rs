@@ -0,0 +1,425 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::api::Api;
+use crate::chain::ChainMap;
+use crate::keys::Keymap;
+use crate::layout::{BoardLayout, Layout, LED_SIZE};
+use crate::link::LinkTarget;
+use crate::mqtt::Broker;
+use crate::power::PowerBudget;
+use crate::wled::{ColorOrder, WledConfig, WledTarget};
+
+/// Replays a Formula 1 session on an LED model of the circuit. Without a
+/// command, opens the window.
+///
+/// Settings are read from `config.toml` in the user's config directory,
+/// `~/.config/f1-led-circuit` on Linux, when it exists; flags given on the
+/// command line take precedence over it.
+///
+/// In the window, space plays and pauses, the arrows step a tick and seek
+/// 30 seconds with shift, 1 to 9 and 0 set the speed, D highlights the
+/// next driver, F and P switch fullscreen and presentation mode, Escape
+/// leaves both and R resets. The `[keys]` table of the settings rebinds
+/// them.
+#[derive(Debug, Parser)]
+pub struct Cli {
+    /// Reads settings from this TOML file instead of the default one.
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+    #[command(flatten)]
+    pub source: SourceArgs,
+    #[command(flatten)]
+    pub playback: PlaybackArgs,
+    #[command(flatten)]
+    pub display: DisplayArgs,
+    #[command(flatten)]
+    pub window: WindowArgs,
+    #[command(flatten)]
+    pub sync: SyncArgs,
+    #[command(flatten)]
+    pub outputs: OutputArgs,
+    /// Keyboard shortcuts of the window, from the `[keys]` table of the
+    /// settings.
+    #[arg(skip)]
+    pub keys: Keymap,
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CliCommand {
+    /// Renders part of the replay to a PNG sequence or an animated GIF,
+    /// without a window or a GPU.
+    Render(RenderArgs),
+    /// Writes the board at one moment of the replay to an SVG, with driver
+    /// labels and a legend.
+    Svg(SvgArgs),
+    /// Renders known boards and compares them with the checked-in
+    /// snapshots of the renderer.
+    Snapshots(SnapshotArgs),
+    /// Serves a CSV export or a generated race over HTTP the way OpenF1
+    /// does during a session, releasing samples as the session clock
+    /// reaches them, to try `--live` against.
+    MockApi(MockApiArgs),
+    /// Runs a local MQTT broker that publishes a CSV export or a generated
+    /// race as a live feed, to try `--mqtt` against.
+    MockBroker(MockBrokerArgs),
+    /// Plays the replay without a window, serving its frames and taking
+    /// commands over HTTP and WebSocket.
+    Headless(HeadlessArgs),
+    /// Stands in for a WLED controller, checking the DDP packets and JSON
+    /// requests `--wled` sends it.
+    MockWled(MockWledArgs),
+    /// Emulates the board's controller: takes the frames `--serial` sends
+    /// over TCP, or DDP over UDP, and shows what the LEDs would. Maps the
+    /// chain back to LEDs with `--chain`.
+    Emulator(EmulatorArgs),
+}
+
+/// Hardware the board is sent to, besides the window.
+#[derive(Debug, Clone, Args)]
+pub struct OutputArgs {
+    /// Sends every frame to a WLED controller, as `ddp://HOST[:PORT]` for
+    /// DDP over UDP or `json://HOST[:PORT]` for its JSON API. May be given
+    /// more than once.
+    #[arg(long, global = true, value_name = "TARGET")]
+    pub wled: Vec<WledTarget>,
+    /// A TOML file of `[[strip]]` tables describing how the LEDs are
+    /// wired: which LEDs each strip holds, its direction, where it starts
+    /// on the chain and which of its pixels to skip. Defaults to the
+    /// board's own wiring.
+    #[arg(long, global = true, value_name = "FILE")]
+    pub chain: Option<PathBuf>,
+    /// Segment the board is on, for the JSON API.
+    #[arg(long, global = true, default_value_t = 0)]
+    pub wled_segment: u8,
+    /// Pixel the first LED of the board is on: of the strip with DDP, of
+    /// the segment with the JSON API.
+    #[arg(long, global = true, default_value_t = 0)]
+    pub wled_offset: u32,
+    /// Channel order to send colors in, for controllers that pass them
+    /// through as sent.
+    #[arg(long, global = true, value_enum, default_value_t = ColorOrder::Rgb)]
+    pub wled_order: ColorOrder,
+    /// Sends every frame to the board's controller over a serial line: a
+    /// device such as `/dev/ttyUSB0`, `tcp://HOST:PORT` for the emulator,
+    /// or `null` to only simulate the line. Only the LEDs that changed are
+    /// sent when that is shorter.
+    #[arg(long, global = true, value_name = "PORT")]
+    pub serial: Option<LinkTarget>,
+    /// Baud rate of the serial line, which frames are dropped to keep to.
+    #[arg(long, global = true, default_value_t = 115_200, value_parser = clap::value_parser!(u32).range(1..))]
+    pub baud: u32,
+    /// Frames between two keyframes, which send every LED so the controller
+    /// recovers from anything it missed.
+    #[arg(long, global = true, default_value_t = 30)]
+    pub keyframe_every: u32,
+    /// Dims frames just enough for the board to draw at most this current,
+    /// in mA, on screen and in every output. The window starts with its
+    /// limiter on at this budget.
+    #[arg(long, global = true, value_name = "MA", value_parser = parse_positive)]
+    pub power_limit: Option<f32>,
+}
+
+impl OutputArgs {
+    /// The supply budget from `--power-limit`, off when it is not given.
+    pub fn power_budget(&self) -> PowerBudget {
+        match self.power_limit {
+            Some(limit_ma) => PowerBudget { enabled: true, limit_ma },
+            None => PowerBudget::default(),
+        }
+    }
+
+    /// How the LEDs are wired, from `--chain` or the board's own.
+    pub fn chain_map(&self) -> Result<ChainMap, String> {
+        match &self.chain {
+            Some(path) => ChainMap::from_file(path, Layout::new(true).max_led_number()),
+            None => Ok(ChainMap::default()),
+        }
+    }
+
+    pub fn wled_config(&self) -> WledConfig {
+        WledConfig {
+            segment: self.wled_segment,
+            offset: self.wled_offset,
+            order: self.wled_order,
+        }
+    }
+}
+
+/// How fast the replay plays.
+#[derive(Debug, Clone, Args)]
+pub struct PlaybackArgs {
+    /// How many times as fast as the session ran the replay starts out
+    /// playing, up to 64.
+    #[arg(long, global = true, default_value_t = 1.0)]
+    pub speed: f32,
+    /// Interval at which the board is rebuilt, in milliseconds.
+    #[arg(long, global = true, value_name = "MS", default_value_t = 1000, value_parser = clap::value_parser!(u64).range(1..))]
+    pub tick_ms: u64,
+}
+
+impl PlaybackArgs {
+    pub fn tick(&self) -> Duration {
+        Duration::from_millis(self.tick_ms)
+    }
+}
+
+/// Light or dark window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeChoice {
+    #[default]
+    Light,
+    Dark,
+}
+
+/// How the board is drawn.
+#[derive(Debug, Clone, Args)]
+pub struct DisplayArgs {
+    #[arg(long, global = true, value_enum, default_value_t = ThemeChoice::Light)]
+    pub theme: ThemeChoice,
+    /// A TOML file of `[[led]]` tables giving where each LED of the board
+    /// is drawn, as its `number` and `x` and `y` in track units. Defaults
+    /// to the board as built.
+    #[arg(long, global = true, value_name = "FILE")]
+    pub layout: Option<PathBuf>,
+    /// Edge length of an LED, in track units.
+    #[arg(long, global = true, default_value_t = LED_SIZE, value_parser = parse_positive)]
+    pub led_size: f32,
+    /// Space kept clear around the board in the window, in pixels.
+    #[arg(long, global = true, default_value_t = 50.0, value_parser = parse_non_negative)]
+    pub padding: f32,
+    /// Opens the window fullscreen with only the board, the session and the
+    /// lap count, for a screen beside the physical board. Escape leaves it.
+    #[arg(long, global = true)]
+    pub present: bool,
+}
+
+impl DisplayArgs {
+    /// Where the LEDs are drawn, from `--layout` or the board's own.
+    pub fn board_layout(&self) -> Result<BoardLayout, String> {
+        let layout = match &self.layout {
+            Some(path) => BoardLayout::from_file(path)?,
+            None => BoardLayout::default(),
+        };
+        Ok(layout.with_led_size(self.led_size))
+    }
+}
+
+/// Keeps several instances, each driving its own board, on the same replay
+/// time: one leads and the others follow its clock.
+#[derive(Debug, Clone, Args)]
+pub struct SyncArgs {
+    /// Leads the instances listening on these UDP addresses, as
+    /// `HOST:PORT`; a broadcast address reaches every follower on its
+    /// network.
+    #[arg(long, global = true, value_name = "ADDR", value_delimiter = ',', conflicts_with = "follow")]
+    pub lead: Vec<SocketAddr>,
+    /// Follows the instance that leads this UDP port.
+    #[arg(long, global = true, value_name = "PORT")]
+    pub follow: Option<u16>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct WindowArgs {
+    /// Serves the frames of the window and takes commands over HTTP and
+    /// WebSocket on this port, as `headless` does.
+    #[arg(long, value_name = "PORT")]
+    pub serve: Option<u16>,
+}
+
+/// Where the session data comes from: the OpenF1 API unless one of these
+/// is given.
+#[derive(Debug, Clone, Args)]
+pub struct SourceArgs {
+    /// Reads location samples from a CSV export instead of the OpenF1 API.
+    #[arg(long, global = true, conflicts_with = "simulate")]
+    pub csv: Option<PathBuf>,
+    /// Generates a race from this seed instead of downloading one.
+    #[arg(long, global = true, value_name = "SEED")]
+    pub simulate: Option<u64>,
+    /// Number of laps of a generated race.
+    #[arg(long, global = true, default_value_t = 10)]
+    pub laps: u32,
+    /// Follows a running session, polling for new samples instead of
+    /// downloading the session once.
+    #[arg(long, global = true, conflicts_with_all = ["csv", "simulate"])]
+    pub live: bool,
+    /// Follows a running session through the location messages an MQTT
+    /// broker pushes, given as `HOST[:PORT]`. With `--live`, the session so
+    /// far is downloaded first.
+    #[arg(long, global = true, value_name = "BROKER", conflicts_with_all = ["csv", "simulate"])]
+    pub mqtt: Option<Broker>,
+    #[arg(long, global = true, default_value = "v1/location")]
+    pub mqtt_topic: String,
+    /// Base URL of the OpenF1 API, or of a server standing in for it.
+    #[arg(long, global = true, default_value = "https://api.openf1.org")]
+    pub api_url: String,
+    /// OpenF1 session key; `latest` is the most recent session. Defaults
+    /// to the last session loaded.
+    #[arg(long, global = true, default_value = "9149")]
+    pub session: String,
+}
+
+impl SourceArgs {
+    pub fn is_offline(&self) -> bool {
+        self.csv.is_some() || self.simulate.is_some()
+    }
+
+    /// Whether the session is followed while it runs rather than loaded
+    /// once.
+    pub fn is_live(&self) -> bool {
+        self.live || self.mqtt.is_some()
+    }
+
+    pub fn api(&self) -> Api {
+        Api::new(&self.api_url, &self.session)
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct RenderArgs {
+    /// A `.gif` file to write, or a directory to write numbered PNGs to.
+    #[arg(short, long)]
+    pub output: PathBuf,
+    /// Replay time to start at, as `[[HH:]MM:]SS`.
+    #[arg(long, default_value = "0", value_parser = parse_elapsed)]
+    pub from: Duration,
+    /// Replay time to stop at, as `[[HH:]MM:]SS`. Defaults to ten seconds
+    /// after the start.
+    #[arg(long, value_parser = parse_elapsed)]
+    pub to: Option<Duration>,
+    #[arg(long, default_value_t = 10)]
+    pub fps: u32,
+    #[arg(long, default_value_t = 1280)]
+    pub width: u32,
+    #[arg(long, default_value_t = 720)]
+    pub height: u32,
+    /// Renders LEDs as the physical board shows them.
+    #[arg(long)]
+    pub physical: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct SvgArgs {
+    #[arg(short, long)]
+    pub output: PathBuf,
+    /// Replay time of the board, as `[[HH:]MM:]SS`.
+    #[arg(long, default_value = "0", value_parser = parse_elapsed)]
+    pub at: Duration,
+}
+
+fn parse_non_negative(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(number) if number.is_finite() && number >= 0.0 => Ok(number),
+        _ => Err(format!("`{}` is not a number of 0 or more", value)),
+    }
+}
+
+fn parse_positive(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(number) if number.is_finite() && number > 0.0 => Ok(number),
+        _ => Err(format!("`{}` is not a number greater than 0", value)),
+    }
+}
+
+/// Parses a replay time given as seconds, `MM:SS` or `HH:MM:SS`, where the
+/// seconds may have a fraction.
+fn parse_elapsed(value: &str) -> Result<Duration, String> {
+    let mut seconds = 0.0;
+    for part in value.split(':') {
+        let part: f64 = part
+            .parse()
+            .map_err(|_| format!("invalid time `{}`, expected [[HH:]MM:]SS", value))?;
+        seconds = seconds * 60.0 + part;
+    }
+    Duration::try_from_secs_f64(seconds).map_err(|e| e.to_string())
+}
+
+#[derive(Debug, Args)]
+pub struct SnapshotArgs {
+    /// Overwrites the snapshots with the current renders instead of
+    /// comparing against them.
+    #[arg(long)]
+    pub update: bool,
+    #[arg(long, default_value = "snapshots")]
+    pub dir: PathBuf,
+    /// Where renders that do not match their snapshot are written.
+    #[arg(long, default_value = "target/snapshot-failures")]
+    pub failures: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct MockApiArgs {
+    #[arg(long, default_value_t = 8000)]
+    pub port: u16,
+    /// Replay time the session clock starts at, as `[[HH:]MM:]SS`.
+    #[arg(long, default_value = "0", value_parser = parse_elapsed)]
+    pub from: Duration,
+    /// How long samples are held back after the session clock reaches
+    /// them, as `[[HH:]MM:]SS`, to simulate the delay of the feed.
+    #[arg(long, default_value = "0", value_parser = parse_elapsed)]
+    pub delay: Duration,
+}
+
+#[derive(Debug, Args)]
+pub struct MockBrokerArgs {
+    #[arg(long, default_value_t = 1883)]
+    pub port: u16,
+    #[arg(long, default_value = "v1/location")]
+    pub topic: String,
+    /// Replay time the session clock starts at, as `[[HH:]MM:]SS`.
+    #[arg(long, default_value = "0", value_parser = parse_elapsed)]
+    pub from: Duration,
+    /// How long samples are held back after the session clock reaches
+    /// them, as `[[HH:]MM:]SS`.
+    #[arg(long, default_value = "0", value_parser = parse_elapsed)]
+    pub delay: Duration,
+    /// Publishes every sample twice.
+    #[arg(long)]
+    pub duplicates: bool,
+    /// Drops all clients at this interval, as `[[HH:]MM:]SS`.
+    #[arg(long, value_parser = parse_elapsed)]
+    pub disconnect_every: Option<Duration>,
+}
+
+#[derive(Debug, Args)]
+pub struct HeadlessArgs {
+    #[arg(long, default_value_t = 8080)]
+    pub port: u16,
+    /// Waits for a `play` command instead of playing right away.
+    #[arg(long)]
+    pub paused: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct MockWledArgs {
+    #[arg(long, default_value_t = crate::wled::DDP_PORT)]
+    pub ddp_port: u16,
+    #[arg(long, default_value_t = 8088)]
+    pub http_port: u16,
+    /// Length of the strip, in pixels.
+    #[arg(long, default_value_t = 300)]
+    pub leds: u32,
+}
+
+#[derive(Debug, Args)]
+pub struct EmulatorArgs {
+    /// TCP port of the link, for `--serial tcp://127.0.0.1:PORT`.
+    #[arg(long, default_value_t = 7878)]
+    pub port: u16,
+    /// Also takes DDP on this UDP port, from pixel `--wled-offset` on.
+    #[arg(long, value_name = "PORT")]
+    pub ddp_port: Option<u16>,
+    /// Runs without a window, reporting what arrives every second.
+    #[arg(long)]
+    pub headless: bool,
+    /// Writes what the LEDs show to this PNG every second.
+    #[arg(long, requires = "headless")]
+    pub snapshot: Option<PathBuf>,
+}
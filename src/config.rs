@@ -0,0 +1,357 @@
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::cli::{Cli, ThemeChoice};
+use crate::keys::Keymap;
+use crate::server::MAX_SPEED;
+use crate::wled::ColorOrder;
+
+/// Directory of the settings, and of what is remembered between runs.
+const DIR: &str = "f1-led-circuit";
+const CONFIG_FILE: &str = "config.toml";
+const STATE_FILE: &str = "state.toml";
+
+/// Settings read from a TOML file, one table per group of flags, with keys
+/// named after the flags. A flag given on the command line takes precedence
+/// over its key. The `keys` table binds keys of the window to actions, such
+/// as `"shift+right" = "seek:60"`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    source: SourceConfig,
+    playback: PlaybackConfig,
+    outputs: OutputConfig,
+    display: DisplayConfig,
+    keys: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SourceConfig {
+    csv: Option<PathBuf>,
+    simulate: Option<u64>,
+    laps: Option<u32>,
+    live: Option<bool>,
+    mqtt: Option<String>,
+    mqtt_topic: Option<String>,
+    api_url: Option<String>,
+    session: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PlaybackConfig {
+    speed: Option<f32>,
+    tick_ms: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct OutputConfig {
+    wled: Option<Vec<String>>,
+    chain: Option<PathBuf>,
+    wled_segment: Option<u8>,
+    wled_offset: Option<u32>,
+    wled_order: Option<ColorOrder>,
+    serial: Option<String>,
+    baud: Option<u32>,
+    keyframe_every: Option<u32>,
+    power_limit: Option<f32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct DisplayConfig {
+    theme: Option<ThemeChoice>,
+    layout: Option<PathBuf>,
+    led_size: Option<f32>,
+    padding: Option<f32>,
+    present: Option<bool>,
+}
+
+/// What is remembered from one run to the next.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    /// The last OpenF1 session loaded.
+    session: Option<String>,
+}
+
+/// Parses the command line, and fills in what it leaves out from the config
+/// file, then from the last session, then from the defaults.
+pub fn parse() -> Result<Cli, String> {
+    let matches = Cli::command().get_matches();
+    let path = match matches.get_one::<PathBuf>("config") {
+        Some(path) => Some(path.clone()),
+        None => default_dir().map(|dir| dir.join(CONFIG_FILE)).filter(|path| path.exists()),
+    };
+    let config = path.map(|path| Config::read(&path)).transpose()?;
+    merge(&matches, config.as_ref(), read_state())
+}
+
+/// The settings of the command line in `matches`, with what it leaves out
+/// taken from `config`, then from `state`.
+fn merge(matches: &ArgMatches, config: Option<&(PathBuf, Config)>, state: State) -> Result<Cli, String> {
+    let mut cli = Cli::from_arg_matches(matches).unwrap_or_else(|e| e.exit());
+    if let Some((path, config)) = config {
+        config.apply(&mut cli, matches).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    if !given(matches, "session") && config.is_none_or(|(_, config)| config.source.session.is_none()) {
+        if let Some(session) = state.session {
+            cli.source.session = session;
+        }
+    }
+    check(&cli)?;
+    Ok(cli)
+}
+
+/// Checks the settings that can come from the command line or the file
+/// alike, once they are merged.
+fn check(cli: &Cli) -> Result<(), String> {
+    let speed = cli.playback.speed;
+    if !(speed.is_finite() && (0.0..=MAX_SPEED).contains(&speed)) {
+        return Err(format!("the speed is {}, not a number from 0 to {}, see --speed or playback.speed", speed, MAX_SPEED));
+    }
+    Ok(())
+}
+
+impl Config {
+    /// Reads the file at `path`, with the paths in it made relative to its
+    /// directory.
+    fn read(path: &Path) -> Result<(PathBuf, Self), String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut config: Config = toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let files = [&mut config.source.csv, &mut config.outputs.chain, &mut config.display.layout];
+        for file in files.into_iter().flatten() {
+            *file = dir.join(&*file);
+        }
+        Ok((path.to_path_buf(), config))
+    }
+
+    /// Sets every setting of `cli` that is in the file and was not given on
+    /// the command line.
+    fn apply(&self, cli: &mut Cli, matches: &ArgMatches) -> Result<(), String> {
+        self.check()?;
+        let set = |id: &str| !given(matches, id);
+
+        // The source is one choice: picking one on the command line drops
+        // the file's rather than conflicting with it.
+        let source = &self.source;
+        if ["csv", "simulate", "live", "mqtt"].iter().all(|id| set(id)) {
+            cli.source.csv = source.csv.clone();
+            cli.source.simulate = source.simulate;
+            cli.source.live = source.live.unwrap_or_default();
+            if let Some(broker) = &source.mqtt {
+                cli.source.mqtt = Some(parse_key("source.mqtt", broker)?);
+            }
+        }
+        override_with(set("laps"), &mut cli.source.laps, source.laps);
+        override_with(set("mqtt_topic"), &mut cli.source.mqtt_topic, source.mqtt_topic.clone());
+        override_with(set("api_url"), &mut cli.source.api_url, source.api_url.clone());
+        override_with(set("session"), &mut cli.source.session, source.session.clone());
+
+        override_with(set("speed"), &mut cli.playback.speed, self.playback.speed);
+        override_with(set("tick_ms"), &mut cli.playback.tick_ms, self.playback.tick_ms);
+
+        let outputs = &self.outputs;
+        if let (true, Some(targets)) = (set("wled"), &outputs.wled) {
+            cli.outputs.wled = targets
+                .iter()
+                .map(|target| parse_key("outputs.wled", target))
+                .collect::<Result<_, _>>()?;
+        }
+        if let (true, Some(target)) = (set("serial"), &outputs.serial) {
+            cli.outputs.serial = Some(parse_key("outputs.serial", target)?);
+        }
+        if set("chain") && outputs.chain.is_some() {
+            cli.outputs.chain = outputs.chain.clone();
+        }
+        override_with(set("wled_segment"), &mut cli.outputs.wled_segment, outputs.wled_segment);
+        override_with(set("wled_offset"), &mut cli.outputs.wled_offset, outputs.wled_offset);
+        override_with(set("wled_order"), &mut cli.outputs.wled_order, outputs.wled_order);
+        override_with(set("baud"), &mut cli.outputs.baud, outputs.baud);
+        override_with(set("keyframe_every"), &mut cli.outputs.keyframe_every, outputs.keyframe_every);
+        if set("power_limit") && outputs.power_limit.is_some() {
+            cli.outputs.power_limit = outputs.power_limit;
+        }
+
+        let display = &self.display;
+        override_with(set("theme"), &mut cli.display.theme, display.theme);
+        if set("layout") && display.layout.is_some() {
+            cli.display.layout = display.layout.clone();
+        }
+        override_with(set("led_size"), &mut cli.display.led_size, display.led_size);
+        override_with(set("padding"), &mut cli.display.padding, display.padding);
+        override_with(set("present"), &mut cli.display.present, display.present);
+
+        cli.keys = Keymap::default().with(&self.keys).map_err(|e| format!("keys: {}", e))?;
+        Ok(())
+    }
+
+    /// Checks what the flags' parsers would have, for the settings the file
+    /// gives.
+    fn check(&self) -> Result<(), String> {
+        let source = &self.source;
+        if source.csv.is_some() && source.simulate.is_some() {
+            return Err("source.csv and source.simulate cannot both be set".to_string());
+        }
+        let live = source.live == Some(true) || source.mqtt.is_some();
+        if live && (source.csv.is_some() || source.simulate.is_some()) {
+            return Err("source.live and source.mqtt follow an OpenF1 session, not source.csv or source.simulate".to_string());
+        }
+        if self.playback.tick_ms == Some(0) {
+            return Err("playback.tick_ms has to be at least 1".to_string());
+        }
+        if self.outputs.baud == Some(0) {
+            return Err("outputs.baud has to be at least 1".to_string());
+        }
+        if let Some(limit) = self.outputs.power_limit.filter(|limit| !(limit.is_finite() && *limit > 0.0)) {
+            return Err(format!("outputs.power_limit is {}, not a number greater than 0", limit));
+        }
+        if let Some(size) = self.display.led_size.filter(|size| !(size.is_finite() && *size > 0.0)) {
+            return Err(format!("display.led_size is {}, not a number greater than 0", size));
+        }
+        if let Some(padding) = self.display.padding.filter(|padding| !(padding.is_finite() && *padding >= 0.0)) {
+            return Err(format!("display.padding is {}, not a number of 0 or more", padding));
+        }
+        Ok(())
+    }
+}
+
+/// Whether the flag `id` was given on the command line, rather than left
+/// at its default.
+fn given(matches: &ArgMatches, id: &str) -> bool {
+    matches.value_source(id) == Some(ValueSource::CommandLine)
+}
+
+fn override_with<T>(set: bool, setting: &mut T, value: Option<T>) {
+    if let (true, Some(value)) = (set, value) {
+        *setting = value;
+    }
+}
+
+fn parse_key<T: FromStr<Err = String>>(key: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|e| format!("{}: {}", key, e))
+}
+
+/// Where the settings are looked for when `--config` is not given.
+fn default_dir() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join(DIR))
+}
+
+fn read_state() -> State {
+    let Some(path) = default_dir().map(|dir| dir.join(STATE_FILE)) else {
+        return State::default();
+    };
+    // A missing or damaged state file only means nothing is remembered.
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|text| toml::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+/// Remembers `session` as the last one loaded, for the next run to start
+/// from.
+pub fn remember_session(session: &str) {
+    let Some(dir) = default_dir() else {
+        return;
+    };
+    let state = State {
+        session: Some(session.to_string()),
+    };
+    let result = std::fs::create_dir_all(&dir)
+        .and_then(|()| std::fs::write(dir.join(STATE_FILE), toml::to_string(&state).unwrap_or_default()));
+    if let Err(e) = result {
+        eprintln!("Failed to remember session {}: {}", session, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Merges the flags in `args` with a config file of `config` and a
+    /// remembered `session`.
+    fn settings(args: &[&str], config: Option<&str>, session: Option<&str>) -> Result<Cli, String> {
+        let matches = Cli::command()
+            .try_get_matches_from(std::iter::once("f1-led-circuit").chain(args.iter().copied()))
+            .map_err(|e| e.to_string())?;
+        let config = config
+            .map(|text| toml::from_str(text).map(|config| (PathBuf::from("config.toml"), config)))
+            .transpose()
+            .map_err(|e| e.to_string())?;
+        let state = State {
+            session: session.map(String::from),
+        };
+        merge(&matches, config.as_ref(), state)
+    }
+
+    fn session(args: &[&str], config: Option<&str>, state: Option<&str>) -> String {
+        settings(args, config, state).unwrap().source.session
+    }
+
+    #[test]
+    fn command_line_beats_file_beats_state_beats_default() {
+        let file = Some("[source]\nsession = \"file\"");
+        assert_eq!(session(&[], None, None), "9149");
+        assert_eq!(session(&[], None, Some("state")), "state");
+        assert_eq!(session(&[], file, Some("state")), "file");
+        assert_eq!(session(&["--session", "cli"], file, Some("state")), "cli");
+        // A file without a session leaves the remembered one.
+        assert_eq!(session(&[], Some("[playback]\nspeed = 2.0"), Some("state")), "state");
+
+        let speed = |args: &[&str]| settings(args, Some("[playback]\nspeed = 2.0"), None).unwrap().playback.speed;
+        assert_eq!(speed(&[]), 2.0);
+        assert_eq!(speed(&["--speed", "4"]), 4.0);
+        assert_eq!(settings(&[], None, None).unwrap().playback.speed, 1.0);
+    }
+
+    #[test]
+    fn a_source_on_the_command_line_replaces_the_files() {
+        let file = Some("[source]\nsimulate = 7\nlaps = 3");
+        let cli = settings(&[], file, None).unwrap();
+        assert_eq!((cli.source.simulate, cli.source.laps), (Some(7), 3));
+
+        let cli = settings(&["--live"], file, None).unwrap();
+        assert!(cli.source.live);
+        assert_eq!(cli.source.simulate, None);
+        // Only the source itself is one choice; the laps still apply.
+        assert_eq!(cli.source.laps, 3);
+    }
+
+    #[test]
+    fn invalid_files_are_rejected() {
+        let error = |config: &str| settings(&[], Some(config), None).unwrap_err();
+        let cases = [
+            ("[source]\ncsv = \"a.csv\"\nsimulate = 1", "source.csv and source.simulate"),
+            ("[source]\nlive = true\ncsv = \"a.csv\"", "source.live and source.mqtt"),
+            ("[source]\nmqtt = \"localhost\"\nsimulate = 1", "source.live and source.mqtt"),
+            ("[playback]\ntick_ms = 0", "playback.tick_ms"),
+            ("[outputs]\nbaud = 0", "outputs.baud"),
+            ("[outputs]\npower_limit = -1.0", "outputs.power_limit"),
+            ("[display]\nled_size = 0.0", "display.led_size"),
+            ("[display]\npadding = -1.0", "display.padding"),
+            ("[outputs]\nwled = [\"\"]", "outputs.wled"),
+            ("[keys]\nx = \"fly\"", "keys"),
+        ];
+        for (config, expected) in cases {
+            let error = error(config);
+            assert!(error.starts_with("config.toml: ") && error.contains(expected), "{}: {}", config, error);
+        }
+        assert!(settings(&[], Some("[unknown]"), None).is_err());
+    }
+
+    #[test]
+    fn speeds_are_checked_once_merged() {
+        let error = settings(&[], Some("[playback]\nspeed = 100.0"), None).unwrap_err();
+        assert!(error.contains("playback.speed"), "{}", error);
+        assert!(settings(&["--speed", "100"], None, None).is_err());
+        assert!(settings(&["--speed=-1"], None, None).is_err());
+        // The command line wins before the check, so it can fix the file.
+        assert!(settings(&["--speed", "8"], Some("[playback]\nspeed = 100.0"), None).is_ok());
+    }
+}
@@ -127,3 +127,7 @@ pub const DRIVERS: &[DriverInfo] = &[
         color: (255, 135, 0),
     },
 ];
+
+pub fn find_driver(number: u32) -> Option<&'static DriverInfo> {
+    DRIVERS.iter().find(|d| d.number == number)
+}
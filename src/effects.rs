@@ -0,0 +1,112 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use crate::led_data::{sector_for_led, UpdateFrame, LED_DATA};
+use crate::race_control::{SafetyCar, SectorFlag, TrackStatus};
+
+const YELLOW: (u8, u8, u8) = (90, 70, 0);
+const DOUBLE_YELLOW: (u8, u8, u8) = (200, 160, 0);
+const RED: (u8, u8, u8) = (120, 0, 0);
+const SAFETY_CAR: (u8, u8, u8) = (255, 140, 0);
+const VIRTUAL_SAFETY_CAR: (u8, u8, u8) = (120, 60, 0);
+const CHEQUERED: (u8, u8, u8) = (255, 255, 255);
+
+/// Number of LEDs lit by the safety car chase light.
+const CHASE_LENGTH: u64 = 4;
+/// Time the chase light takes to advance by one LED.
+const CHASE_STEP_MS: u64 = 100;
+/// Time the VSC pattern takes to swap between odd and even LEDs.
+const VSC_BLINK_MS: u64 = 1000;
+/// Time the chequered pattern takes to advance by one LED.
+const CHEQUERED_STEP_MS: u64 = 250;
+const CHEQUERED_ANIMATION: Duration = Duration::from_secs(10);
+
+/// Composites the race control effects layer under the drivers of `frame`.
+///
+/// Driver LEDs always win: effects only light LEDs that no car is on. The
+/// animation phase is taken from the frame timestamp so that replaying the
+/// same moment always renders the same board.
+pub fn composite(frame: &UpdateFrame, status: &TrackStatus) -> UpdateFrame {
+    let mut update_frame = frame.clone();
+
+    let occupied: HashSet<u32> = frame.led_states.iter().map(|(num, _)| *num).collect();
+
+    for led in LED_DATA {
+        if occupied.contains(&led.led_number) {
+            continue;
+        }
+        if let Some(color) = effect_color(led.led_number, status, frame.timestamp) {
+            update_frame.set_led_state(led.led_number, color);
+        }
+    }
+
+    update_frame
+}
+
+fn effect_color(led_number: u32, status: &TrackStatus, timestamp: u64) -> Option<(u8, u8, u8)> {
+    let led_count = LED_DATA.len() as u64;
+    let index = led_number as u64;
+
+    if let Some(chequered_for) = status.chequered_for {
+        if chequered_for < CHEQUERED_ANIMATION {
+            let step = timestamp / CHEQUERED_STEP_MS;
+            return ((index + step) / 2).is_multiple_of(2).then_some(CHEQUERED);
+        }
+        return None;
+    }
+
+    if status.red_flag {
+        return Some(RED);
+    }
+
+    match status.safety_car {
+        Some(SafetyCar::Deployed) => {
+            let head = (timestamp / CHASE_STEP_MS) % led_count;
+            let behind = (head + led_count - index % led_count) % led_count;
+            return (behind < CHASE_LENGTH).then_some(SAFETY_CAR);
+        }
+        Some(SafetyCar::Virtual) => {
+            let phase = timestamp / VSC_BLINK_MS;
+            return (index + phase).is_multiple_of(2).then_some(VIRTUAL_SAFETY_CAR);
+        }
+        None => {}
+    }
+
+    let sector = sector_for_led(led_number)?;
+    match status.sector_flags.get(&sector)? {
+        SectorFlag::Yellow => Some(YELLOW),
+        SectorFlag::DoubleYellow => Some(DOUBLE_YELLOW),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::led_data::LED_SECTORS;
+
+    #[test]
+    fn yellow_flags_light_their_sector_around_the_cars() {
+        let sector = &LED_SECTORS[3];
+        let mut status = TrackStatus::default();
+        status.sector_flags.insert(sector.sector, SectorFlag::Yellow);
+        let car = (sector.first_led, (0, 0, 255));
+        let frame = UpdateFrame {
+            timestamp: 0,
+            led_states: vec![car],
+        };
+
+        let colors = composite(&frame, &status).led_colors(LED_DATA.len() as u32);
+        assert_eq!(colors[car.0 as usize], Some(car.1));
+        for led_number in sector.first_led + 1..=sector.last_led {
+            assert_eq!(colors[led_number as usize], Some(YELLOW), "LED {}", led_number);
+        }
+        let lit = colors.iter().flatten().count() as u32;
+        assert_eq!(lit, sector.last_led - sector.first_led + 1);
+    }
+
+    #[test]
+    fn green_track_has_no_effects() {
+        let frame = UpdateFrame::new(1234);
+        assert!(composite(&frame, &TrackStatus::default()).led_states.is_empty());
+    }
+}
@@ -0,0 +1,564 @@
+use iced::futures::SinkExt;
+use iced::subscription::{self, Subscription};
+use iced::widget::canvas::{Cache, Canvas};
+use iced::widget::{column, container, text};
+use iced::{executor, Application, Command, Element, Length, Settings, Theme};
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::watch;
+
+use crate::chain::ChainMap;
+use crate::graph::Graph;
+use crate::layout::Layout;
+use crate::led_data::UpdateFrame;
+use crate::link::{Decoded, Decoder, Packet};
+use crate::physical::LedModel;
+use crate::wled;
+use crate::Message;
+
+/// Interval at which the headless emulator reports what it received and
+/// writes its snapshot.
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+const SNAPSHOT_WIDTH: u32 = 1280;
+const SNAPSHOT_HEIGHT: u32 = 720;
+
+/// What the firmware received since it started, and what it could not
+/// use.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FirmwareStats {
+    pub keyframes: u32,
+    pub deltas: u32,
+    pub ddp_frames: u32,
+    pub bad_crc: u32,
+    pub malformed: u32,
+    pub garbage_bytes: usize,
+    /// Deltas that build on a frame the firmware is not showing, which it
+    /// skips until the next keyframe.
+    pub stale_deltas: u32,
+    /// Pixels addressed past the end of the chain.
+    pub out_of_range: u32,
+    /// Keyframes with more or fewer pixels than the chain has.
+    pub wrong_length: u32,
+}
+
+impl fmt::Display for FirmwareStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} keyframes, {} deltas, {} DDP frames | {} CRC errors, {} malformed, {} bytes of garbage, \
+             {} stale deltas, {} pixels out of range, {} keyframes of the wrong length",
+            self.keyframes,
+            self.deltas,
+            self.ddp_frames,
+            self.bad_crc,
+            self.malformed,
+            self.garbage_bytes,
+            self.stale_deltas,
+            self.out_of_range,
+            self.wrong_length
+        )
+    }
+}
+
+/// The board's receiving firmware: a chain of pixels that link packets
+/// and DDP write to.
+#[derive(Debug, Clone)]
+pub struct Firmware {
+    shown: Vec<(u8, u8, u8)>,
+    /// DDP pixels received since the last push.
+    ddp: Vec<(u8, u8, u8)>,
+    ddp_offset: u32,
+    /// Sequence number of the frame shown, which deltas have to build on.
+    showing: Option<u16>,
+    stats: FirmwareStats,
+}
+
+impl Firmware {
+    /// Firmware driving `pixels` pixels, which take DDP from pixel
+    /// `ddp_offset` of the strip on.
+    pub fn new(pixels: u32, ddp_offset: u32) -> Self {
+        Self {
+            shown: vec![(0, 0, 0); pixels as usize],
+            ddp: vec![(0, 0, 0); pixels as usize],
+            ddp_offset,
+            showing: None,
+            stats: FirmwareStats::default(),
+        }
+    }
+
+    pub fn pixels(&self) -> &[(u8, u8, u8)] {
+        &self.shown
+    }
+
+    pub fn stats(&self) -> &FirmwareStats {
+        &self.stats
+    }
+
+    /// Takes in what the link decoder found. Returns whether the LEDs
+    /// changed.
+    fn decoded(&mut self, decoded: Decoded) -> bool {
+        match decoded {
+            Decoded::Packet(packet) => return self.apply(packet),
+            Decoded::Garbage(bytes) => self.stats.garbage_bytes += bytes,
+            Decoded::BadCrc => self.stats.bad_crc += 1,
+            Decoded::Malformed(e) => {
+                eprintln!("Malformed packet: {}", e);
+                self.stats.malformed += 1;
+            }
+        }
+        false
+    }
+
+    fn apply(&mut self, packet: Packet) -> bool {
+        match packet {
+            Packet::Keyframe { seq, pixels } => {
+                if pixels.len() != self.shown.len() {
+                    self.stats.wrong_length += 1;
+                }
+                // Like the firmware, writes what fits and leaves the rest.
+                for (shown, pixel) in self.shown.iter_mut().zip(pixels) {
+                    *shown = pixel;
+                }
+                self.showing = Some(seq);
+                self.stats.keyframes += 1;
+            }
+            Packet::Delta { seq, base, changes } => {
+                if self.showing != Some(base) {
+                    self.stats.stale_deltas += 1;
+                    return false;
+                }
+                for (index, color) in changes {
+                    match self.shown.get_mut(index as usize) {
+                        Some(pixel) => *pixel = color,
+                        None => self.stats.out_of_range += 1,
+                    }
+                }
+                self.showing = Some(seq);
+                self.stats.deltas += 1;
+            }
+        }
+        true
+    }
+
+    /// Takes in a DDP datagram. Returns whether the LEDs changed.
+    fn ddp(&mut self, datagram: &[u8]) -> bool {
+        let packet = match wled::parse_ddp(datagram) {
+            Ok(packet) => packet,
+            Err(e) => {
+                eprintln!("Malformed DDP packet: {}", e);
+                self.stats.malformed += 1;
+                return false;
+            }
+        };
+        let first = packet.offset / 3;
+        for (i, pixel) in packet.data.chunks(3).enumerate() {
+            // Pixels before the chain are another device's.
+            let Some(index) = (first + i as u32).checked_sub(self.ddp_offset) else {
+                continue;
+            };
+            match self.ddp.get_mut(index as usize) {
+                Some(slot) => *slot = (pixel[0], pixel[1], pixel[2]),
+                None => self.stats.out_of_range += 1,
+            }
+        }
+        if !packet.push {
+            return false;
+        }
+        self.shown.clone_from(&self.ddp);
+        self.stats.ddp_frames += 1;
+        true
+    }
+}
+
+/// Where the emulator takes frames, and the chain it drives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Listen {
+    /// TCP port of the link protocol, which `--serial tcp://` sends to.
+    pub port: u16,
+    pub ddp_port: Option<u16>,
+    pub pixels: u32,
+    pub ddp_offset: u32,
+}
+
+/// The sockets of a running emulator.
+pub struct Receiver {
+    listen: Listen,
+    link: TcpListener,
+    ddp: Option<UdpSocket>,
+}
+
+impl Receiver {
+    pub async fn bind(listen: Listen) -> Result<Self, String> {
+        let link = TcpListener::bind(("127.0.0.1", listen.port))
+            .await
+            .map_err(|e| format!("failed to listen on port {}: {}", listen.port, e))?;
+        let ddp = match listen.ddp_port {
+            Some(port) => Some(
+                UdpSocket::bind(("127.0.0.1", port))
+                    .await
+                    .map_err(|e| format!("failed to listen on UDP port {}: {}", port, e))?,
+            ),
+            None => None,
+        };
+        eprintln!("Emulating the controller with {} pixels, link on tcp://127.0.0.1:{}", listen.pixels, listen.port);
+        if let Some(port) = listen.ddp_port {
+            eprintln!("Taking DDP on udp://127.0.0.1:{}", port);
+        }
+        Ok(Self { listen, link, ddp })
+    }
+
+    /// Runs the firmware on what arrives, handing it to `shown` every time
+    /// the LEDs change. One link is taken at a time, as a serial port does;
+    /// a new one replaces the last.
+    pub async fn run(self, shown: watch::Sender<Firmware>) {
+        let mut firmware = Firmware::new(self.listen.pixels, self.listen.ddp_offset);
+        let mut decoder = Decoder::new(self.listen.pixels);
+        let mut client: Option<TcpStream> = None;
+        let mut buffer = [0; 4096];
+        let mut datagram = [0; 2048];
+
+        loop {
+            let changed = tokio::select! {
+                accepted = self.link.accept() => match accepted {
+                    Ok((stream, address)) => {
+                        eprintln!("Link connected from {}", address);
+                        client = Some(stream);
+                        decoder.reset();
+                        false
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to accept a link: {}", e);
+                        false
+                    }
+                },
+                read = async {
+                    match &mut client {
+                        Some(stream) => stream.read(&mut buffer).await,
+                        None => std::future::pending().await,
+                    }
+                } => match read {
+                    Ok(0) | Err(_) => {
+                        eprintln!("Link disconnected");
+                        client = None;
+                        false
+                    }
+                    Ok(length) => {
+                        decoder.push(&buffer[..length]);
+                        let mut changed = false;
+                        while let Some(decoded) = decoder.next() {
+                            changed |= firmware.decoded(decoded);
+                        }
+                        changed
+                    }
+                },
+                received = async {
+                    match &self.ddp {
+                        Some(socket) => socket.recv(&mut datagram).await,
+                        None => std::future::pending().await,
+                    }
+                } => match received {
+                    Ok(length) => firmware.ddp(&datagram[..length]),
+                    Err(e) => {
+                        eprintln!("Failed to receive DDP: {}", e);
+                        false
+                    }
+                },
+            };
+            // Rejected packets count too.
+            if changed || shown.borrow().stats != firmware.stats {
+                shown.send_replace(firmware.clone());
+            }
+        }
+    }
+}
+
+/// The board as the emulated LEDs show it, with `padding` pixels around it.
+fn graph<'a>(
+    layout: &'a Layout,
+    padding: f32,
+    track_cache: &'a Cache,
+    frame: &'a UpdateFrame,
+    hovered: Option<u32>,
+) -> Graph<'a> {
+    Graph {
+        layout,
+        track_cache,
+        update_frame: Some(frame),
+        positions: &[],
+        reference_lap: None,
+        physical: Some(LedModel::default()),
+        hovered,
+        follow: None,
+        padding,
+    }
+}
+
+/// Runs the emulator without a window: reports what it received every
+/// second, and writes what the LEDs show on `layout` to `snapshot` if
+/// given.
+pub fn run_headless(
+    listen: Listen,
+    chain: ChainMap,
+    layout: Layout,
+    padding: f32,
+    snapshot: Option<&Path>,
+) -> Result<(), String> {
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+    runtime.block_on(async {
+        let receiver = Receiver::bind(listen).await?;
+        let (shown, received) = watch::channel(Firmware::new(listen.pixels, listen.ddp_offset));
+        tokio::spawn(receiver.run(shown));
+
+        let track_cache = Cache::new();
+        let mut reported = FirmwareStats::default();
+        let mut report = tokio::time::interval(REPORT_INTERVAL);
+        loop {
+            report.tick().await;
+            let firmware = received.borrow().clone();
+            if firmware.stats == reported {
+                continue;
+            }
+            reported = firmware.stats.clone();
+            let frame = chain.frame(firmware.pixels());
+            eprintln!("{} LEDs lit | {}", frame.led_states.len(), reported);
+
+            if let Some(path) = snapshot {
+                let caption: Vec<String> = reported.to_string().split(" | ").map(String::from).collect();
+                let graph = graph(&layout, padding, &track_cache, &frame, None);
+                let pixmap = crate::render::render(&graph, &caption, SNAPSHOT_WIDTH, SNAPSHOT_HEIGHT)?;
+                pixmap.save_png(path).map_err(|e| e.to_string())?;
+            }
+        }
+    })
+}
+
+/// Runs the emulator in a window of its own, drawing the board on
+/// `layout`.
+pub fn run_window(listen: Listen, chain: ChainMap, layout: Layout, padding: f32) -> Result<(), String> {
+    EmulatorWindow::run(Settings::with_flags((listen, chain, layout, padding))).map_err(|e| e.to_string())
+}
+
+struct EmulatorWindow {
+    listen: Listen,
+    chain: ChainMap,
+    layout: Layout,
+    padding: f32,
+    track_cache: Cache,
+    firmware: Firmware,
+    frame: UpdateFrame,
+    hovered: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+enum EmulatorMessage {
+    Shown(Firmware),
+    Hovered(Option<u32>),
+    /// What the board canvas sends that the emulator has no use for.
+    Ignored,
+}
+
+impl Application for EmulatorWindow {
+    type Message = EmulatorMessage;
+    type Theme = Theme;
+    type Executor = executor::Default;
+    type Flags = (Listen, ChainMap, Layout, f32);
+
+    fn new((listen, chain, layout, padding): Self::Flags) -> (Self, Command<EmulatorMessage>) {
+        (
+            Self {
+                listen,
+                chain,
+                layout,
+                padding,
+                track_cache: Cache::new(),
+                firmware: Firmware::new(listen.pixels, listen.ddp_offset),
+                frame: UpdateFrame::new(0),
+                hovered: None,
+            },
+            Command::none(),
+        )
+    }
+
+    fn title(&self) -> String {
+        String::from("LED controller emulator")
+    }
+
+    fn update(&mut self, message: EmulatorMessage) -> Command<EmulatorMessage> {
+        match message {
+            EmulatorMessage::Shown(firmware) => {
+                self.frame = self.chain.frame(firmware.pixels());
+                self.firmware = firmware;
+            }
+            EmulatorMessage::Hovered(hovered) => self.hovered = hovered,
+            EmulatorMessage::Ignored => {}
+        }
+        Command::none()
+    }
+
+    fn subscription(&self) -> Subscription<EmulatorMessage> {
+        let listen = self.listen;
+        subscription::channel(listen, 16, move |mut output| async move {
+            let mut received = match Receiver::bind(listen).await {
+                Ok(receiver) => {
+                    let (shown, received) = watch::channel(Firmware::new(listen.pixels, listen.ddp_offset));
+                    tokio::spawn(receiver.run(shown));
+                    Some(received)
+                }
+                Err(e) => {
+                    eprintln!("Failed to start the emulator: {}", e);
+                    None
+                }
+            };
+
+            loop {
+                let Some(receiver) = &mut received else {
+                    std::future::pending::<()>().await;
+                    continue;
+                };
+                if receiver.changed().await.is_err() {
+                    received = None;
+                    continue;
+                }
+                let firmware = receiver.borrow_and_update().clone();
+                let _ = output.send(EmulatorMessage::Shown(firmware)).await;
+            }
+        })
+    }
+
+    fn view(&self) -> Element<'_, EmulatorMessage> {
+        let board: Element<Message> = Canvas::new(graph(
+            &self.layout,
+            self.padding,
+            &self.track_cache,
+            &self.frame,
+            self.hovered,
+        ))
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .into();
+        let board = board.map(|message| match message {
+            Message::LedHovered(hovered) => EmulatorMessage::Hovered(hovered),
+            _ => EmulatorMessage::Ignored,
+        });
+
+        let hovered = self.hovered.map(|led_number| match self.chain.index(led_number) {
+            Some(index) => {
+                let (r, g, b) = self.firmware.pixels().get(index as usize).copied().unwrap_or_default();
+                format!("LED {}: chain pixel {}, #{:02X}{:02X}{:02X}", led_number, index, r, g, b)
+            }
+            None => format!("LED {}: not wired", led_number),
+        });
+
+        container(
+            column![
+                board,
+                text(self.firmware.stats().to_string()).size(14),
+                text(hovered.unwrap_or_default()).size(14),
+            ]
+            .spacing(5),
+        )
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .padding(10)
+        .into()
+    }
+
+    fn theme(&self) -> Theme {
+        Theme::Dark
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: (u8, u8, u8) = (255, 0, 0);
+    const BLUE: (u8, u8, u8) = (0, 0, 255);
+
+    fn keyframe(seq: u16, pixels: usize) -> Packet {
+        Packet::Keyframe {
+            seq,
+            pixels: vec![RED; pixels],
+        }
+    }
+
+    fn delta(seq: u16, base: u16, changes: &[(u16, (u8, u8, u8))]) -> Packet {
+        Packet::Delta {
+            seq,
+            base,
+            changes: changes.to_vec(),
+        }
+    }
+
+    #[test]
+    fn keyframes_set_every_pixel() {
+        let mut firmware = Firmware::new(4, 0);
+        assert!(firmware.apply(keyframe(1, 4)));
+        assert_eq!(firmware.pixels(), &[RED; 4]);
+
+        // A keyframe for a longer chain sets what fits.
+        assert!(firmware.apply(Packet::Keyframe {
+            seq: 2,
+            pixels: vec![BLUE; 6],
+        }));
+        assert_eq!(firmware.pixels(), &[BLUE; 4]);
+        assert_eq!(firmware.stats().keyframes, 2);
+        assert_eq!(firmware.stats().wrong_length, 1);
+    }
+
+    #[test]
+    fn deltas_build_on_the_frame_shown() {
+        let mut firmware = Firmware::new(4, 0);
+        firmware.apply(keyframe(1, 4));
+        assert!(firmware.apply(delta(2, 1, &[(2, BLUE)])));
+        assert!(firmware.apply(delta(3, 2, &[(0, BLUE), (9, BLUE)])));
+        assert_eq!(firmware.pixels(), &[BLUE, RED, BLUE, RED]);
+        assert_eq!(firmware.stats().deltas, 2);
+        assert_eq!(firmware.stats().out_of_range, 1);
+    }
+
+    #[test]
+    fn deltas_after_a_missed_frame_wait_for_a_keyframe() {
+        let mut firmware = Firmware::new(4, 0);
+        // Nothing is shown yet for a delta to build on.
+        assert!(!firmware.apply(delta(1, 0, &[(0, BLUE)])));
+        firmware.apply(keyframe(5, 4));
+
+        // Frame 6 was lost, so neither it nor what follows applies.
+        assert!(!firmware.apply(delta(7, 6, &[(1, BLUE)])));
+        assert!(!firmware.apply(delta(8, 7, &[(2, BLUE)])));
+        assert_eq!(firmware.pixels(), &[RED; 4]);
+        assert_eq!(firmware.stats().stale_deltas, 3);
+
+        assert!(firmware.apply(keyframe(9, 4)));
+        assert!(firmware.apply(delta(10, 9, &[(3, BLUE)])));
+        assert_eq!(firmware.pixels(), &[RED, RED, RED, BLUE]);
+    }
+
+    #[test]
+    fn link_bytes_drive_the_pixels() {
+        let mut firmware = Firmware::new(4, 0);
+        let mut decoder = Decoder::new(4);
+        decoder.push(&keyframe(1, 4).encode().unwrap());
+        decoder.push(&[0xFF, 0xFF]);
+        decoder.push(&delta(2, 1, &[(1, BLUE)]).encode().unwrap());
+        while let Some(decoded) = decoder.next() {
+            firmware.decoded(decoded);
+        }
+        assert_eq!(firmware.pixels(), &[RED, BLUE, RED, RED]);
+        assert_eq!(firmware.stats().garbage_bytes, 2);
+    }
+
+    #[test]
+    fn ddp_frames_show_when_pushed() {
+        let mut firmware = Firmware::new(2, 1);
+        let data = [9, 9, 9, 255, 0, 0, 0, 0, 255];
+        let packets = wled::ddp_packets(&data, 0, 1);
+        assert!(firmware.ddp(&packets[0]));
+        assert_eq!(firmware.pixels(), &[RED, BLUE]);
+        assert_eq!(firmware.stats().ddp_frames, 1);
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::driver_info::{find_driver, DriverInfo, DRIVERS};
+
+/// Brightness of the other drivers while one driver is highlighted.
+const DIMMED: f32 = 0.2;
+
+/// Which drivers make it onto the board, and how bright.
+#[derive(Debug, Clone, Default)]
+pub struct DriverFilter {
+    hidden: BTreeSet<u32>,
+    highlighted: Option<u32>,
+}
+
+impl DriverFilter {
+    pub fn is_visible(&self, driver_number: u32) -> bool {
+        !self.hidden.contains(&driver_number)
+    }
+
+    pub fn set_visible(&mut self, driver_number: u32, visible: bool) {
+        if visible {
+            self.hidden.remove(&driver_number);
+        } else {
+            self.hidden.insert(driver_number);
+        }
+    }
+
+    pub fn show_all(&mut self) {
+        self.hidden.clear();
+    }
+
+    /// Hides every driver outside `team`, e.g. to follow a team battle.
+    pub fn isolate_team(&mut self, team: &str) {
+        self.hidden = DRIVERS
+            .iter()
+            .filter(|driver| driver.team != team)
+            .map(|driver| driver.number)
+            .collect();
+    }
+
+    pub fn highlighted(&self) -> Option<u32> {
+        self.highlighted
+    }
+
+    pub fn highlight(&mut self, driver_number: Option<u32>) {
+        self.highlighted = driver_number;
+    }
+
+    pub fn visible_drivers(&self) -> impl Iterator<Item = &'static DriverInfo> + '_ {
+        DRIVERS.iter().filter(|driver| self.is_visible(driver.number))
+    }
+
+    /// The color `driver_number` is drawn in, or `None` if it is hidden.
+    pub fn color_for(&self, driver_number: u32) -> Option<(u8, u8, u8)> {
+        if !self.is_visible(driver_number) {
+            return None;
+        }
+        let (r, g, b) = find_driver(driver_number)?.color;
+
+        match self.highlighted {
+            Some(highlighted) if highlighted != driver_number => {
+                let dim = |c: u8| (c as f32 * DIMMED).round() as u8;
+                Some((dim(r), dim(g), dim(b)))
+            }
+            _ => Some((r, g, b)),
+        }
+    }
+}
+
+/// An entry of the highlight picker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Highlight {
+    None,
+    Driver(u32),
+}
+
+impl Highlight {
+    pub fn options() -> Vec<Highlight> {
+        std::iter::once(Highlight::None)
+            .chain(DRIVERS.iter().map(|driver| Highlight::Driver(driver.number)))
+            .collect()
+    }
+}
+
+impl fmt::Display for Highlight {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Highlight::None => write!(f, "No highlight"),
+            Highlight::Driver(number) => match find_driver(*number) {
+                Some(driver) => write!(f, "#{} {}", driver.number, driver.name),
+                None => write!(f, "#{}", number),
+            },
+        }
+    }
+}
+
+/// An entry of the team picker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TeamChoice {
+    All,
+    Team(&'static str),
+}
+
+impl TeamChoice {
+    pub fn options() -> Vec<TeamChoice> {
+        let mut options = vec![TeamChoice::All];
+        for driver in DRIVERS {
+            if !options.contains(&TeamChoice::Team(driver.team)) {
+                options.push(TeamChoice::Team(driver.team));
+            }
+        }
+        options
+    }
+}
+
+impl fmt::Display for TeamChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TeamChoice::All => write!(f, "All teams"),
+            TeamChoice::Team(team) => write!(f, "{}", team),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hidden_drivers_have_no_color() {
+        let mut filter = DriverFilter::default();
+        let color = find_driver(1).unwrap().color;
+        assert_eq!(filter.color_for(1), Some(color));
+
+        filter.set_visible(1, false);
+        assert_eq!(filter.color_for(1), None);
+        assert!(filter.visible_drivers().all(|driver| driver.number != 1));
+        filter.show_all();
+        assert_eq!(filter.color_for(1), Some(color));
+        // Numbers of no driver have no color to draw.
+        assert_eq!(filter.color_for(999), None);
+    }
+
+    #[test]
+    fn isolating_a_team_hides_everyone_else() {
+        let mut filter = DriverFilter::default();
+        filter.isolate_team("Red Bull");
+        let visible: Vec<&str> = filter.visible_drivers().map(|driver| driver.team).collect();
+        assert!(!visible.is_empty());
+        assert!(visible.iter().all(|team| *team == "Red Bull"));
+    }
+
+    #[test]
+    fn highlighting_dims_the_other_drivers() {
+        let mut filter = DriverFilter::default();
+        filter.highlight(Some(1));
+        assert_eq!(filter.color_for(1), Some(find_driver(1).unwrap().color));
+        // (0, 82, 255) at a fifth of the brightness.
+        assert_eq!(filter.color_for(2), Some((0, 16, 51)));
+
+        filter.highlight(None);
+        assert_eq!(filter.color_for(2), Some((0, 82, 255)));
+    }
+}
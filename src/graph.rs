@@ -0,0 +1,625 @@
+use iced::alignment;
+use iced::event::Status;
+use iced::theme::Theme;
+use iced::widget::canvas::{self, Cache, Event, Frame, Path, Program, Stroke, Text};
+use iced::{mouse, Color, Point, Rectangle, Renderer, Size, Vector};
+
+use crate::circuit::{CORNERS, DRS_ZONES, SECTOR_BOUNDARIES_AFTER_LED, START_FINISH_AFTER_LED};
+use crate::driver_info::find_driver;
+use crate::layout::Layout;
+use crate::led_data::{LedCoordinate, UpdateFrame};
+use crate::physical::LedModel;
+use crate::replay::DriverPosition;
+use crate::Message;
+
+/// Space kept clear around the board, in pixels, unless configured.
+pub const PADDING: f32 = 50.0;
+const MIN_ZOOM: f32 = 0.5;
+const MAX_ZOOM: f32 = 20.0;
+/// Cursor travel, in pixels, after which a press becomes a drag.
+const DRAG_THRESHOLD: f32 = 3.0;
+/// On-screen LED size from which LED numbers are labelled.
+const LABEL_MIN_LED_SIZE: f32 = 16.0;
+/// Width of the drawn circuit, in track units.
+const TRACK_WIDTH: f32 = 80.0;
+const DRS_COLOR: Color = Color::from_rgb(0.0, 0.6, 0.2);
+const SECTOR_COLOR: Color = Color::from_rgb(0.8, 0.1, 0.1);
+const CORNER_COLOR: Color = Color::from_rgb(0.3, 0.3, 0.3);
+const PCB_COLOR: Color = Color::from_rgb(0.05, 0.06, 0.05);
+const SILKSCREEN_COLOR: Color = Color::from_rgb(0.35, 0.37, 0.35);
+/// Color of an unlit LED behind its diffuser.
+const LED_OFF_COLOR: Color = Color::from_rgb(0.16, 0.16, 0.15);
+/// Number of halo rings drawn around a physical LED, and how far the outer
+/// one reaches, in LED radii.
+const GLOW_RINGS: usize = 4;
+const GLOW_REACH: f32 = 2.5;
+
+pub struct Graph<'a> {
+    pub layout: &'a Layout,
+    /// Static geometry: LED outlines, centerline and labels. It only has to
+    /// be redrawn when the view or the layout changes.
+    pub track_cache: &'a Cache,
+    pub update_frame: Option<&'a UpdateFrame>,
+    pub positions: &'a [DriverPosition],
+    /// One lap of location samples to draw the circuit from, once the replay
+    /// has been downloaded.
+    pub reference_lap: Option<&'a [(f32, f32)]>,
+    /// Renders LEDs as the physical board would show them, instead of as
+    /// ideal colored squares.
+    pub physical: Option<LedModel>,
+    pub hovered: Option<u32>,
+    /// Track position the camera is locked onto, in follow mode.
+    pub follow: Option<Point>,
+    /// Space kept clear around the board, in pixels.
+    pub padding: f32,
+}
+
+/// Zoom and pan of the canvas, kept in the widget state so that it survives
+/// `view()` rebuilding the `Graph`.
+#[derive(Debug, Clone, Copy)]
+pub struct Camera {
+    zoom: f32,
+    /// Offset of the view center from the track center (or the followed
+    /// driver), in track units.
+    offset: Vector,
+    press: Option<Point>,
+    dragging: bool,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            zoom: 1.0,
+            offset: Vector::new(0.0, 0.0),
+            press: None,
+            dragging: false,
+        }
+    }
+}
+
+/// Maps track coordinates onto the canvas with one scale for both axes, so
+/// the circuit keeps its shape whatever the window aspect ratio.
+struct Projection {
+    center: Point,
+    scale: f32,
+    size: Size,
+    led_size: f32,
+}
+
+impl Projection {
+    fn new(layout: &Layout, bounds: Rectangle, padding: f32, camera: &Camera, follow: Option<Point>) -> Self {
+        let width = layout.max_x - layout.min_x;
+        let height = layout.max_y - layout.min_y;
+
+        let fit = ((bounds.width - 2.0 * padding) / width)
+            .min((bounds.height - 2.0 * padding) / height)
+            .max(f32::EPSILON);
+        let center = follow.unwrap_or(Point::new(
+            layout.min_x + width / 2.0,
+            layout.min_y + height / 2.0,
+        ));
+
+        Self {
+            center: center + camera.offset,
+            scale: fit * camera.zoom,
+            size: bounds.size(),
+            led_size: layout.led_size,
+        }
+    }
+
+    fn project(&self, x: f32, y: f32) -> Point {
+        Point::new(
+            self.size.width / 2.0 + (x - self.center.x) * self.scale,
+            self.size.height / 2.0 - (y - self.center.y) * self.scale,
+        )
+    }
+
+    fn unproject(&self, point: Point) -> Point {
+        Point::new(
+            self.center.x + (point.x - self.size.width / 2.0) / self.scale,
+            self.center.y - (point.y - self.size.height / 2.0) / self.scale,
+        )
+    }
+
+    /// The square drawn for `led`, centered on its position.
+    fn led_bounds(&self, led: &LedCoordinate) -> Rectangle {
+        let center = self.project(led.x_led, led.y_led);
+        let size = self.led_size * self.scale;
+        Rectangle::new(
+            Point::new(center.x - size / 2.0, center.y - size / 2.0),
+            Size::new(size, size),
+        )
+    }
+}
+
+impl<'a> Graph<'a> {
+    fn led_at(&self, projection: &Projection, point: Point) -> Option<u32> {
+        self.layout
+            .leds()
+            .iter()
+            .find(|led| projection.led_bounds(led).contains(point))
+            .map(|led| led.led_number)
+    }
+
+    fn tooltip_lines(&self, led_number: u32) -> Vec<String> {
+        let mut lines = vec![format!("LED {}", led_number)];
+        lines.extend(
+            self.positions
+                .iter()
+                .filter(|p| p.led_number == led_number && p.on_board())
+                .filter_map(|p| find_driver(p.driver_number))
+                .map(|driver| format!("#{} {} ({})", driver.number, driver.name, driver.team)),
+        );
+        lines
+    }
+
+    /// Draws everything that does not change from one replay frame to the
+    /// next: the circuit outline, its annotations, an outline for every LED
+    /// and, when zoomed in far enough, their numbers.
+    fn draw_track(&self, frame: &mut Frame, projection: &Projection, theme: &Theme) {
+        let dark = theme.extended_palette().is_dark;
+        let (asphalt, outline) = if self.physical.is_some() {
+            frame.fill_rectangle(Point::ORIGIN, frame.size(), PCB_COLOR);
+            (PCB_COLOR, SILKSCREEN_COLOR)
+        } else if dark {
+            (Color::from_rgb(0.2, 0.2, 0.21), Color::from_rgb(0.4, 0.4, 0.42))
+        } else {
+            (Color::from_rgb(0.88, 0.88, 0.88), Color::from_rgb(0.6, 0.6, 0.6))
+        };
+
+        let polyline = |points: &mut dyn Iterator<Item = (f32, f32)>, closed: bool| {
+            Path::new(|builder| {
+                let mut points = points.map(|(x, y)| projection.project(x, y));
+                if let Some(first) = points.next() {
+                    builder.move_to(first);
+                    points.for_each(|point| builder.line_to(point));
+                    if closed {
+                        builder.close();
+                    }
+                }
+            })
+        };
+        let led_points = |leds: &'a [LedCoordinate]| leds.iter().map(|led| (led.x_led, led.y_led));
+
+        // The circuit itself, from a reference lap once one is available and
+        // from the LEDs until then.
+        let track_width = (TRACK_WIDTH * projection.scale).max(2.0);
+        let circuit = match self.reference_lap {
+            Some(lap) => polyline(&mut lap.iter().copied(), true),
+            None => polyline(&mut led_points(self.layout.track_leds()), true),
+        };
+        frame.stroke(
+            &circuit,
+            Stroke::default().with_color(asphalt).with_width(track_width),
+        );
+        frame.stroke(
+            &polyline(&mut led_points(self.layout.pit_lane_leds()), false),
+            Stroke::default().with_color(asphalt).with_width(track_width / 2.0),
+        );
+        frame.stroke(
+            &circuit,
+            Stroke::default().with_color(outline).with_width(1.0),
+        );
+
+        self.draw_annotations(frame, projection, dark);
+
+        let label = projection.led_bounds(&self.layout.leds()[0]).width >= LABEL_MIN_LED_SIZE;
+        for led in self.layout.leds() {
+            let led_bounds = projection.led_bounds(led);
+            if self.physical.is_some() {
+                frame.fill(
+                    &Path::circle(led_bounds.center(), led_bounds.width / 2.0),
+                    LED_OFF_COLOR,
+                );
+            } else {
+                frame.stroke(
+                    &Path::rectangle(led_bounds.position(), led_bounds.size()),
+                    Stroke::default().with_color(outline).with_width(1.0),
+                );
+            }
+
+            if label {
+                frame.fill_text(Text {
+                    content: led.led_number.to_string(),
+                    position: Point::new(led_bounds.x + led_bounds.width + 2.0, led_bounds.y),
+                    color: outline,
+                    size: 12.0.into(),
+                    ..Text::default()
+                });
+            }
+        }
+    }
+
+    /// Start/finish line, sector boundaries, DRS zones and corner numbers.
+    fn draw_annotations(&self, frame: &mut Frame, projection: &Projection, dark: bool) {
+        let (line_color, corner_color) = match dark {
+            true => (Color::WHITE, Color::from_rgb(0.7, 0.7, 0.7)),
+            false => (Color::BLACK, CORNER_COLOR),
+        };
+        let label = |frame: &mut Frame, content: String, position: Point, color: Color| {
+            frame.fill_text(Text {
+                content,
+                position,
+                color,
+                size: 14.0.into(),
+                horizontal_alignment: alignment::Horizontal::Center,
+                vertical_alignment: alignment::Vertical::Center,
+                ..Text::default()
+            });
+        };
+
+        for zone in DRS_ZONES {
+            let points: Vec<Point> = (zone.first_led..=zone.last_led)
+                .filter_map(|led_number| self.layout.led(led_number))
+                .filter_map(|led| {
+                    let (_, normal) = self.track_direction(led.led_number)?;
+                    let offset = TRACK_WIDTH * 1.5;
+                    Some(projection.project(led.x_led + normal.0 * offset, led.y_led + normal.1 * offset))
+                })
+                .collect();
+            if let (Some(first), Some(_)) = (points.first(), points.get(1)) {
+                let path = Path::new(|builder| {
+                    builder.move_to(*first);
+                    points[1..].iter().for_each(|point| builder.line_to(*point));
+                });
+                frame.stroke(&path, Stroke::default().with_color(DRS_COLOR).with_width(3.0));
+            }
+        }
+        for (i, zone) in DRS_ZONES.iter().enumerate() {
+            if let Some(position) = self.beside_led(projection, zone.first_led, 3.0) {
+                label(frame, format!("DRS {}", i + 1), position, DRS_COLOR);
+            }
+        }
+
+        let boundaries = std::iter::once(START_FINISH_AFTER_LED).chain(SECTOR_BOUNDARIES_AFTER_LED.iter().copied());
+        for (i, after_led) in boundaries.enumerate() {
+            let Some(((x, y), normal)) = self.between_leds(after_led) else {
+                continue;
+            };
+            let half = TRACK_WIDTH * 2.0;
+            let path = Path::line(
+                projection.project(x + normal.0 * half, y + normal.1 * half),
+                projection.project(x - normal.0 * half, y - normal.1 * half),
+            );
+            let (color, width, text) = if i == 0 {
+                (line_color, 4.0, "S/F".to_string())
+            } else {
+                (SECTOR_COLOR, 2.0, format!("S{}", i + 1))
+            };
+            frame.stroke(&path, Stroke::default().with_color(color).with_width(width));
+
+            let offset = half + TRACK_WIDTH * 1.5;
+            label(frame, text, projection.project(x - normal.0 * offset, y - normal.1 * offset), color);
+        }
+
+        for (corner, led_number) in CORNERS {
+            if let Some(position) = self.beside_led(projection, *led_number, -3.0) {
+                label(frame, format!("T{}", corner), position, corner_color);
+            }
+        }
+    }
+
+    /// Direction of travel at a track LED and the normal to its left, both as
+    /// unit vectors in track coordinates.
+    fn track_direction(&self, led_number: u32) -> Option<((f32, f32), (f32, f32))> {
+        let leds = self.layout.track_leds();
+        let index = leds.iter().position(|led| led.led_number == led_number)?;
+        let previous = &leds[(index + leds.len() - 1) % leds.len()];
+        let next = &leds[(index + 1) % leds.len()];
+
+        let (dx, dy) = (next.x_led - previous.x_led, next.y_led - previous.y_led);
+        let length = (dx * dx + dy * dy).sqrt().max(f32::EPSILON);
+        let direction = (dx / length, dy / length);
+        Some((direction, (-direction.1, direction.0)))
+    }
+
+    /// Midpoint between a track LED and the next one, with the normal there.
+    fn between_leds(&self, led_number: u32) -> Option<((f32, f32), (f32, f32))> {
+        let leds = self.layout.track_leds();
+        let index = leds.iter().position(|led| led.led_number == led_number)?;
+        let (a, b) = (&leds[index], &leds[(index + 1) % leds.len()]);
+
+        let (dx, dy) = (b.x_led - a.x_led, b.y_led - a.y_led);
+        let length = (dx * dx + dy * dy).sqrt().max(f32::EPSILON);
+        let midpoint = ((a.x_led + b.x_led) / 2.0, (a.y_led + b.y_led) / 2.0);
+        Some((midpoint, (-dy / length, dx / length)))
+    }
+
+    /// A point `widths` track widths to the left of an LED, or to its right
+    /// for negative values.
+    fn beside_led(&self, projection: &Projection, led_number: u32, widths: f32) -> Option<Point> {
+        let led = self.layout.led(led_number)?;
+        let (_, normal) = self.track_direction(led_number)?;
+        let offset = TRACK_WIDTH * widths;
+        Some(projection.project(led.x_led + normal.0 * offset, led.y_led + normal.1 * offset))
+    }
+}
+
+impl Program<Message> for Graph<'_> {
+    type State = Camera;
+
+    fn update(
+        &self,
+        camera: &mut Camera,
+        event: Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> (Status, Option<Message>) {
+        let projection = Projection::new(self.layout, bounds, self.padding, camera, self.follow);
+        let position = cursor.position_in(bounds);
+        let hovered = position.and_then(|point| self.led_at(&projection, point));
+
+        match event {
+            Event::Mouse(mouse::Event::WheelScrolled { delta }) => {
+                let Some(point) = position else {
+                    return (Status::Ignored, None);
+                };
+                let steps = match delta {
+                    mouse::ScrollDelta::Lines { y, .. } => y,
+                    mouse::ScrollDelta::Pixels { y, .. } => y / 50.0,
+                };
+
+                // Zoom around the cursor: the track point under it stays put.
+                let anchor = projection.unproject(point);
+                camera.zoom = (camera.zoom * 1.1f32.powf(steps)).clamp(MIN_ZOOM, MAX_ZOOM);
+                let zoomed = Projection::new(self.layout, bounds, self.padding, camera, self.follow);
+                camera.offset = camera.offset + (anchor - zoomed.unproject(point));
+                self.track_cache.clear();
+
+                (Status::Captured, None)
+            }
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                let Some(point) = position else {
+                    return (Status::Ignored, None);
+                };
+                camera.press = Some(point);
+                camera.dragging = false;
+                (Status::Captured, None)
+            }
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Right))
+                if position.is_some() =>
+            {
+                *camera = Camera::default();
+                self.track_cache.clear();
+                (Status::Captured, None)
+            }
+            Event::Mouse(mouse::Event::CursorMoved { .. }) if camera.press.is_some() => {
+                let (Some(press), Some(point)) = (camera.press, cursor.position_from(bounds.position())) else {
+                    return (Status::Ignored, None);
+                };
+                if camera.dragging || press.distance(point) > DRAG_THRESHOLD {
+                    camera.dragging = true;
+                    camera.offset = camera.offset + (projection.unproject(press) - projection.unproject(point));
+                    camera.press = Some(point);
+                    self.track_cache.clear();
+                }
+                (Status::Captured, None)
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                let clicked = camera.press.take().is_some() && !camera.dragging;
+                camera.dragging = false;
+                if clicked {
+                    (Status::Captured, Some(Message::LedClicked(hovered)))
+                } else {
+                    (Status::Ignored, None)
+                }
+            }
+            Event::Mouse(mouse::Event::CursorMoved { .. }) if hovered != self.hovered => {
+                (Status::Captured, Some(Message::LedHovered(hovered)))
+            }
+            Event::Mouse(mouse::Event::CursorLeft) if self.hovered.is_some() => {
+                (Status::Captured, Some(Message::LedHovered(None)))
+            }
+            _ => (Status::Ignored, None),
+        }
+    }
+
+    fn draw(
+        &self,
+        camera: &Camera,
+        _renderer: &Renderer,
+        theme: &Theme,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> Vec<canvas::Geometry> {
+        let projection = Projection::new(self.layout, bounds, self.padding, camera, self.follow);
+
+        let track = self.track_cache.draw(_renderer, bounds.size(), |frame| {
+            self.draw_track(frame, &projection, theme);
+        });
+
+        let mut frame = Frame::new(_renderer, bounds.size());
+
+        // Draw the LED rectangles
+        if let Some(frame_data) = self.update_frame {
+            let colors = frame_data.led_colors(self.layout.max_led_number());
+            for led in self.layout.leds() {
+                // Unlit LEDs are left to the outlines of the static layer.
+                let Some(col) = colors[led.led_number as usize] else {
+                    continue;
+                };
+                if let Some(model) = &self.physical {
+                    draw_physical_led(&mut frame, projection.led_bounds(led), model.appearance(col));
+                    continue;
+                }
+                let color = Color::from_rgb8(col.0, col.1, col.2);
+
+                let led_bounds = projection.led_bounds(led);
+                let point = Path::rectangle(led_bounds.position(), led_bounds.size());
+                frame.fill(&point, color);
+            }
+        }
+
+        if let (Some(led_number), Some(cursor)) = (self.hovered, cursor.position_in(bounds)) {
+            if !camera.dragging {
+                draw_tooltip(&mut frame, bounds, cursor, &self.tooltip_lines(led_number));
+            }
+        }
+
+        vec![track, frame.into_geometry()]
+    }
+
+    fn mouse_interaction(
+        &self,
+        camera: &Camera,
+        _bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> mouse::Interaction {
+        if camera.dragging {
+            mouse::Interaction::Grabbing
+        } else if self.hovered.is_some() {
+            mouse::Interaction::Pointer
+        } else {
+            mouse::Interaction::default()
+        }
+    }
+}
+
+/// Draws a lit LED as a round diffuser with a halo around it. The canvas
+/// has no additive blending, so the halo is a stack of translucent rings,
+/// which adds up the same way over the dark board.
+fn draw_physical_led(frame: &mut Frame, bounds: Rectangle, [r, g, b]: [f32; 3]) {
+    let center = bounds.center();
+    let radius = bounds.width / 2.0;
+    let intensity = r.max(g).max(b);
+
+    for ring in (1..=GLOW_RINGS).rev() {
+        let reach = 1.0 + (GLOW_REACH - 1.0) * ring as f32 / GLOW_RINGS as f32;
+        let alpha = 0.35 * intensity / ring as f32;
+        frame.fill(
+            &Path::circle(center, radius * reach),
+            Color::from_rgba(r, g, b, alpha),
+        );
+    }
+
+    frame.fill(&Path::circle(center, radius), Color::from_rgb(r, g, b));
+
+    // The diffuser saturates towards white in the middle of a bright LED.
+    let hot = 0.5 * intensity * intensity;
+    frame.fill(
+        &Path::circle(center, radius * 0.5),
+        Color::from_rgb(r + (1.0 - r) * hot, g + (1.0 - g) * hot, b + (1.0 - b) * hot),
+    );
+}
+
+fn draw_tooltip(frame: &mut Frame, bounds: Rectangle, cursor: Point, lines: &[String]) {
+    const TEXT_SIZE: f32 = 14.0;
+    const LINE_HEIGHT: f32 = 18.0;
+    const PADDING: f32 = 6.0;
+
+    let longest = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+    let size = Size::new(
+        longest as f32 * TEXT_SIZE * 0.6 + 2.0 * PADDING,
+        lines.len() as f32 * LINE_HEIGHT + 2.0 * PADDING,
+    );
+
+    // Keep the tooltip inside the canvas, flipping it to the other side of
+    // the cursor near the right and bottom edges.
+    let mut origin = Point::new(cursor.x + 12.0, cursor.y + 12.0);
+    if origin.x + size.width > bounds.width {
+        origin.x = (cursor.x - 12.0 - size.width).max(0.0);
+    }
+    if origin.y + size.height > bounds.height {
+        origin.y = (cursor.y - 12.0 - size.height).max(0.0);
+    }
+
+    frame.fill(
+        &Path::rectangle(origin, size),
+        Color::from_rgba(0.1, 0.1, 0.1, 0.9),
+    );
+
+    for (i, line) in lines.iter().enumerate() {
+        frame.fill_text(Text {
+            content: line.clone(),
+            position: Point::new(origin.x + PADDING, origin.y + PADDING + i as f32 * LINE_HEIGHT),
+            color: Color::WHITE,
+            size: TEXT_SIZE.into(),
+            font: iced::Font::MONOSPACE,
+            ..Text::default()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDS: Rectangle = Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 800.0,
+        height: 600.0,
+    };
+
+    fn mouse(graph: &Graph, camera: &mut Camera, event: mouse::Event, at: Point) {
+        graph.update(camera, Event::Mouse(event), BOUNDS, mouse::Cursor::Available(at));
+    }
+
+    fn projection(graph: &Graph, camera: &Camera) -> Projection {
+        Projection::new(graph.layout, BOUNDS, graph.padding, camera, graph.follow)
+    }
+
+    fn assert_near(a: Point, b: Point) {
+        assert!(a.distance(b) < 0.01, "{:?} is not {:?}", a, b);
+    }
+
+    #[test]
+    fn zooming_keeps_the_track_under_the_cursor() {
+        let layout = Layout::new(true);
+        let cache = Cache::new();
+        for follow in [None, Some(Point::new(300.0, -200.0))] {
+            let graph = Graph {
+                layout: &layout,
+                track_cache: &cache,
+                update_frame: None,
+                positions: &[],
+                reference_lap: None,
+                physical: None,
+                hovered: None,
+                follow,
+                padding: PADDING,
+            };
+            let mut camera = Camera::default();
+            let cursor = Point::new(620.0, 140.0);
+            let anchor = projection(&graph, &camera).unproject(cursor);
+
+            for steps in [3.0, -1.5, 40.0] {
+                let delta = mouse::ScrollDelta::Lines { x: 0.0, y: steps };
+                mouse(&graph, &mut camera, mouse::Event::WheelScrolled { delta }, cursor);
+                assert_near(projection(&graph, &camera).project(anchor.x, anchor.y), cursor);
+            }
+            // The last scroll went past the most zoom there is.
+            assert_eq!(camera.zoom, MAX_ZOOM);
+        }
+    }
+
+    #[test]
+    fn dragging_pans_the_track_with_the_cursor() {
+        let layout = Layout::new(true);
+        let cache = Cache::new();
+        let graph = Graph {
+            layout: &layout,
+            track_cache: &cache,
+            update_frame: None,
+            positions: &[],
+            reference_lap: None,
+            physical: None,
+            hovered: None,
+            follow: None,
+            padding: PADDING,
+        };
+        let mut camera = Camera::default();
+        let (press, release) = (Point::new(400.0, 300.0), Point::new(250.0, 380.0));
+        let grabbed = projection(&graph, &camera).unproject(press);
+
+        mouse(&graph, &mut camera, mouse::Event::ButtonPressed(mouse::Button::Left), press);
+        let moved = mouse::Event::CursorMoved { position: release };
+        mouse(&graph, &mut camera, moved, release);
+        mouse(&graph, &mut camera, mouse::Event::ButtonReleased(mouse::Button::Left), release);
+        assert_near(projection(&graph, &camera).project(grabbed.x, grabbed.y), release);
+
+        // A right click puts the view back.
+        mouse(&graph, &mut camera, mouse::Event::ButtonPressed(mouse::Button::Right), release);
+        assert_near(projection(&graph, &camera).project(grabbed.x, grabbed.y), press);
+    }
+}
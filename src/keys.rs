@@ -0,0 +1,278 @@
+use iced::keyboard::{key::Named, Key, Modifiers};
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+use crate::server::MAX_SPEED;
+
+/// Names of the keys that do not type a character, as bindings spell them.
+const NAMED: &[(&str, Named)] = &[
+    ("space", Named::Space),
+    ("enter", Named::Enter),
+    ("escape", Named::Escape),
+    ("tab", Named::Tab),
+    ("backspace", Named::Backspace),
+    ("delete", Named::Delete),
+    ("left", Named::ArrowLeft),
+    ("right", Named::ArrowRight),
+    ("up", Named::ArrowUp),
+    ("down", Named::ArrowDown),
+    ("home", Named::Home),
+    ("end", Named::End),
+    ("pageup", Named::PageUp),
+    ("pagedown", Named::PageDown),
+];
+
+/// The shortcuts the window starts with.
+const DEFAULTS: &[(&str, &str)] = &[
+    ("space", "play-pause"),
+    ("left", "step:-1"),
+    ("right", "step:1"),
+    ("shift+left", "seek:-30"),
+    ("shift+right", "seek:30"),
+    ("1", "speed:1"),
+    ("2", "speed:2"),
+    ("3", "speed:3"),
+    ("4", "speed:4"),
+    ("5", "speed:5"),
+    ("6", "speed:6"),
+    ("7", "speed:7"),
+    ("8", "speed:8"),
+    ("9", "speed:9"),
+    ("0", "speed:10"),
+    ("d", "next-driver"),
+    ("shift+d", "previous-driver"),
+    ("f", "fullscreen"),
+    ("p", "present"),
+    ("escape", "exit"),
+    ("r", "reset"),
+];
+
+/// What a shortcut does, as the settings file spells it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    /// `play-pause`: the Start and Stop button.
+    PlayPause,
+    /// `step:N`: moves the replay by N ticks, back when negative.
+    Step(i32),
+    /// `seek:S`: moves the replay by S seconds, back when negative.
+    Seek(f32),
+    /// `speed:X`: plays the replay X times as fast as the session ran, up to
+    /// `MAX_SPEED`.
+    Speed(f32),
+    /// `next-driver` and `previous-driver`: highlights the driver after or
+    /// before the highlighted one, then none past the last.
+    CycleDriver(i32),
+    /// `fullscreen`: switches the window in and out of fullscreen.
+    Fullscreen,
+    /// `present`: switches presentation mode on and off.
+    Present,
+    /// `exit`: leaves presentation mode and fullscreen.
+    Exit,
+    /// `reset`: the Reset button.
+    Reset,
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (name, argument) = match value.split_once(':') {
+            Some((name, argument)) => (name, Some(argument)),
+            None => (value, None),
+        };
+        let missing = || format!("`{}` needs a number after a colon, as in `{}:2`", value, name);
+        let number = || -> Result<f32, String> {
+            argument
+                .and_then(|argument| argument.parse::<f32>().ok())
+                .filter(|number| number.is_finite())
+                .ok_or_else(missing)
+        };
+        Ok(match name {
+            "play-pause" => Action::PlayPause,
+            "step" => match argument.and_then(|argument| argument.parse().ok()) {
+                Some(ticks) => Action::Step(ticks),
+                None => return Err(format!("`{}` needs a whole number of ticks after a colon, as in `step:2`", value)),
+            },
+            "seek" => Action::Seek(number()?),
+            "speed" => match number()? {
+                speed if (0.0..=MAX_SPEED).contains(&speed) => Action::Speed(speed),
+                _ => return Err(format!("`{}` is not a speed from 0 to {}", value, MAX_SPEED)),
+            },
+            "next-driver" => Action::CycleDriver(1),
+            "previous-driver" => Action::CycleDriver(-1),
+            "fullscreen" => Action::Fullscreen,
+            "present" => Action::Present,
+            "exit" => Action::Exit,
+            "reset" => Action::Reset,
+            _ => return Err(format!("unknown action `{}`", value)),
+        })
+    }
+}
+
+/// A key with the modifiers held with it, such as `shift+left`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Chord {
+    /// A name of `NAMED`, or the character the key types, in lowercase.
+    key: String,
+    shift: bool,
+    ctrl: bool,
+    alt: bool,
+}
+
+impl FromStr for Chord {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let lowercase = value.to_lowercase();
+        let mut parts: Vec<&str> = lowercase.split('+').collect();
+        let key = parts.pop().unwrap_or_default();
+        if NAMED.iter().all(|(name, _)| *name != key) && key.chars().count() != 1 {
+            return Err(format!("unknown key `{}` in `{}`", key, value));
+        }
+        let mut chord = Chord {
+            key: key.to_string(),
+            shift: false,
+            ctrl: false,
+            alt: false,
+        };
+        for modifier in parts {
+            let held = match modifier {
+                "shift" => &mut chord.shift,
+                "ctrl" => &mut chord.ctrl,
+                "alt" => &mut chord.alt,
+                _ => return Err(format!("unknown modifier `{}` in `{}`, expected shift, ctrl or alt", modifier, value)),
+            };
+            *held = true;
+        }
+        Ok(chord)
+    }
+}
+
+impl Chord {
+    fn pressed(key: &Key, modifiers: Modifiers) -> Option<Self> {
+        let key = match key {
+            Key::Named(named) => NAMED.iter().find(|(_, n)| n == named)?.0.to_string(),
+            Key::Character(character) => character.to_lowercase(),
+            Key::Unidentified => return None,
+        };
+        Some(Chord {
+            key,
+            shift: modifiers.shift(),
+            ctrl: modifiers.control(),
+            alt: modifiers.alt(),
+        })
+    }
+}
+
+/// The keyboard shortcuts of the window.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: HashMap<Chord, Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = DEFAULTS
+            .iter()
+            .map(|(chord, action)| {
+                let chord = chord.parse().expect("the default keys are valid");
+                let action = action.parse().expect("the default actions are valid");
+                (chord, action)
+            })
+            .collect();
+        Keymap { bindings }
+    }
+}
+
+impl Keymap {
+    /// These shortcuts with `overrides` bound over them, from key to action,
+    /// where the action `none` unbinds the key.
+    pub fn with(mut self, overrides: &BTreeMap<String, String>) -> Result<Self, String> {
+        for (chord, action) in overrides {
+            let parsed: Chord = chord.parse()?;
+            match action.as_str() {
+                "none" => {
+                    self.bindings.remove(&parsed);
+                }
+                _ => {
+                    let action = action.parse().map_err(|e| format!("`{}`: {}", chord, e))?;
+                    self.bindings.insert(parsed, action);
+                }
+            }
+        }
+        Ok(self)
+    }
+
+    /// What pressing `key` with `modifiers` held does.
+    pub fn action(&self, key: &Key, modifiers: Modifiers) -> Option<Action> {
+        self.bindings.get(&Chord::pressed(key, modifiers)?).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keymap(overrides: &[(&str, &str)]) -> Result<Keymap, String> {
+        let overrides = overrides.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        Keymap::default().with(&overrides)
+    }
+
+    fn press(keymap: &Keymap, key: Key, modifiers: Modifiers) -> Option<Action> {
+        keymap.action(&key, modifiers)
+    }
+
+    fn character(c: &str) -> Key {
+        Key::Character(c.into())
+    }
+
+    #[test]
+    fn chords_are_parsed_with_their_modifiers() {
+        let chord: Chord = "Ctrl+Shift+PageUp".parse().unwrap();
+        assert_eq!(
+            chord,
+            Chord {
+                key: "pageup".to_string(),
+                shift: true,
+                ctrl: true,
+                alt: false,
+            }
+        );
+        assert_eq!("alt+x".parse::<Chord>().unwrap().key, "x");
+
+        assert!("shift+".parse::<Chord>().unwrap_err().contains("unknown key"));
+        assert!("f13".parse::<Chord>().unwrap_err().contains("unknown key `f13`"));
+        assert!("meta+x".parse::<Chord>().unwrap_err().contains("unknown modifier `meta`"));
+    }
+
+    #[test]
+    fn actions_are_parsed_with_their_arguments() {
+        assert_eq!("step:-2".parse::<Action>(), Ok(Action::Step(-2)));
+        assert_eq!("seek:7.5".parse::<Action>(), Ok(Action::Seek(7.5)));
+        assert_eq!("speed:64".parse::<Action>(), Ok(Action::Speed(64.0)));
+        assert!("fly".parse::<Action>().unwrap_err().contains("unknown action"));
+        assert!("step:1.5".parse::<Action>().is_err());
+        assert!("seek".parse::<Action>().is_err());
+        assert!("seek:inf".parse::<Action>().is_err());
+        assert!("speed:65".parse::<Action>().is_err());
+    }
+
+    #[test]
+    fn overrides_rebind_and_unbind_keys() {
+        let keymap = keymap(&[("space", "none"), ("ctrl+s", "seek:60"), ("D", "reset")]).unwrap();
+        assert_eq!(press(&keymap, Key::Named(Named::Space), Modifiers::empty()), None);
+        assert_eq!(press(&keymap, character("s"), Modifiers::CTRL), Some(Action::Seek(60.0)));
+        assert_eq!(press(&keymap, character("s"), Modifiers::empty()), None);
+        // Keys match whatever case they are typed in.
+        assert_eq!(press(&keymap, character("d"), Modifiers::empty()), Some(Action::Reset));
+        // The defaults that were not overridden stay.
+        assert_eq!(press(&keymap, character("D"), Modifiers::SHIFT), Some(Action::CycleDriver(-1)));
+        assert_eq!(press(&keymap, Key::Named(Named::ArrowRight), Modifiers::empty()), Some(Action::Step(1)));
+    }
+
+    #[test]
+    fn invalid_overrides_name_the_key() {
+        assert!(keymap(&[("x", "fly")]).unwrap_err().contains("`x`: unknown action `fly`"));
+        assert!(keymap(&[("hyper+x", "reset")]).unwrap_err().contains("unknown modifier"));
+    }
+}
@@ -0,0 +1,167 @@
+use serde::Deserialize;
+use std::path::Path;
+
+use crate::led_data::{LedCoordinate, LED_DATA, PIT_LANE_LED_DATA};
+
+/// Edge length of an LED on the board as built, in track units, so that
+/// LEDs grow and shrink with the view.
+pub const LED_SIZE: f32 = 125.0;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LedEntry {
+    number: u32,
+    x: f32,
+    y: f32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LayoutFile {
+    led: Vec<LedEntry>,
+}
+
+/// Where every LED of the board is drawn, pit lane included, and how big.
+#[derive(Debug, Clone)]
+pub struct BoardLayout {
+    track: Vec<LedCoordinate>,
+    pit_lane: Vec<LedCoordinate>,
+    led_size: f32,
+}
+
+impl Default for BoardLayout {
+    fn default() -> Self {
+        BoardLayout {
+            track: LED_DATA.to_vec(),
+            pit_lane: PIT_LANE_LED_DATA.to_vec(),
+            led_size: LED_SIZE,
+        }
+    }
+}
+
+impl BoardLayout {
+    /// Reads the LEDs from a TOML file of `[[led]]` tables, and checks that
+    /// it places every LED of the board once. Cars are still placed on the
+    /// LEDs by number, so the file moves LEDs rather than adding any.
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let file: LayoutFile = toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        let board = BoardLayout::default();
+        let max_led_number = board.pit_lane.iter().chain(&board.track).map(|led| led.led_number).max().unwrap_or(0);
+        let mut placed: Vec<Option<LedCoordinate>> = vec![None; max_led_number as usize + 1];
+        for entry in file.led {
+            let slot = placed
+                .get_mut(entry.number as usize)
+                .filter(|_| entry.number != 0)
+                .ok_or_else(|| format!("{}: LED {} is not on the board", path.display(), entry.number))?;
+            if slot.is_some() {
+                return Err(format!("{}: LED {} is placed twice", path.display(), entry.number));
+            }
+            if !entry.x.is_finite() || !entry.y.is_finite() {
+                return Err(format!("{}: LED {} is not at a position", path.display(), entry.number));
+            }
+            *slot = Some(LedCoordinate {
+                x_led: entry.x,
+                y_led: entry.y,
+                led_number: entry.number,
+            });
+        }
+
+        let place = |leds: &[LedCoordinate]| -> Result<Vec<LedCoordinate>, String> {
+            leds.iter()
+                .map(|led| {
+                    placed[led.led_number as usize]
+                        .clone()
+                        .ok_or_else(|| format!("{}: LED {} is not placed", path.display(), led.led_number))
+                })
+                .collect()
+        };
+        Ok(BoardLayout {
+            track: place(&board.track)?,
+            pit_lane: place(&board.pit_lane)?,
+            led_size: board.led_size,
+        })
+    }
+
+    pub fn with_led_size(self, led_size: f32) -> Self {
+        BoardLayout { led_size, ..self }
+    }
+
+    /// The LEDs to draw: the track's, and the pit lane's if `pit_lane`.
+    pub fn layout(&self, pit_lane: bool) -> Layout {
+        let mut leds = self.track.clone();
+        if pit_lane {
+            leds.extend_from_slice(&self.pit_lane);
+        }
+        Layout::from_leds(leds, self.track.len(), self.led_size)
+    }
+}
+
+/// The LEDs on the board, with their bounding box worked out once instead
+/// of on every draw.
+#[derive(Debug, Clone)]
+pub struct Layout {
+    leds: Vec<LedCoordinate>,
+    /// How many of `leds` are on the track, before the pit lane's.
+    track_len: usize,
+    pub led_size: f32,
+    pub min_x: f32,
+    pub max_x: f32,
+    pub min_y: f32,
+    pub max_y: f32,
+    max_led_number: u32,
+}
+
+impl Layout {
+    /// The board as built.
+    pub fn new(pit_lane: bool) -> Self {
+        BoardLayout::default().layout(pit_lane)
+    }
+
+    fn from_leds(leds: Vec<LedCoordinate>, track_len: usize, led_size: f32) -> Self {
+        let (min_x, max_x, min_y, max_y) = leds.iter().fold(
+            (f32::MAX, f32::MIN, f32::MAX, f32::MIN),
+            |(min_x, max_x, min_y, max_y), led| {
+                (
+                    min_x.min(led.x_led),
+                    max_x.max(led.x_led),
+                    min_y.min(led.y_led),
+                    max_y.max(led.y_led),
+                )
+            },
+        );
+        let max_led_number = leds.iter().map(|led| led.led_number).max().unwrap_or(0);
+
+        Self {
+            leds,
+            track_len,
+            led_size,
+            min_x,
+            max_x,
+            min_y,
+            max_y,
+            max_led_number,
+        }
+    }
+
+    pub fn leds(&self) -> &[LedCoordinate] {
+        &self.leds
+    }
+
+    pub fn led(&self, led_number: u32) -> Option<&LedCoordinate> {
+        self.leds.iter().find(|led| led.led_number == led_number)
+    }
+
+    pub fn track_leds(&self) -> &[LedCoordinate] {
+        &self.leds[..self.track_len]
+    }
+
+    pub fn pit_lane_leds(&self) -> &[LedCoordinate] {
+        &self.leds[self.track_len..]
+    }
+
+    pub fn max_led_number(&self) -> u32 {
+        self.max_led_number
+    }
+}
@@ -22,6 +22,18 @@ impl UpdateFrame {
     pub fn set_led_state(&mut self, led_number: u32, color: (u8, u8, u8)) {
         self.led_states.push((led_number, color));
     }
+
+    /// The color of every LED up to `max_led_number`, indexed by LED number.
+    /// When an LED is listed more than once, the first entry wins.
+    pub fn led_colors(&self, max_led_number: u32) -> Vec<Option<(u8, u8, u8)>> {
+        let mut colors = vec![None; max_led_number as usize + 1];
+        for (led_number, color) in &self.led_states {
+            if let Some(slot @ None) = colors.get_mut(*led_number as usize) {
+                *slot = Some(*color);
+            }
+        }
+        colors
+    }
 }
 
 pub const LED_DATA: &[LedCoordinate] = &[
@@ -506,3 +518,173 @@ pub const LED_DATA: &[LedCoordinate] = &[
         led_number: 96,
     },
 ];
+
+/// Optional LEDs running down the pit lane, beside the main straight. They
+/// continue the numbering after the last track LED.
+///
+/// These are not surveyed: they are spaced evenly along a straight line
+/// drawn beside the main straight, which only roughly follows where the
+/// pit lane runs.
+pub const PIT_LANE_LED_DATA: &[LedCoordinate] = &[
+    LedCoordinate {
+        x_led: -175.0,
+        y_led: 1613.0,
+        led_number: 97,
+    },
+    LedCoordinate {
+        x_led: -18.0,
+        y_led: 2013.0,
+        led_number: 98,
+    },
+    LedCoordinate {
+        x_led: 138.0,
+        y_led: 2414.0,
+        led_number: 99,
+    },
+    LedCoordinate {
+        x_led: 295.0,
+        y_led: 2814.0,
+        led_number: 100,
+    },
+    LedCoordinate {
+        x_led: 451.0,
+        y_led: 3215.0,
+        led_number: 101,
+    },
+    LedCoordinate {
+        x_led: 608.0,
+        y_led: 3615.0,
+        led_number: 102,
+    },
+    LedCoordinate {
+        x_led: 764.0,
+        y_led: 4016.0,
+        led_number: 103,
+    },
+    LedCoordinate {
+        x_led: 921.0,
+        y_led: 4416.0,
+        led_number: 104,
+    },
+    LedCoordinate {
+        x_led: 1078.0,
+        y_led: 4817.0,
+        led_number: 105,
+    },
+    LedCoordinate {
+        x_led: 1234.0,
+        y_led: 5217.0,
+        led_number: 106,
+    },
+    LedCoordinate {
+        x_led: 1391.0,
+        y_led: 5618.0,
+        led_number: 107,
+    },
+];
+
+/// Marshal sectors as numbered in OpenF1 race control messages, tagged onto
+/// runs of consecutive LEDs around the board.
+#[derive(Debug, Clone)]
+pub struct LedSector {
+    pub sector: u32,
+    pub first_led: u32,
+    pub last_led: u32,
+}
+
+/// An approximation rather than the circuit's marshal sectors: the track
+/// LEDs are split into 16 even runs of 6 from LED 1 on. Real sectors vary
+/// in length and number, so a yellow flag lights about where it was shown,
+/// not exactly.
+pub const LED_SECTORS: &[LedSector] = &[
+    LedSector { sector: 1, first_led: 1, last_led: 6 },
+    LedSector { sector: 2, first_led: 7, last_led: 12 },
+    LedSector { sector: 3, first_led: 13, last_led: 18 },
+    LedSector { sector: 4, first_led: 19, last_led: 24 },
+    LedSector { sector: 5, first_led: 25, last_led: 30 },
+    LedSector { sector: 6, first_led: 31, last_led: 36 },
+    LedSector { sector: 7, first_led: 37, last_led: 42 },
+    LedSector { sector: 8, first_led: 43, last_led: 48 },
+    LedSector { sector: 9, first_led: 49, last_led: 54 },
+    LedSector { sector: 10, first_led: 55, last_led: 60 },
+    LedSector { sector: 11, first_led: 61, last_led: 66 },
+    LedSector { sector: 12, first_led: 67, last_led: 72 },
+    LedSector { sector: 13, first_led: 73, last_led: 78 },
+    LedSector { sector: 14, first_led: 79, last_led: 84 },
+    LedSector { sector: 15, first_led: 85, last_led: 90 },
+    LedSector { sector: 16, first_led: 91, last_led: 96 },
+];
+
+pub fn sector_for_led(led_number: u32) -> Option<u32> {
+    LED_SECTORS
+        .iter()
+        .find(|s| (s.first_led..=s.last_led).contains(&led_number))
+        .map(|s| s.sector)
+}
+
+pub fn nearest_led(x: f32, y: f32) -> &'static LedCoordinate {
+    LED_DATA
+        .iter()
+        .min_by(|a, b| {
+            let dist_a = ((a.x_led - x).powi(2) + (a.y_led - y).powi(2)).sqrt();
+            let dist_b = ((b.x_led - x).powi(2) + (b.y_led - y).powi(2)).sqrt();
+            dist_a.total_cmp(&dist_b)
+        })
+        .unwrap()
+}
+
+pub fn is_pit_lane_led(led_number: u32) -> bool {
+    PIT_LANE_LED_DATA.iter().any(|led| led.led_number == led_number)
+}
+
+pub fn nearest_pit_lane_led(x: f32, y: f32) -> &'static LedCoordinate {
+    PIT_LANE_LED_DATA
+        .iter()
+        .min_by(|a, b| {
+            let dist_a = ((a.x_led - x).powi(2) + (a.y_led - y).powi(2)).sqrt();
+            let dist_b = ((b.x_led - x).powi(2) + (b.y_led - y).powi(2)).sqrt();
+            dist_a.total_cmp(&dist_b)
+        })
+        .unwrap()
+}
+
+/// Whether a location sample lies on the pit lane rather than the track,
+/// judged by which LED polyline it is closer to. LEDs are too sparse to
+/// compare against individually.
+pub fn on_pit_lane(x: f32, y: f32) -> bool {
+    let track = polyline_distance(LED_DATA, true, x, y);
+    let pit_lane = polyline_distance(PIT_LANE_LED_DATA, false, x, y);
+    pit_lane < track
+}
+
+fn polyline_distance(leds: &[LedCoordinate], closed: bool, x: f32, y: f32) -> f32 {
+    let segments = leds.windows(2).map(|w| (&w[0], &w[1]));
+    let closing = closed.then(|| (&leds[leds.len() - 1], &leds[0]));
+
+    segments
+        .chain(closing)
+        .map(|(a, b)| {
+            let (dx, dy) = (b.x_led - a.x_led, b.y_led - a.y_led);
+            let length = dx * dx + dy * dy;
+            let t = if length > 0.0 {
+                (((x - a.x_led) * dx + (y - a.y_led) * dy) / length).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let (px, py) = (a.x_led + t * dx, a.y_led + t * dy);
+            ((px - x).powi(2) + (py - y).powi(2)).sqrt()
+        })
+        .fold(f32::MAX, f32::min)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions_that_are_not_numbers_do_not_panic() {
+        nearest_led(f32::NAN, 0.0);
+        nearest_pit_lane_led(0.0, f32::INFINITY);
+        assert_eq!(nearest_led(LED_DATA[5].x_led, LED_DATA[5].y_led).led_number, LED_DATA[5].led_number);
+    }
+}
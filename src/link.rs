@@ -0,0 +1,696 @@
+use std::io::Write;
+use std::net::TcpStream;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+use crate::output::Output;
+
+/// First bytes of every packet, for the controller to find packets in the
+/// byte stream.
+pub const SYNC: [u8; 2] = [0xA5, 0x5A];
+/// Packet kind carrying every pixel of the chain.
+pub const KEYFRAME: u8 = b'K';
+/// Packet kind carrying the pixels that changed since an earlier frame.
+pub const DELTA: u8 = b'D';
+/// Sync, kind, sequence, base and payload length.
+pub const HEADER: usize = 9;
+/// The CRC after the payload.
+pub const TRAILER: usize = 2;
+/// Most pixels a chain may have on the link: every pixel of a keyframe has
+/// to fit its payload length.
+pub const MAX_PIXELS: u32 = u16::MAX as u32 / 3;
+/// Bits on the wire per byte with 8N1 framing: a start and a stop bit.
+const BITS_PER_BYTE: u32 = 10;
+/// Longest a frame may wait for the frames before it to go out. Later than
+/// this it would show a board that has moved on, so it is dropped.
+const MAX_DELAY: Duration = Duration::from_millis(250);
+/// Interval at which the link reports how it keeps up.
+const REPORT_INTERVAL: Duration = Duration::from_secs(5);
+/// Wait before connecting to the emulator again.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// CRC-16/CCITT-FALSE, as the controller checks it.
+pub fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in bytes {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+/// One packet of the link protocol. On the wire, little-endian:
+///
+/// ```text
+/// A5 5A | kind | seq: u16 | base: u16 | len: u16 | payload | crc: u16
+/// ```
+///
+/// A keyframe's payload is RGB for every pixel of the chain, and its base
+/// is its own sequence number. A delta's payload is `index: u16, r, g, b`
+/// for every pixel that changed since frame `base`, which the controller
+/// must be showing for the delta to apply. The CRC covers everything from
+/// the kind to the end of the payload.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Packet {
+    Keyframe {
+        seq: u16,
+        pixels: Vec<(u8, u8, u8)>,
+    },
+    Delta {
+        seq: u16,
+        base: u16,
+        changes: Vec<(u16, (u8, u8, u8))>,
+    },
+}
+
+impl Packet {
+    /// The packet's bytes on the wire, unless its payload is too long for
+    /// the length field.
+    pub fn encode(&self) -> Result<Vec<u8>, String> {
+        let (kind, seq, base, payload) = match self {
+            Packet::Keyframe { seq, pixels } => {
+                let payload = pixels.iter().flat_map(|&(r, g, b)| [r, g, b]).collect::<Vec<_>>();
+                (KEYFRAME, *seq, *seq, payload)
+            }
+            Packet::Delta { seq, base, changes } => {
+                let payload = changes
+                    .iter()
+                    .flat_map(|&(index, (r, g, b))| {
+                        let [low, high] = index.to_le_bytes();
+                        [low, high, r, g, b]
+                    })
+                    .collect::<Vec<_>>();
+                (DELTA, *seq, *base, payload)
+            }
+        };
+
+        let len = u16::try_from(payload.len())
+            .map_err(|_| format!("{} bytes of payload do not fit a packet", payload.len()))?;
+        let mut bytes = Vec::with_capacity(HEADER + payload.len() + TRAILER);
+        bytes.extend_from_slice(&SYNC);
+        bytes.push(kind);
+        bytes.extend_from_slice(&seq.to_le_bytes());
+        bytes.extend_from_slice(&base.to_le_bytes());
+        bytes.extend_from_slice(&len.to_le_bytes());
+        bytes.extend_from_slice(&payload);
+        let crc = crc16(&bytes[SYNC.len()..]);
+        bytes.extend_from_slice(&crc.to_le_bytes());
+        Ok(bytes)
+    }
+}
+
+/// What the decoder found next in the byte stream.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decoded {
+    Packet(Packet),
+    /// Bytes skipped to find the next sync.
+    Garbage(usize),
+    /// A packet whose CRC does not match; the search for the next one goes
+    /// on right after its sync.
+    BadCrc,
+    /// A packet that makes no sense: one with a valid CRC, or a header
+    /// giving a length no packet for the chain has, after which the search
+    /// goes on right after its sync.
+    Malformed(String),
+}
+
+/// Finds packets in the bytes of the link, as the controller does.
+#[derive(Debug)]
+pub struct Decoder {
+    buffer: Vec<u8>,
+    /// Longest payload of a packet for the chain: a delta changing every
+    /// pixel. A longer one is a corrupted header, which would otherwise
+    /// hold up every packet after it until that many bytes came in.
+    max_payload: usize,
+}
+
+impl Decoder {
+    /// A decoder for a chain of `pixels` pixels.
+    pub fn new(pixels: u32) -> Self {
+        Self {
+            buffer: Vec::new(),
+            max_payload: pixels as usize * 5,
+        }
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Forgets a packet cut off halfway, when the link is made again.
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+    }
+
+    /// The next thing in the bytes pushed so far, or `None` until more
+    /// bytes come in.
+    pub fn next(&mut self) -> Option<Decoded> {
+        let start = self.buffer.windows(SYNC.len()).position(|window| window == SYNC);
+        // A last byte that could start a sync is kept for the next bytes.
+        let garbage = start.unwrap_or(self.buffer.len().saturating_sub(SYNC.len() - 1));
+        if garbage > 0 {
+            self.buffer.drain(..garbage);
+            return Some(Decoded::Garbage(garbage));
+        }
+        if self.buffer.len() < HEADER {
+            return None;
+        }
+
+        let kind = self.buffer[2];
+        let seq = u16::from_le_bytes([self.buffer[3], self.buffer[4]]);
+        let base = u16::from_le_bytes([self.buffer[5], self.buffer[6]]);
+        let len = u16::from_le_bytes([self.buffer[7], self.buffer[8]]) as usize;
+        if len > self.max_payload {
+            self.buffer.drain(..SYNC.len());
+            return Some(Decoded::Malformed(format!(
+                "{} bytes of payload is more than any packet for {} pixels",
+                len,
+                self.max_payload / 5
+            )));
+        }
+        if self.buffer.len() < HEADER + len + TRAILER {
+            return None;
+        }
+        let crc = u16::from_le_bytes([self.buffer[HEADER + len], self.buffer[HEADER + len + 1]]);
+        if crc16(&self.buffer[SYNC.len()..HEADER + len]) != crc {
+            self.buffer.drain(..SYNC.len());
+            return Some(Decoded::BadCrc);
+        }
+
+        let packet: Vec<u8> = self.buffer.drain(..HEADER + len + TRAILER).collect();
+        let payload = &packet[HEADER..HEADER + len];
+        Some(match kind {
+            KEYFRAME if len.is_multiple_of(3) => Decoded::Packet(Packet::Keyframe {
+                seq,
+                pixels: payload.chunks(3).map(|p| (p[0], p[1], p[2])).collect(),
+            }),
+            DELTA if len.is_multiple_of(5) => Decoded::Packet(Packet::Delta {
+                seq,
+                base,
+                changes: payload
+                    .chunks(5)
+                    .map(|c| (u16::from_le_bytes([c[0], c[1]]), (c[2], c[3], c[4])))
+                    .collect(),
+            }),
+            KEYFRAME | DELTA => Decoded::Malformed(format!("{} bytes of payload split a pixel", len)),
+            kind => Decoded::Malformed(format!("unknown packet kind {:#04x}", kind)),
+        })
+    }
+}
+
+/// Picks how each frame is sent: only the pixels that changed since the
+/// last frame sent, unless every pixel is cheaper or a keyframe is due.
+#[derive(Debug)]
+struct Encoder {
+    /// Sequence number of the last frame sent, if any was.
+    last_seq: Option<u16>,
+    /// The pixels of the last frame sent.
+    sent: Vec<(u8, u8, u8)>,
+    keyframe_every: u32,
+    since_keyframe: u32,
+}
+
+impl Encoder {
+    fn packet(&self, pixels: &[(u8, u8, u8)]) -> Packet {
+        let keyframe = |seq| Packet::Keyframe {
+            seq,
+            pixels: pixels.to_vec(),
+        };
+        let Some(base) = self.last_seq else {
+            return keyframe(0);
+        };
+        let seq = base.wrapping_add(1);
+        // A chain too long for the link gets no deltas, whose indexes would
+        // not fit, and its keyframes fail to encode.
+        if self.since_keyframe + 1 >= self.keyframe_every
+            || self.sent.len() != pixels.len()
+            || pixels.len() > MAX_PIXELS as usize
+        {
+            return keyframe(seq);
+        }
+
+        let changes: Vec<(u16, (u8, u8, u8))> = pixels
+            .iter()
+            .zip(&self.sent)
+            .enumerate()
+            .filter(|(_, (now, before))| now != before)
+            .map(|(index, (now, _))| (u16::try_from(index).expect("the chain fits the link"), *now))
+            .collect();
+        // Five bytes per changed pixel against three per pixel.
+        if changes.len() * 5 < pixels.len() * 3 {
+            Packet::Delta {
+                seq,
+                base,
+                changes,
+            }
+        } else {
+            keyframe(seq)
+        }
+    }
+
+    /// Takes note that `packet` went out, for the next delta to build on.
+    fn sent(&mut self, packet: &Packet, pixels: &[(u8, u8, u8)]) {
+        let seq = match packet {
+            Packet::Keyframe { seq, .. } => {
+                self.since_keyframe = 0;
+                seq
+            }
+            Packet::Delta { seq, .. } => {
+                self.since_keyframe += 1;
+                seq
+            }
+        };
+        self.last_seq = Some(*seq);
+        self.sent = pixels.to_vec();
+    }
+
+    /// Makes the next frame a keyframe, for a controller that lost track.
+    fn reset(&mut self) {
+        self.since_keyframe = self.keyframe_every;
+    }
+}
+
+/// A link of `baud` bits per second, 8N1, that sends one frame at a time.
+#[derive(Debug)]
+struct Bandwidth {
+    baud: u32,
+    /// When the frames sent so far are all out.
+    busy_until: Instant,
+}
+
+enum Sent {
+    OnTime,
+    /// Waited this long for the frames before it.
+    Delayed(Duration),
+    Dropped,
+}
+
+impl Bandwidth {
+    fn transmit_time(&self, bytes: usize) -> Duration {
+        Duration::from_secs_f64((bytes as u32 * BITS_PER_BYTE) as f64 / self.baud as f64)
+    }
+
+    /// Queues `bytes` at `now`, unless they would wait too long.
+    fn send(&mut self, bytes: usize, now: Instant) -> Sent {
+        let wait = self.busy_until.saturating_duration_since(now);
+        if wait > MAX_DELAY {
+            return Sent::Dropped;
+        }
+        self.busy_until = now.max(self.busy_until) + self.transmit_time(bytes);
+        match wait.is_zero() {
+            true => Sent::OnTime,
+            false => Sent::Delayed(wait),
+        }
+    }
+}
+
+/// How the link kept up since the last report.
+#[derive(Debug, Default)]
+struct LinkStats {
+    keyframes: u32,
+    deltas: u32,
+    bytes: usize,
+    delayed: u32,
+    max_delay: Duration,
+    dropped: u32,
+}
+
+/// Where the bytes of the link go.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkTarget {
+    /// A serial device, such as `/dev/ttyUSB0` or `COM3`.
+    Serial(String),
+    /// The controller emulator, as `tcp://HOST:PORT`.
+    Tcp(String),
+    /// Nowhere: the link is only simulated.
+    Null,
+}
+
+impl FromStr for LinkTarget {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Ok(match value {
+            "null" => LinkTarget::Null,
+            _ => match value.strip_prefix("tcp://") {
+                Some(address) => LinkTarget::Tcp(address.to_string()),
+                None => LinkTarget::Serial(value.to_string()),
+            },
+        })
+    }
+}
+
+/// Sends frames to the board's controller in the link protocol, with the
+/// bandwidth of a serial line of the given baud rate. Frames the line
+/// cannot carry in time are dropped, and what was delayed or dropped is
+/// reported at intervals.
+pub struct SerialLink {
+    target: String,
+    encoder: Encoder,
+    bandwidth: Bandwidth,
+    stats: LinkStats,
+    last_report: Instant,
+    /// Bytes for the thread that writes them, so a slow line never holds
+    /// up the replay. `None` when the link is only simulated.
+    writer: Option<mpsc::Sender<Vec<u8>>>,
+    /// Set by the writer when the controller may have lost frames, for the
+    /// next one to be a keyframe.
+    resync: Arc<AtomicBool>,
+}
+
+impl SerialLink {
+    pub fn new(target: &LinkTarget, pixels: u32, baud: u32, keyframe_every: u32) -> Result<Self, String> {
+        if baud == 0 {
+            return Err("the baud rate has to be above 0".to_string());
+        }
+        if pixels > MAX_PIXELS {
+            return Err(format!("the chain has {} pixels, more than the {} a link can carry", pixels, MAX_PIXELS));
+        }
+        let resync = Arc::new(AtomicBool::new(false));
+        let (name, writer) = match target {
+            LinkTarget::Serial(path) => {
+                let port = serialport::new(path, baud)
+                    .timeout(Duration::from_secs(1))
+                    .open()
+                    .map_err(|e| format!("failed to open {}: {}", path, e))?;
+                (path.clone(), Some(spawn_writer(path.clone(), port, resync.clone())))
+            }
+            LinkTarget::Tcp(address) => {
+                let stream = Reconnecting::new(address, resync.clone());
+                let writer = spawn_writer(address.clone(), stream, resync.clone());
+                (format!("tcp://{}", address), Some(writer))
+            }
+            LinkTarget::Null => ("a simulated link".to_string(), None),
+        };
+
+        let now = Instant::now();
+        Ok(Self {
+            target: format!("{} at {} baud", name, baud),
+            encoder: Encoder {
+                last_seq: None,
+                sent: Vec::new(),
+                keyframe_every: keyframe_every.max(1),
+                since_keyframe: 0,
+            },
+            bandwidth: Bandwidth { baud, busy_until: now },
+            stats: LinkStats::default(),
+            last_report: now,
+            writer,
+            resync,
+        })
+    }
+
+    fn report(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.last_report);
+        if elapsed < REPORT_INTERVAL {
+            return;
+        }
+        let stats = std::mem::take(&mut self.stats);
+        self.last_report = now;
+        let capacity = self.bandwidth.baud as f64 / BITS_PER_BYTE as f64 * elapsed.as_secs_f64();
+        eprintln!(
+            "{}: {} keyframes, {} deltas, {:.0}% of the line, {} delayed (up to {} ms), {} dropped",
+            self.target,
+            stats.keyframes,
+            stats.deltas,
+            stats.bytes as f64 / capacity * 100.0,
+            stats.delayed,
+            stats.max_delay.as_millis(),
+            stats.dropped
+        );
+    }
+}
+
+impl Output for SerialLink {
+    fn describe(&self) -> String {
+        self.target.clone()
+    }
+
+    fn send(&mut self, pixels: &[(u8, u8, u8)]) -> Result<(), String> {
+        if self.resync.swap(false, Ordering::Relaxed) {
+            self.encoder.reset();
+        }
+        let now = Instant::now();
+        let packet = self.encoder.packet(pixels);
+        let bytes = packet.encode()?;
+
+        match self.bandwidth.send(bytes.len(), now) {
+            Sent::Dropped => self.stats.dropped += 1,
+            sent => {
+                if let Sent::Delayed(wait) = sent {
+                    self.stats.delayed += 1;
+                    self.stats.max_delay = self.stats.max_delay.max(wait);
+                }
+                match packet {
+                    Packet::Keyframe { .. } => self.stats.keyframes += 1,
+                    Packet::Delta { .. } => self.stats.deltas += 1,
+                }
+                self.stats.bytes += bytes.len();
+                self.encoder.sent(&packet, pixels);
+                if let Some(writer) = &self.writer {
+                    writer.send(bytes).map_err(|_| "the link is closed".to_string())?;
+                }
+            }
+        }
+        self.report(now);
+        Ok(())
+    }
+}
+
+/// Writes what is sent to it to `sink` until the link is dropped. Failed
+/// writes are reported once until writing works again.
+fn spawn_writer<W: Write + Send + 'static>(name: String, mut sink: W, resync: Arc<AtomicBool>) -> mpsc::Sender<Vec<u8>> {
+    let (sender, received) = mpsc::channel::<Vec<u8>>();
+    std::thread::spawn(move || {
+        let mut failing = false;
+        for bytes in received {
+            match sink.write_all(&bytes).and_then(|()| sink.flush()) {
+                Ok(()) if failing => {
+                    eprintln!("Writing to {} again", name);
+                    failing = false;
+                }
+                Ok(()) => {}
+                Err(e) => {
+                    if !failing {
+                        eprintln!("Failed to write to {}: {}", name, e);
+                        failing = true;
+                    }
+                    resync.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+    });
+    sender
+}
+
+/// A TCP connection that is made again when it is lost, at most once per
+/// `RECONNECT_DELAY`. A new connection asks for a keyframe, since what is
+/// on the other end has seen none of the frames before.
+struct Reconnecting {
+    address: String,
+    stream: Option<TcpStream>,
+    last_attempt: Option<Instant>,
+    resync: Arc<AtomicBool>,
+}
+
+impl Reconnecting {
+    fn new(address: &str, resync: Arc<AtomicBool>) -> Self {
+        Self {
+            address: address.to_string(),
+            stream: None,
+            last_attempt: None,
+            resync,
+        }
+    }
+
+    fn stream(&mut self) -> std::io::Result<&mut TcpStream> {
+        if self.stream.is_none() {
+            if self.last_attempt.is_some_and(|last| last.elapsed() < RECONNECT_DELAY) {
+                return Err(std::io::Error::new(std::io::ErrorKind::NotConnected, "not connected"));
+            }
+            self.last_attempt = Some(Instant::now());
+            let stream = TcpStream::connect(&self.address)?;
+            stream.set_nodelay(true)?;
+            self.stream = Some(stream);
+            self.resync.store(true, Ordering::Relaxed);
+        }
+        Ok(self.stream.as_mut().expect("connected above"))
+    }
+}
+
+impl Write for Reconnecting {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        let written = self.stream()?.write(bytes);
+        if written.is_err() {
+            self.stream = None;
+        }
+        written
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.stream {
+            Some(stream) => stream.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PIXELS: usize = 20;
+
+    fn encoder(keyframe_every: u32) -> Encoder {
+        Encoder {
+            last_seq: None,
+            sent: Vec::new(),
+            keyframe_every,
+            since_keyframe: 0,
+        }
+    }
+
+    /// Encodes the next frame of `pixels`, taking note that it was sent.
+    fn send(encoder: &mut Encoder, pixels: &[(u8, u8, u8)]) -> Packet {
+        let packet = encoder.packet(pixels);
+        encoder.sent(&packet, pixels);
+        packet
+    }
+
+    fn decode_all(decoder: &mut Decoder) -> Vec<Decoded> {
+        std::iter::from_fn(|| decoder.next()).collect()
+    }
+
+    #[test]
+    fn crc16_matches_the_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+    }
+
+    #[test]
+    fn encoder_sends_deltas_between_keyframes() {
+        let mut encoder = encoder(3);
+        let mut pixels = vec![(0, 0, 0); PIXELS];
+        assert!(matches!(send(&mut encoder, &pixels), Packet::Keyframe { seq: 0, .. }));
+
+        pixels[4] = (255, 0, 0);
+        let delta = send(&mut encoder, &pixels);
+        assert_eq!(
+            delta,
+            Packet::Delta {
+                seq: 1,
+                base: 0,
+                changes: vec![(4, (255, 0, 0))]
+            }
+        );
+        pixels[5] = (0, 255, 0);
+        assert!(matches!(send(&mut encoder, &pixels), Packet::Delta { seq: 2, base: 1, .. }));
+        // Every third frame is a keyframe.
+        pixels[6] = (0, 0, 255);
+        assert!(matches!(send(&mut encoder, &pixels), Packet::Keyframe { seq: 3, .. }));
+    }
+
+    #[test]
+    fn encoder_sends_keyframes_when_they_are_shorter_or_asked_for() {
+        let mut encoder = encoder(100);
+        let pixels = vec![(0, 0, 0); PIXELS];
+        send(&mut encoder, &pixels);
+
+        // Five bytes for each of 12 changes are more than three for each
+        // of 20 pixels.
+        let mut changed = pixels.clone();
+        changed[..12].fill((9, 9, 9));
+        assert!(matches!(send(&mut encoder, &changed), Packet::Keyframe { .. }));
+        assert!(matches!(send(&mut encoder, &changed[..10]), Packet::Keyframe { .. }));
+        encoder.reset();
+        assert!(matches!(send(&mut encoder, &changed[..10]), Packet::Keyframe { .. }));
+        assert!(matches!(send(&mut encoder, &changed[..10]), Packet::Delta { .. }));
+    }
+
+    #[test]
+    fn bandwidth_delays_then_drops_frames() {
+        let start = Instant::now();
+        // 100 bytes take 100 ms at 10000 baud.
+        let mut bandwidth = Bandwidth {
+            baud: 10_000,
+            busy_until: start,
+        };
+        assert_eq!(bandwidth.transmit_time(100), Duration::from_millis(100));
+        assert!(matches!(bandwidth.send(100, start), Sent::OnTime));
+        assert!(matches!(bandwidth.send(100, start), Sent::Delayed(wait) if wait == Duration::from_millis(100)));
+        assert!(matches!(bandwidth.send(100, start), Sent::Delayed(wait) if wait == Duration::from_millis(200)));
+        assert!(matches!(bandwidth.send(100, start), Sent::Dropped));
+        // Once the line is free again, frames go out on time.
+        assert!(matches!(bandwidth.send(100, start + Duration::from_secs(1)), Sent::OnTime));
+    }
+
+    #[test]
+    fn decoder_resyncs_after_garbage_and_bad_packets() {
+        let keyframe = Packet::Keyframe {
+            seq: 7,
+            pixels: vec![(1, 2, 3); PIXELS],
+        };
+        let delta = Packet::Delta {
+            seq: 8,
+            base: 7,
+            changes: vec![(3, (4, 5, 6))],
+        };
+        let mut corrupted = delta.encode().unwrap();
+        let last = corrupted.len() - 3;
+        corrupted[last] ^= 0xFF;
+
+        let mut decoder = Decoder::new(PIXELS as u32);
+        decoder.push(&[0x00, 0xA5, 0x11]);
+        decoder.push(&keyframe.encode().unwrap());
+        decoder.push(&corrupted);
+        decoder.push(&delta.encode().unwrap());
+        let decoded = decode_all(&mut decoder);
+        assert_eq!(decoded[0], Decoded::Garbage(3));
+        assert_eq!(decoded[1], Decoded::Packet(keyframe));
+        assert_eq!(decoded[2], Decoded::BadCrc);
+        assert!(matches!(decoded[3], Decoded::Garbage(_)));
+        assert_eq!(decoded[4], Decoded::Packet(delta));
+        assert_eq!(decoded.len(), 5);
+    }
+
+    #[test]
+    fn decoder_skips_lengths_no_packet_has() {
+        let delta = Packet::Delta {
+            seq: 1,
+            base: 0,
+            changes: vec![(0, (1, 1, 1))],
+        };
+        let mut corrupted = delta.encode().unwrap();
+        corrupted[7..9].copy_from_slice(&u16::MAX.to_le_bytes());
+
+        // The next packet comes through without waiting for 64 KB.
+        let mut decoder = Decoder::new(PIXELS as u32);
+        decoder.push(&corrupted);
+        decoder.push(&delta.encode().unwrap());
+        let decoded = decode_all(&mut decoder);
+        assert!(matches!(decoded[0], Decoded::Malformed(_)));
+        assert!(matches!(decoded[1], Decoded::Garbage(_)));
+        assert_eq!(decoded[2], Decoded::Packet(delta));
+    }
+
+    #[test]
+    fn chains_too_long_for_the_link_are_rejected() {
+        let keyframe = |pixels: u32| Packet::Keyframe {
+            seq: 0,
+            pixels: vec![(1, 2, 3); pixels as usize],
+        };
+        assert!(keyframe(MAX_PIXELS).encode().is_ok());
+        assert!(keyframe(MAX_PIXELS + 1).encode().is_err());
+
+        assert!(SerialLink::new(&LinkTarget::Null, MAX_PIXELS, 115_200, 10).is_ok());
+        assert!(SerialLink::new(&LinkTarget::Null, MAX_PIXELS + 1, 115_200, 10).is_err());
+        // Past 65536 pixels, delta indexes would alias other pixels.
+        let mut encoder = encoder(10);
+        let pixels = vec![(0, 0, 0); 70_000];
+        send(&mut encoder, &pixels);
+        assert!(matches!(encoder.packet(&pixels), Packet::Keyframe { .. }));
+    }
+}
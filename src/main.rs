@@ -1,42 +1,459 @@
 mod led_data;
 mod driver_info;
+mod api;
+mod board;
+mod chain;
+mod circuit;
+mod cli;
+mod config;
+mod effects;
+mod emulator;
+mod filter;
+mod graph;
+mod keys;
+mod layout;
+mod link;
+mod mock_api;
+mod mock_broker;
+mod mock_wled;
+mod mqtt;
+mod output;
+mod overlay;
+mod physical;
+mod pit;
+mod power;
+mod race_control;
+mod render;
+mod replay;
+mod server;
+mod simulator;
+mod snapshot;
+mod standings;
+mod svg;
+mod sync;
+mod telemetry;
+mod trails;
+mod wled;
 
 use iced::alignment;
 use iced::executor;
+use iced::event;
+use iced::keyboard::{self, Key, Modifiers};
 use iced::theme::{self, Theme};
 use iced::time;
-use iced::widget::{button, container, row, text, column};
+use iced::window;
+use iced::widget::{button, checkbox, container, horizontal_space, pick_list, row, scrollable, slider, text, column};
 use iced::{
     Alignment, Application, Command, Element, Length, Settings, Subscription,
-    widget::canvas::{self, Canvas, Path, Frame, Program}, Color, Point, Size, mouse, Renderer
+    widget::canvas::{Cache, Canvas},
 };
-use reqwest::Client;
-use serde::Deserialize;
+use std::fs;
 use std::time::{Duration, Instant};
-use led_data::{LedCoordinate, LED_DATA, UpdateFrame};
-use driver_info::DRIVERS;
-use std::f32;
+use led_data::UpdateFrame;
+use driver_info::{find_driver, DRIVERS};
+use filter::{DriverFilter, Highlight, TeamChoice};
+use api::Api;
+use board::{Board, Session};
+use cli::{
+    Cli, CliCommand, DisplayArgs, EmulatorArgs, HeadlessArgs, MockApiArgs, MockBrokerArgs, MockWledArgs, OutputArgs,
+    PlaybackArgs, RenderArgs, SourceArgs, SvgArgs, SyncArgs, ThemeChoice,
+};
+use graph::Graph;
+use keys::{Action, Keymap};
+use layout::{BoardLayout, Layout};
+use mock_api::LiveSession;
+use output::Outputs;
+use overlay::{LapCount, SessionInfo};
+use physical::{LedModel, RenderMode};
+use pit::{PitData, PitStops};
+use power::{PowerBudget, PowerReport};
+use race_control::{RaceControl, RaceControlMessage};
+use render::RenderOptions;
+use server::{Control, FrameState, Frames, ServerEvent};
+use simulator::SimulationConfig;
+use replay::{format_elapsed, DriverPosition, LocationData, Replay};
+use standings::{PositionData, Standings};
+use sync::{Beacon, FollowerClock, Leader};
+use telemetry::{CarData, LapData, Telemetry};
+use trails::{TrailKind, TrailMode, Trails};
 use chrono::{DateTime, Utc};
 
-#[derive(Debug, Deserialize)]
-struct LocationData {
-    x: f32,
-    y: f32,
-    date: String,
-    driver_number: u32,
-}
+/// Interval at which a live session is polled for new samples.
+const LIVE_POLL: Duration = Duration::from_secs(2);
+/// How far back before the newest sample each poll asks from, so samples
+/// of one driver that arrive later than another's are not missed.
+const LIVE_OVERLAP: Duration = Duration::from_secs(5);
 
 pub fn main() -> iced::Result {
-    Race::run(Settings::default())
+    // The requests made, among others, with RUST_LOG=debug.
+    env_logger::init();
+    let result = config::parse().and_then(|mut cli| match cli.command.take() {
+        // Loaded before the window opens, so that a bad layout file stops
+        // here rather than leaving the board blank.
+        None => match cli.display.board_layout() {
+            Ok(layout) => {
+                Race::run(Settings::with_flags((cli, layout))).map_err(|e| e.to_string())
+            }
+            Err(e) => Err(e),
+        },
+        Some(CliCommand::Render(args)) => render_replay(&cli.source, &cli.display, args),
+        Some(CliCommand::Svg(args)) => export_svg(&cli.source, &cli.display, args),
+        Some(CliCommand::Snapshots(args)) if args.update => snapshot::update(&args.dir),
+        Some(CliCommand::Snapshots(args)) => snapshot::check(&args.dir, &args.failures),
+        Some(CliCommand::MockApi(args)) => serve_mock_api(&cli.source, args),
+        Some(CliCommand::MockBroker(args)) => run_mock_broker(&cli.source, args),
+        Some(CliCommand::Headless(args)) => {
+            run_headless(&cli.source, &cli.sync, &cli.outputs, &cli.playback, &cli.display, args)
+        }
+        Some(CliCommand::MockWled(args)) => run_mock_wled(args),
+        Some(CliCommand::Emulator(args)) => run_emulator(&cli.outputs, &cli.display, args),
+    });
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Reads the session from a CSV export or generates it, for the sources
+/// that need no network.
+fn load_offline(source: &SourceArgs) -> Result<(Replay, RaceControl), String> {
+    if let Some(path) = &source.csv {
+        return Ok((Replay::from_csv(path)?, RaceControl::default()));
+    }
+    let seed = source.simulate.ok_or("the session has to be downloaded")?;
+
+    let simulation = simulator::simulate(&SimulationConfig {
+        seed,
+        laps: source.laps,
+        ..SimulationConfig::default()
+    });
+    let mut replay = Replay::default();
+    for locations in simulation.locations {
+        replay.add_driver(locations);
+    }
+    Ok((replay, RaceControl::new(simulation.race_control)))
+}
+
+/// Loads the whole session up front, from wherever `source` says.
+fn load_session(source: &SourceArgs) -> Result<Session, String> {
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+    runtime.block_on(open_session(source, source.api()))
+}
+
+/// Loads the whole session from an offline source, or downloads it from
+/// `api`.
+async fn open_session(source: &SourceArgs, api: Api) -> Result<Session, String> {
+    let (replay, race_control, pit_stops) = if source.is_offline() {
+        let (replay, race_control) = load_offline(source)?;
+        (replay, race_control, PitStops::default())
+    } else {
+        let drivers = DRIVERS.iter().map(|driver| driver.number).collect();
+        let replay = fetch_driver_data(api.clone(), drivers).await?;
+        let race_control = fetch_race_control(api.clone()).await.unwrap_or_else(|e| {
+            eprintln!("Failed to fetch race control messages: {}", e);
+            RaceControl::default()
+        });
+        let pit_stops = fetch_pit_stops(api).await.unwrap_or_else(|e| {
+            eprintln!("Failed to fetch pit stops: {}", e);
+            PitStops::default()
+        });
+        (replay, race_control, pit_stops)
+    };
+    if replay.is_empty() {
+        return Err("no location data".to_string());
+    }
+
+    Ok(Session::new(replay, race_control, pit_stops))
+}
+
+/// The `render` command: writes part of the replay to images.
+fn render_replay(source: &SourceArgs, display: &DisplayArgs, args: RenderArgs) -> Result<(), String> {
+    let layout = display.board_layout()?.layout(true);
+    let session = load_session(source)?;
+    let filter = DriverFilter::default();
+
+    let options = RenderOptions {
+        from: args.from,
+        to: args.to.unwrap_or(args.from + Duration::from_secs(10)),
+        fps: args.fps,
+        width: args.width,
+        height: args.height,
+        physical: args.physical.then(LedModel::default),
+        padding: display.padding,
+    };
+    let count = render::export(
+        &session.board(&filter, true),
+        &layout,
+        session.reference_lap.as_deref(),
+        &options,
+        &args.output,
+    )?;
+    eprintln!("Wrote {} frames to {}", count, args.output.display());
+    Ok(())
+}
+
+/// The `svg` command: writes the board at one moment to an SVG.
+fn export_svg(source: &SourceArgs, display: &DisplayArgs, args: SvgArgs) -> Result<(), String> {
+    let layout = display.board_layout()?.layout(true);
+    let session = load_session(source)?;
+    let filter = DriverFilter::default();
+
+    let (frame, positions) = session
+        .board(&filter, true)
+        .frame_at(args.at, &mut Trails::new(TrailMode::Off))
+        .ok_or("the replay has no data at that time")?;
+    let svg = svg::board_svg(
+        &layout,
+        Some(&frame),
+        &positions,
+        &filter,
+        &format_elapsed(args.at),
+    );
+    fs::write(&args.output, svg).map_err(|e| e.to_string())?;
+    eprintln!("Wrote {}", args.output.display());
+    Ok(())
+}
+
+/// The `mock-api` command: serves an offline session as a live one.
+fn serve_mock_api(source: &SourceArgs, args: MockApiArgs) -> Result<(), String> {
+    if !source.is_offline() {
+        return Err("the mock API serves a CSV export or a generated race, see --csv and --simulate".to_string());
+    }
+    let (replay, _) = load_offline(source)?;
+    let session = LiveSession::new(&replay, args.from, args.delay)?;
+    mock_api::serve(&session, args.port)
+}
+
+/// The `mock-broker` command: publishes an offline session over MQTT.
+fn run_mock_broker(source: &SourceArgs, args: MockBrokerArgs) -> Result<(), String> {
+    if !source.is_offline() {
+        return Err("the mock broker publishes a CSV export or a generated race, see --csv and --simulate".to_string());
+    }
+    let (replay, _) = load_offline(source)?;
+    let session = LiveSession::new(&replay, args.from, args.delay)?;
+
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+    runtime.block_on(mock_broker::run(
+        &session,
+        args.port,
+        &args.topic,
+        args.duplicates,
+        args.disconnect_every,
+    ))
+}
+
+fn run_mock_wled(args: MockWledArgs) -> Result<(), String> {
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+    runtime.block_on(mock_wled::run(args.ddp_port, args.http_port, args.leds))
+}
+
+fn run_emulator(outputs: &OutputArgs, display: &DisplayArgs, args: EmulatorArgs) -> Result<(), String> {
+    let chain = outputs.chain_map()?;
+    let layout = display.board_layout()?.layout(true);
+    let listen = emulator::Listen {
+        port: args.port,
+        ddp_port: args.ddp_port,
+        pixels: chain.pixel_count(),
+        ddp_offset: outputs.wled_offset,
+    };
+    match args.headless {
+        true => emulator::run_headless(listen, chain, layout, display.padding, args.snapshot.as_deref()),
+        false => emulator::run_window(listen, chain, layout, display.padding),
+    }
+}
+
+/// The `headless` command: plays the replay with no window, for the
+/// clients of the frame server, the instances it leads and its outputs.
+fn run_headless(
+    source: &SourceArgs,
+    sync: &SyncArgs,
+    outputs: &OutputArgs,
+    playback: &PlaybackArgs,
+    display: &DisplayArgs,
+    args: HeadlessArgs,
+) -> Result<(), String> {
+    let layout = display.board_layout()?.layout(true);
+    let power_budget = outputs.power_budget();
+    let led_model = LedModel::default();
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+    runtime.block_on(async {
+        let mut outputs = Outputs::new(outputs)?;
+        let mut session_key = source.session.clone();
+        let mut session = open_session(source, source.api()).await?;
+        if !source.is_offline() {
+            config::remember_session(&session_key);
+        }
+        let filter = DriverFilter::default();
+        let mut trails = Trails::new(TrailMode::Off);
+
+        let (controls, mut received) = tokio::sync::mpsc::channel(16);
+        let frames = server::serve(args.port, controls).await?;
+        let mut leader = match sync.lead.is_empty() {
+            true => None,
+            false => Some(Leader::new(sync.lead.clone())?),
+        };
+        let beacons = match sync.follow {
+            Some(port) => Some(sync::bind(port).await?),
+            None => None,
+        };
+        let mut follower = beacons.as_ref().map(|_| FollowerClock::new(Instant::now()));
+
+        let tick = playback.tick();
+        let mut ticks = tokio::time::interval(tick);
+        let mut heartbeat = tokio::time::interval(sync::BEACON_INTERVAL);
+        let mut elapsed = Duration::ZERO;
+        let mut playing = !args.paused;
+        let mut speed = playback.speed;
+        let mut frames_built = 0;
+
+        loop {
+            // Whether the event changes the board, which is then built and
+            // served.
+            let mut build = true;
+            let mut open = None;
+            tokio::select! {
+                _ = ticks.tick() => match &mut follower {
+                    // Following a leader, frames are built when the
+                    // leader's are.
+                    Some(clock) => {
+                        elapsed = clock.advance(Instant::now());
+                        build = !clock.hears_leader(Instant::now(), 2 * tick);
+                    }
+                    None if playing => elapsed += tick.mul_f32(speed),
+                    None => {}
+                },
+                _ = heartbeat.tick(), if leader.is_some() => build = false,
+                beacon = async {
+                    match &beacons {
+                        Some(socket) => sync::receive(socket).await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    let clock = follower.as_mut().expect("beacons are only received by followers");
+                    build = clock.receive(&beacon, Instant::now());
+                    elapsed = clock.elapsed();
+                    playing = clock.playing();
+                    speed = beacon.speed;
+                    if beacon.session != session_key && !source.is_offline() {
+                        open = Some(beacon.session);
+                    }
+                }
+                Some(control) = received.recv() => match control {
+                    Control::Play => playing = true,
+                    Control::Pause => playing = false,
+                    Control::Seek { seconds } => {
+                        elapsed = Duration::try_from_secs_f64(seconds).unwrap_or_default();
+                        trails.clear();
+                    }
+                    Control::Speed { speed: new_speed } => speed = new_speed,
+                    Control::Session { key } if source.is_offline() => {
+                        eprintln!("Cannot load session {}: the session is not from OpenF1", key);
+                    }
+                    Control::Session { key } => open = Some(key),
+                },
+            }
+
+            if let Some(key) = open {
+                match open_session(source, Api::new(&source.api_url, &key)).await {
+                    Ok(opened) => {
+                        config::remember_session(&key);
+                        session = opened;
+                        session_key = key;
+                        elapsed = Duration::ZERO;
+                        trails.clear();
+                    }
+                    Err(e) => eprintln!("Failed to load session {}: {}", key, e),
+                }
+            }
+            elapsed = elapsed.min(session.replay.duration());
+
+            if build {
+                frames_built += 1;
+                let board = session.board(&filter, true);
+                let (frame, positions) = board
+                    .limited_frame_at(elapsed, &mut trails, &power_budget, &led_model, &layout)
+                    .map_or((None, Vec::new()), |(frame, positions)| (Some(frame), positions));
+                if let Some(frame) = &frame {
+                    outputs.send(frame);
+                }
+                let time = session.replay.time_at(elapsed);
+                frames.publish(&FrameState {
+                    elapsed_ms: elapsed.as_millis() as u64,
+                    time,
+                    playing,
+                    speed,
+                    session: session_key.clone(),
+                    status: time.and_then(|time| session.race_control.status_at(time).label()),
+                    leds: server::led_states(frame.as_ref()),
+                    drivers: server::driver_states(&positions),
+                });
+            }
+            if let Some(leader) = &mut leader {
+                leader.send(&session_key, elapsed, frames_built, playing, speed);
+            }
+        }
+    })
 }
 
 struct Race {
     duration: Duration,
     state: State,
+    last_tick: Instant,
     update_frame: Option<UpdateFrame>,
-    client: Client,
-    driver_index: usize,
-    max_calls: usize,
+    positions: Vec<DriverPosition>,
+    hovered: Option<u32>,
+    pinned: Option<Pinned>,
+    telemetry: Telemetry,
+    replay: Option<Replay>,
+    reference_lap: Option<Vec<(f32, f32)>>,
+    race_control: RaceControl,
+    pit_stops: PitStops,
+    standings: Standings,
+    laps: LapCount,
+    session_info: Option<SessionInfo>,
+    pit_lane_leds: bool,
+    board_layout: BoardLayout,
+    layout: Layout,
+    /// Space kept clear around the board, in pixels.
+    padding: f32,
+    theme: ThemeChoice,
+    /// Fullscreen with only the board and the overlays.
+    presenting: bool,
+    fullscreen: bool,
+    keys: Keymap,
+    track_cache: Cache,
+    physical_cache: Cache,
+    render_mode: RenderMode,
+    led_model: LedModel,
+    power_budget: PowerBudget,
+    power_report: Option<PowerReport>,
+    /// Counts the changes that put a power report out of date, so that one
+    /// measured before the latest is dropped when it comes in.
+    power_analysis: u64,
+    analyzing_power: bool,
+    filter: DriverFilter,
+    team_choice: TeamChoice,
+    follow_highlighted: bool,
+    trails: Trails,
+    trail_kind: TrailKind,
+    trail_length: u8,
+    source: SourceArgs,
+    api: Api,
+    /// Whether a poll of the live session is waiting for its answer.
+    polling: bool,
+    /// How many times as fast as the session ran the replay is played.
+    speed: f32,
+    /// Interval at which the board is rebuilt.
+    tick: Duration,
+    serve_port: Option<u16>,
+    frames: Option<Frames>,
+    leader: Option<Leader>,
+    follower: Option<FollowerClock>,
+    follow_port: Option<u16>,
+    /// Frames built since the start, which followers build theirs along.
+    frames_built: u64,
+    outputs: Outputs,
 }
 
 enum State {
@@ -45,31 +462,136 @@ enum State {
     Displaying,
 }
 
+/// What the detail card is showing: every driver on an LED, or one driver
+/// wherever they are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pinned {
+    Led(u32),
+    Driver(u32),
+}
+
 #[derive(Debug, Clone)]
 enum Message {
     Toggle,
     Reset,
     Tick(Instant),
-    DataFetched(Result<UpdateFrame, String>),
+    Poll,
+    LocationsPolled(Result<Vec<LocationData>, String>),
+    LocationsReceived(Vec<LocationData>),
+    Server(ServerEvent),
+    Heartbeat,
+    Beacon(Beacon),
+    DataFetched(Result<Replay, String>),
+    OfflineLoaded(Result<(Replay, RaceControl), String>),
+    RaceControlFetched(Result<RaceControl, String>),
+    PitFetched(Result<PitStops, String>),
+    PositionsFetched(Result<Standings, String>),
+    SessionInfoFetched(Result<SessionInfo, String>),
+    TogglePitLaneLeds(bool),
+    ToggleDarkTheme(bool),
+    Present(bool),
+    KeyPressed(Key, Modifiers),
+    ToggleDriver(u32, bool),
+    TeamSelected(TeamChoice),
+    HighlightSelected(Highlight),
+    ToggleFollow(bool),
+    DriversAdded(Result<Replay, String>),
+    TrailKindSelected(TrailKind),
+    TrailLengthChanged(u8),
+    RenderModeSelected(RenderMode),
+    GammaChanged(f32),
+    BrightnessChanged(f32),
+    MaxCurrentChanged(f32),
+    ChannelCurrentChanged(f32),
+    TogglePowerLimit(bool),
+    PowerBudgetChanged(f32),
+    AnalyzePower,
+    PowerAnalyzed(u64, Option<PowerReport>),
+    ExportSvg,
+    LedHovered(Option<u32>),
+    LedClicked(Option<u32>),
+    DriverPinned(u32),
+    Unpin,
+    CarDataFetched(u32, DateTime<Utc>, Result<Vec<CarData>, String>),
+    LapsFetched(u32, Result<Vec<LapData>, String>),
 }
 
 impl Application for Race {
     type Message = Message;
     type Theme = Theme;
     type Executor = executor::Default;
-    type Flags = ();
+    type Flags = (Cli, BoardLayout);
 
-    fn new(_flags: ()) -> (Race, Command<Message>) {
+    fn new((cli, board_layout): Self::Flags) -> (Race, Command<Message>) {
+        let Cli { source, window, sync, outputs: outputs_args, playback, display, keys, .. } = cli;
+        let api = source.api();
+        let outputs = Outputs::new(&outputs_args).unwrap_or_else(|e| {
+            eprintln!("Failed to set up the outputs: {}", e);
+            Outputs::default()
+        });
+        let leader = if sync.lead.is_empty() {
+            None
+        } else {
+            Leader::new(sync.lead)
+                .map_err(|e| eprintln!("Failed to lead the other boards: {}", e))
+                .ok()
+        };
         (
             Race {
                 duration: Duration::default(),
                 state: State::Idle,
+                last_tick: Instant::now(),
                 update_frame: None,
-                client: Client::new(),
-                driver_index: 0,
-                max_calls: 20,
+                positions: Vec::new(),
+                hovered: None,
+                pinned: None,
+                telemetry: Telemetry::default(),
+                replay: None,
+                reference_lap: None,
+                race_control: RaceControl::default(),
+                pit_stops: PitStops::default(),
+                standings: Standings::default(),
+                laps: LapCount::default(),
+                session_info: None,
+                pit_lane_leds: true,
+                layout: board_layout.layout(true),
+                board_layout,
+                padding: display.padding,
+                theme: display.theme,
+                presenting: display.present,
+                fullscreen: display.present,
+                keys,
+                track_cache: Cache::new(),
+                physical_cache: Cache::new(),
+                render_mode: RenderMode::Ideal,
+                led_model: LedModel::default(),
+                power_budget: outputs_args.power_budget(),
+                power_report: None,
+                power_analysis: 0,
+                analyzing_power: false,
+                filter: DriverFilter::default(),
+                team_choice: TeamChoice::All,
+                follow_highlighted: false,
+                trails: Trails::new(TrailMode::Off),
+                trail_kind: TrailKind::Off,
+                trail_length: 3,
+                source,
+                api,
+                polling: false,
+                speed: playback.speed,
+                tick: playback.tick(),
+                serve_port: window.serve,
+                frames: None,
+                leader,
+                follower: sync.follow.map(|_| FollowerClock::new(Instant::now())),
+                follow_port: sync.follow,
+                frames_built: 0,
+                outputs,
+            },
+            match display.present {
+                true => window::change_mode(window::Id::MAIN, window::Mode::Fullscreen),
+                false => Command::none(),
             },
-            Command::none(),
         )
     }
 
@@ -80,19 +602,60 @@ impl Application for Race {
     fn update(&mut self, message: Message) -> Command<Message> {
         match message {
             Message::Toggle => match self.state {
-                State::Idle => {
+                State::Idle if self.replay.is_some() => {
+                    self.state = State::Displaying;
+                    self.last_tick = Instant::now();
+                }
+                State::Idle if self.source.is_offline() => {
                     self.state = State::Fetching;
                     self.update_frame = None;
-                    self.driver_index = 0;
+                    let source = self.source.clone();
                     return Command::perform(
-                        fetch_driver_data(
-                            self.client.clone(),
-                            self.driver_index,
-                            self.max_calls,
-                        ), 
-                        Message::DataFetched
+                        async move { load_offline(&source) },
+                        Message::OfflineLoaded,
                     );
                 }
+                State::Idle => {
+                    self.state = State::Fetching;
+                    self.update_frame = None;
+                    let locations = if self.source.live {
+                        self.polling = true;
+                        Command::perform(
+                            fetch_new_locations(self.api.clone(), None),
+                            Message::LocationsPolled
+                        )
+                    } else if self.source.mqtt.is_some() {
+                        // The broker pushes the samples from here on.
+                        Command::none()
+                    } else {
+                        Command::perform(
+                            fetch_driver_data(
+                                self.api.clone(),
+                                self.filter.visible_drivers().map(|d| d.number).collect(),
+                            ),
+                            Message::DataFetched
+                        )
+                    };
+                    return Command::batch([
+                        locations,
+                        Command::perform(
+                            fetch_race_control(self.api.clone()),
+                            Message::RaceControlFetched
+                        ),
+                        Command::perform(
+                            fetch_pit_stops(self.api.clone()),
+                            Message::PitFetched
+                        ),
+                        Command::perform(
+                            fetch_standings(self.api.clone()),
+                            Message::PositionsFetched
+                        ),
+                        Command::perform(
+                            fetch_session_info(self.api.clone()),
+                            Message::SessionInfoFetched
+                        ),
+                    ]);
+                }
                 State::Fetching => {
                     self.state = State::Idle;
                 }
@@ -102,40 +665,329 @@ impl Application for Race {
             },
             Message::Tick(now) => {
                 if let State::Displaying = &mut self.state {
-                    self.duration += now - Instant::now();
+                    let build = match &mut self.follower {
+                        // Following a leader, frames are built when the
+                        // leader's are.
+                        Some(clock) => {
+                            self.duration = clock.advance(now);
+                            !clock.hears_leader(now, 2 * self.tick)
+                        }
+                        None => {
+                            self.duration += (now - self.last_tick).mul_f32(self.speed);
+                            true
+                        }
+                    };
+                    self.last_tick = now;
+                    if let Some(replay) = &self.replay {
+                        if self.duration >= replay.duration() {
+                            self.duration = replay.duration();
+                            // A live session waits at the newest sample
+                            // for the next ones instead of ending.
+                            if !self.source.is_live() {
+                                self.state = State::Idle;
+                            }
+                        }
+                    }
+                    if build {
+                        self.update_frame = self.frame_at(self.duration);
+                    }
+                    return self.fetch_telemetry();
+                }
+            }
+            Message::Heartbeat => {
+                self.send_beacon();
+            }
+            Message::Beacon(beacon) => {
+                return self.follow(beacon);
+            }
+            Message::Poll => {
+                if !self.polling {
+                    self.polling = true;
+                    let after = self.replay.as_ref().and_then(poll_after);
+                    return Command::perform(
+                        fetch_new_locations(self.api.clone(), after),
+                        Message::LocationsPolled,
+                    );
                 }
             }
+            Message::LocationsPolled(Ok(data)) => {
+                self.polling = false;
+                return self.add_live_locations(data);
+            }
+            Message::LocationsPolled(Err(e)) => {
+                self.polling = false;
+                eprintln!("Failed to poll the live session: {}", e);
+            }
+            Message::LocationsReceived(data) => {
+                return self.add_live_locations(data);
+            }
+            Message::Server(ServerEvent::Started(frames)) => {
+                self.frames = Some(frames);
+                self.publish_frame(self.update_frame.as_ref());
+            }
+            Message::Server(ServerEvent::Control(control)) => {
+                let command = self.control(control);
+                self.publish_frame(self.update_frame.as_ref());
+                return command;
+            }
             Message::Reset => {
                 self.duration = Duration::default();
                 self.update_frame = None;
+                self.trails.clear();
             }
-            Message::DataFetched(Ok(new_frame)) => {
-                self.update_frame = Some(new_frame);
-                if self.update_frame.is_some() {
-                    self.state = State::Displaying;
-                } else {
+            Message::DataFetched(Ok(replay)) => {
+                if replay.is_empty() {
                     self.state = State::Idle;
+                } else {
+                    if self.pit_stops.is_empty() {
+                        self.pit_stops = PitStops::from_replay(&replay);
+                    }
+                    self.reference_lap = circuit::reference_lap(&replay);
+                    self.laps = LapCount::new(&replay);
+                    self.clear_track_caches();
+                    self.replay = Some(replay);
+                    if !self.source.is_offline() {
+                        config::remember_session(&self.source.session);
+                    }
+                    self.state = State::Displaying;
+                    self.last_tick = Instant::now();
+                    self.update_frame = self.frame_at(self.duration);
                 }
             }
             Message::DataFetched(Err(_)) => {
                 self.state = State::Idle;
             }
+            Message::OfflineLoaded(Ok((replay, race_control))) => {
+                self.race_control = race_control;
+                return self.update(Message::DataFetched(Ok(replay)));
+            }
+            Message::OfflineLoaded(Err(e)) => {
+                eprintln!("Failed to load the session: {}", e);
+                self.state = State::Idle;
+            }
+            Message::RaceControlFetched(Ok(race_control)) => {
+                self.race_control = race_control;
+            }
+            Message::RaceControlFetched(Err(e)) => {
+                eprintln!("Failed to fetch race control messages: {}", e);
+            }
+            Message::PitFetched(Ok(pit_stops)) => {
+                if !pit_stops.is_empty() {
+                    self.pit_stops = pit_stops;
+                }
+            }
+            Message::PitFetched(Err(e)) => {
+                eprintln!("Failed to fetch pit stops: {}", e);
+            }
+            Message::PositionsFetched(Ok(standings)) => {
+                self.standings = standings;
+            }
+            Message::PositionsFetched(Err(e)) => {
+                eprintln!("Failed to fetch positions: {}", e);
+            }
+            Message::SessionInfoFetched(Ok(info)) => {
+                self.session_info = Some(info);
+            }
+            Message::SessionInfoFetched(Err(e)) => {
+                eprintln!("Failed to fetch the session: {}", e);
+            }
+            Message::TogglePitLaneLeds(enabled) => {
+                self.pit_lane_leds = enabled;
+                self.layout = self.board_layout.layout(enabled);
+                self.clear_track_caches();
+                self.refresh_frame();
+            }
+            Message::ToggleDarkTheme(dark) => {
+                self.theme = if dark { ThemeChoice::Dark } else { ThemeChoice::Light };
+                self.clear_track_caches();
+            }
+            Message::Present(presenting) => {
+                return self.set_window(presenting, presenting);
+            }
+            Message::KeyPressed(key, modifiers) => {
+                if let Some(action) = self.keys.action(&key, modifiers) {
+                    return self.shortcut(action);
+                }
+            }
+            Message::ToggleDriver(driver_number, visible) => {
+                self.filter.set_visible(driver_number, visible);
+                self.refresh_frame();
+                return self.fetch_missing_drivers();
+            }
+            Message::TeamSelected(team_choice) => {
+                self.team_choice = team_choice;
+                match team_choice {
+                    TeamChoice::All => self.filter.show_all(),
+                    TeamChoice::Team(team) => self.filter.isolate_team(team),
+                }
+                self.refresh_frame();
+                return self.fetch_missing_drivers();
+            }
+            Message::HighlightSelected(highlight) => {
+                self.filter.highlight(match highlight {
+                    Highlight::None => None,
+                    Highlight::Driver(number) => Some(number),
+                });
+                self.clear_track_caches();
+                self.refresh_frame();
+            }
+            Message::ToggleFollow(follow) => {
+                self.follow_highlighted = follow;
+                self.clear_track_caches();
+            }
+            Message::DriversAdded(Ok(added)) => {
+                if let Some(replay) = &mut self.replay {
+                    replay.merge(added);
+                    self.laps = LapCount::new(replay);
+                }
+                self.refresh_frame();
+            }
+            Message::DriversAdded(Err(e)) => {
+                eprintln!("Failed to fetch driver data: {}", e);
+            }
+            Message::TrailKindSelected(trail_kind) => {
+                self.trail_kind = trail_kind;
+                self.apply_trail_mode();
+            }
+            Message::TrailLengthChanged(trail_length) => {
+                self.trail_length = trail_length;
+                self.apply_trail_mode();
+            }
+            Message::RenderModeSelected(render_mode) => {
+                self.render_mode = render_mode;
+            }
+            Message::GammaChanged(gamma) => {
+                self.led_model.gamma = gamma;
+                self.refresh_frame();
+            }
+            Message::BrightnessChanged(brightness) => {
+                self.led_model.brightness = brightness;
+                self.refresh_frame();
+            }
+            Message::MaxCurrentChanged(max_current_ma) => {
+                self.led_model.max_current_ma = max_current_ma;
+                self.refresh_frame();
+            }
+            Message::ChannelCurrentChanged(channel_ma) => {
+                self.led_model.channel_ma = channel_ma;
+                self.refresh_frame();
+            }
+            Message::TogglePowerLimit(enabled) => {
+                self.power_budget.enabled = enabled;
+                self.refresh_frame();
+            }
+            Message::PowerBudgetChanged(limit_ma) => {
+                self.power_budget.limit_ma = limit_ma;
+                self.refresh_frame();
+            }
+            Message::AnalyzePower => {
+                return self.analyze_power();
+            }
+            Message::PowerAnalyzed(analysis, report) => {
+                if analysis == self.power_analysis {
+                    self.power_report = report;
+                    self.analyzing_power = false;
+                }
+            }
+            Message::ExportSvg => {
+                let seconds = self.duration.as_secs();
+                let path = format!(
+                    "board_{:0>2}-{:0>2}-{:0>2}.svg",
+                    seconds / 3600,
+                    seconds / 60 % 60,
+                    seconds % 60,
+                );
+                let svg = svg::board_svg(
+                    &self.layout,
+                    self.update_frame.as_ref(),
+                    &self.positions,
+                    &self.filter,
+                    &format_elapsed(self.duration),
+                );
+                match fs::write(&path, svg) {
+                    Ok(()) => eprintln!("Wrote {}", path),
+                    Err(e) => eprintln!("Failed to write {}: {}", path, e),
+                }
+            }
+            Message::LedHovered(hovered) => {
+                self.hovered = hovered;
+            }
+            Message::LedClicked(led_number) => {
+                self.pinned = led_number.map(Pinned::Led);
+                return self.fetch_telemetry();
+            }
+            Message::DriverPinned(driver_number) => {
+                self.pinned = Some(Pinned::Driver(driver_number));
+                return self.fetch_telemetry();
+            }
+            Message::Unpin => {
+                self.pinned = None;
+            }
+            Message::CarDataFetched(_, _, Ok(data)) => {
+                self.telemetry.add_car_data(data);
+            }
+            Message::LapsFetched(_, Ok(data)) => {
+                self.telemetry.add_laps(data);
+            }
+            // Released so that the next look at the driver tries again.
+            Message::CarDataFetched(driver_number, start, Err(e)) => {
+                eprintln!("Failed to fetch the car data of driver {}: {}", driver_number, e);
+                self.telemetry.car_data_failed(driver_number, start);
+            }
+            Message::LapsFetched(driver_number, Err(e)) => {
+                eprintln!("Failed to fetch the laps of driver {}: {}", driver_number, e);
+                self.telemetry.laps_failed(driver_number);
+            }
         }
 
         Command::none()
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        match self.state {
+        let tick = match self.state {
             State::Idle | State::Fetching => Subscription::none(),
-            State::Displaying => time::every(Duration::from_millis(1000)).map(Message::Tick),
-        }
+            State::Displaying => time::every(self.tick).map(Message::Tick),
+        };
+        // A live session keeps coming in while the replay is paused.
+        let following = self.replay.is_some() || matches!(self.state, State::Fetching);
+        let live = match &self.source.mqtt {
+            Some(broker) if following => {
+                mqtt::locations(broker.clone(), self.source.mqtt_topic.clone())
+                    .map(Message::LocationsReceived)
+            }
+            None if following && self.source.live => time::every(LIVE_POLL).map(|_| Message::Poll),
+            _ => Subscription::none(),
+        };
+        let server = match self.serve_port {
+            Some(port) => server::subscription(port).map(Message::Server),
+            None => Subscription::none(),
+        };
+        let sync = match self.follow_port {
+            Some(port) => sync::beacons(port).map(Message::Beacon),
+            None if self.leader.is_some() => time::every(sync::BEACON_INTERVAL).map(|_| Message::Heartbeat),
+            None => Subscription::none(),
+        };
+        // Keys a widget handled, such as the arrows moving a slider, are
+        // not shortcuts.
+        let keys = event::listen_with(|event, status| match (event, status) {
+            (
+                iced::Event::Keyboard(keyboard::Event::KeyPressed { key, modifiers, .. }),
+                event::Status::Ignored,
+            ) => Some(Message::KeyPressed(key, modifiers)),
+            _ => None,
+        });
+        Subscription::batch([tick, live, server, sync, keys])
     }
 
-    fn view(&self) -> Element<Message> {
+    fn view(&self) -> Element<'_, Message> {
         if let State::Fetching = self.state {
+            let label = if self.source.is_live() {
+                "WAITING FOR LIVE DATA..."
+            } else {
+                "DOWNLOADING DATA..."
+            };
             return container(
-                text("DOWNLOADING DATA...")
+                text(label)
                     .size(50)
                     .horizontal_alignment(alignment::Horizontal::Center)
                     .vertical_alignment(alignment::Vertical::Center),
@@ -146,20 +998,11 @@ impl Application for Race {
             .align_y(alignment::Vertical::Center)
             .into();
         }
+        if self.presenting {
+            return self.presentation_view();
+        }
 
-        const MINUTE: u64 = 60;
-        const HOUR: u64 = 60 * MINUTE;
-
-        let seconds = self.duration.as_secs();
-
-        let duration = text(format!(
-            "{:0>2}:{:0>2}:{:0>2}.{:0>2}",
-            seconds / HOUR,
-            (seconds % HOUR) / MINUTE,
-            seconds % MINUTE,
-            self.duration.subsec_millis() / 10,
-        ))
-        .size(40);
+        let duration = text(format_elapsed(self.duration)).size(40);
 
         let button = |label| {
             button(
@@ -182,7 +1025,25 @@ impl Application for Race {
             .style(theme::Button::Destructive)
             .on_press(Message::Reset);
 
-        let duration_container = container(duration)
+        let svg_button = button("SVG")
+            .style(theme::Button::Secondary)
+            .on_press(Message::ExportSvg);
+
+        let present_button = button("Present")
+            .style(theme::Button::Secondary)
+            .on_press(Message::Present(true));
+
+        let status_label = text(self.status_label().unwrap_or_default()).size(20);
+
+        let live_label = text(self.live_label().unwrap_or_default())
+            .size(20)
+            .style(iced::Color::from_rgb(0.8, 0.0, 0.0));
+
+        let sync_label = text(self.sync_label().unwrap_or_default()).size(16);
+
+        let duration_container = container(
+            column![duration, status_label, live_label, sync_label].spacing(5)
+        )
             .padding(10)
             .align_x(alignment::Horizontal::Left)
             .align_y(alignment::Vertical::Bottom)
@@ -190,6 +1051,8 @@ impl Application for Race {
 
         let buttons_container = container(
             row![
+                container(present_button).padding(10),
+                container(svg_button).padding(10),
                 container(toggle_button).padding(10),
                 container(reset_button).padding(10)
             ]
@@ -200,22 +1063,28 @@ impl Application for Race {
         .align_y(alignment::Vertical::Bottom)
         .width(Length::FillPortion(1));
 
+        let pit_lane_toggle = checkbox("Pit lane LEDs", self.pit_lane_leds)
+            .on_toggle(Message::TogglePitLaneLeds);
+
+        let dark_toggle = checkbox("Dark theme", self.theme == ThemeChoice::Dark)
+            .on_toggle(Message::ToggleDarkTheme);
+
         let bottom_row = row![
             duration_container,
+            column![pit_lane_toggle, dark_toggle].spacing(10),
             buttons_container
         ]
+        .align_items(Alignment::Center)
         .width(Length::Fill);
 
-        let canvas = Canvas::new(Graph {
-            data: LED_DATA.to_vec(),
-            update_frame: self.update_frame.clone(),
-        })
-        .width(Length::Fill)
-        .height(Length::Fill);
-
         container(
             column![
-                canvas,
+                row![
+                    self.drivers_view(),
+                    self.board_view(),
+                    column![self.detail_view(), self.standings_view()].spacing(20)
+                ]
+                .spacing(20),
                 bottom_row
             ]
             .spacing(20)
@@ -227,108 +1096,772 @@ impl Application for Race {
     }
 
     fn theme(&self) -> Theme {
-        Theme::Light
+        match self.theme {
+            ThemeChoice::Light => Theme::Light,
+            ThemeChoice::Dark => Theme::Dark,
+        }
     }
 }
 
-struct Graph {
-    data: Vec<LedCoordinate>,
-    update_frame: Option<UpdateFrame>,
-}
+impl Race {
+    /// The board as the render mode shows it.
+    fn board_view(&self) -> Element<'_, Message> {
+        let graph = |track_cache, physical| {
+            Canvas::new(Graph {
+                layout: &self.layout,
+                track_cache,
+                update_frame: self.update_frame.as_ref(),
+                positions: &self.positions,
+                reference_lap: self.reference_lap.as_deref(),
+                physical,
+                hovered: self.hovered,
+                follow: self.follow_point(),
+                padding: self.padding,
+            })
+            .width(Length::Fill)
+            .height(Length::Fill)
+        };
+        let ideal = || graph(&self.track_cache, None);
+        let physical = || graph(&self.physical_cache, Some(self.led_model));
 
-impl<Message> Program<Message> for Graph {
-    type State = ();
-
-    fn draw(
-        &self,
-        _state: &Self::State,
-        _renderer: &Renderer,
-        _theme: &Theme,
-        bounds: iced::Rectangle,
-        _cursor: mouse::Cursor,
-    ) -> Vec<canvas::Geometry> {
-        let mut frame = Frame::new(_renderer, bounds.size());
-
-        let (min_x, max_x, min_y, max_y) = self.data.iter().fold(
-            (f32::MAX, f32::MIN, f32::MAX, f32::MIN),
-            |(min_x, max_x, min_y, max_y), led| {
-                (
-                    min_x.min(led.x_led),
-                    max_x.max(led.x_led),
-                    min_y.min(led.y_led),
-                    max_y.max(led.y_led),
-                )
-            },
-        );
+        match self.render_mode {
+            RenderMode::Ideal => ideal().into(),
+            RenderMode::Physical => physical().into(),
+            RenderMode::SideBySide => row![ideal(), physical()].spacing(10).into(),
+        }
+    }
 
-        let width = max_x - min_x;
-        let height = max_y - min_y;
+    /// The board on its own, between a banner with the session and the lap
+    /// and one with the replay clock and track status, large enough to
+    /// read across a room.
+    fn presentation_view(&self) -> Element<'_, Message> {
+        let top = row![
+            text(self.session_title()).size(56),
+            horizontal_space(),
+            text(self.lap_label().unwrap_or_default()).size(56),
+        ]
+        .align_items(Alignment::Center);
 
-        // Apply padding
-        let padding = 50.0;
-        let scale_x = (bounds.width - 2.0 * padding) / width;
-        let scale_y = (bounds.height - 2.0 * padding) / height;
+        let bottom = row![
+            text(format_elapsed(self.duration)).size(40),
+            horizontal_space(),
+            text(self.status_label().unwrap_or_default()).size(40),
+            horizontal_space(),
+            text(self.live_label().unwrap_or_default())
+                .size(32)
+                .style(iced::Color::from_rgb(0.8, 0.0, 0.0)),
+        ]
+        .align_items(Alignment::Center);
 
-        // Draw the LED rectangles
-        if let Some(frame_data) = &self.update_frame {
-            for led in &self.data {
-                let x = (led.x_led - min_x) * scale_x + padding;
-                let y = bounds.height - (led.y_led - min_y) * scale_y - padding;
+        container(column![top, self.board_view(), bottom].spacing(20))
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .padding(30)
+            .into()
+    }
 
-                let color = frame_data
-                    .led_states
-                    .iter()
-                    .find(|(num, _)| *num == led.led_number)
-                    .map(|(_, col)| Color::from_rgb8(col.0, col.1, col.2))
-                    .unwrap_or(Color::from_rgb(0.0, 0.0, 0.0));
+    /// Switches presentation mode and fullscreen.
+    fn set_window(&mut self, presenting: bool, fullscreen: bool) -> Command<Message> {
+        self.presenting = presenting;
+        self.clear_track_caches();
+        if fullscreen == self.fullscreen {
+            return Command::none();
+        }
+        self.fullscreen = fullscreen;
+        let mode = if fullscreen { window::Mode::Fullscreen } else { window::Mode::Windowed };
+        window::change_mode(window::Id::MAIN, mode)
+    }
 
-                let point = Path::rectangle(Point::new(x, y), Size::new(10.0, 10.0));
-                frame.fill(&point, color);
+    /// Carries out a keyboard shortcut.
+    fn shortcut(&mut self, action: Action) -> Command<Message> {
+        match action {
+            Action::PlayPause => self.update(Message::Toggle),
+            Action::Step(ticks) => self.seek_by(self.tick.as_secs_f64() * ticks as f64),
+            Action::Seek(seconds) => self.seek_by(seconds as f64),
+            Action::Speed(speed) => self.control(Control::Speed { speed }),
+            Action::CycleDriver(step) => {
+                let options: Vec<Highlight> = std::iter::once(Highlight::None)
+                    .chain(self.filter.visible_drivers().map(|driver| Highlight::Driver(driver.number)))
+                    .collect();
+                let current = match self.filter.highlighted() {
+                    Some(number) => Highlight::Driver(number),
+                    None => Highlight::None,
+                };
+                let index = options.iter().position(|option| *option == current).unwrap_or(0);
+                let next = (index as i32 + step).rem_euclid(options.len() as i32) as usize;
+                self.update(Message::HighlightSelected(options[next]))
             }
+            Action::Fullscreen => self.set_window(self.presenting, !self.fullscreen),
+            Action::Present => self.set_window(!self.presenting, !self.presenting),
+            Action::Exit => self.set_window(false, false),
+            Action::Reset => self.update(Message::Reset),
         }
+    }
 
-        vec![frame.into_geometry()]
+    /// Moves the replay `seconds` from where it is, as a seek from a client
+    /// of the frame server does.
+    fn seek_by(&mut self, seconds: f64) -> Command<Message> {
+        let seconds = (self.duration.as_secs_f64() + seconds).max(0.0);
+        self.control(Control::Seek { seconds })
+    }
+
+    /// What the session is: its OpenF1 name once it has been fetched.
+    fn session_title(&self) -> String {
+        if let Some(path) = &self.source.csv {
+            return path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        }
+        if let Some(seed) = self.source.simulate {
+            return format!("Simulated race {}", seed);
+        }
+        match &self.session_info {
+            Some(info) => info.title(),
+            None => format!("Session {}", self.source.session),
+        }
+    }
+
+    /// The lap the leader is on, out of the race length when it is known.
+    fn lap_label(&self) -> Option<String> {
+        let lap = self.laps.lap_at(self.current_time()?, self.total_laps());
+        Some(match self.total_laps() {
+            Some(total) => format!("LAP {}/{}", lap, total),
+            None => format!("LAP {}", lap),
+        })
+    }
+
+    /// Laps of the race, which only a generated one is known to have.
+    fn total_laps(&self) -> Option<u32> {
+        self.source.simulate.map(|_| self.source.laps)
+    }
+
+    fn status_label(&self) -> Option<&'static str> {
+        self.race_control.status_at(self.current_time()?).label()
     }
-}
 
-async fn fetch_driver_data(
-    client: Client,
-    driver_index: usize,
-    max_calls: usize,
-) -> Result<UpdateFrame, String> {
-    let session_key = "9149";
+    /// Builds the board for `elapsed` into the replay and moves the view to
+    /// it, with the power limiter applied.
+    fn frame_at(&mut self, elapsed: Duration) -> Option<UpdateFrame> {
+        // Built field by field, so the trails can be borrowed mutably
+        // alongside it.
+        let board = Board {
+            replay: self.replay.as_ref()?,
+            race_control: &self.race_control,
+            pit_stops: &self.pit_stops,
+            filter: &self.filter,
+            pit_lane_leds: self.pit_lane_leds,
+        };
+        let (frame, positions) =
+            board.limited_frame_at(elapsed, &mut self.trails, &self.power_budget, &self.led_model, &self.layout)?;
+        self.positions = positions;
+        if self.follow_point().is_some() {
+            // The camera moves with the followed driver, and the static
+            // layer with it.
+            self.clear_track_caches();
+        }
+        self.publish_frame(Some(&frame));
+        self.outputs.send(&frame);
+        self.frames_built += 1;
+        self.send_beacon();
+        Some(frame)
+    }
 
-    let mut update_frame = UpdateFrame::new(0);
-    let mut call_count = 0;
+    /// Sends the replay clock to the boards this one leads.
+    fn send_beacon(&mut self) {
+        if let Some(leader) = &mut self.leader {
+            leader.send(
+                &self.source.session,
+                self.duration,
+                self.frames_built,
+                matches!(self.state, State::Displaying),
+                self.speed,
+            );
+        }
+    }
 
-    for driver in &DRIVERS[driver_index..] {
-        if call_count >= max_calls {
-            break;
+    /// Moves the replay along with the leader's clock: starts, stops and
+    /// switches sessions with it, and builds a frame when it does.
+    fn follow(&mut self, beacon: Beacon) -> Command<Message> {
+        if beacon.session != self.source.session && !self.source.is_offline() {
+            return self.control(Control::Session { key: beacon.session });
         }
+        let Some(clock) = &mut self.follower else {
+            return Command::none();
+        };
 
-        let url = format!(
-            "https://api.openf1.org/v1/location?session_key={}&driver_number={}",
-            session_key, driver.number,
-        );
-        eprintln!("url: {}", url);
-        let resp = client.get(&url).send().await.map_err(|e| e.to_string())?;
-        if resp.status().is_success() {
-            let data: Vec<LocationData> = resp.json().await.map_err(|e| e.to_string())?;
-            if let Some(location) = data.into_iter().find(|d| d.x != 0.0 && d.y != 0.0) {
-                eprintln!("Fetched and using data: {:?}", location); // Print debug statement
+        let now = Instant::now();
+        let build = clock.receive(&beacon, now);
+        let playing = clock.playing();
+        self.duration = clock.elapsed();
+        self.speed = beacon.speed;
+        match self.state {
+            State::Idle if playing && self.replay.is_none() => return self.update(Message::Toggle),
+            State::Idle if playing => {
+                self.state = State::Displaying;
+                self.last_tick = now;
+            }
+            State::Displaying if !playing => self.state = State::Idle,
+            _ => {}
+        }
+
+        let Some(replay) = &self.replay else {
+            return Command::none();
+        };
+        self.duration = self.duration.min(replay.duration());
+        if build {
+            self.update_frame = self.frame_at(self.duration);
+        }
+        Command::none()
+    }
+
+    /// Whether this board leads or follows others, and how far off the
+    /// leader it is.
+    fn sync_label(&self) -> Option<String> {
+        if let Some(leader) = &self.leader {
+            return Some(format!("LEADING {} board(s)", leader.followers()));
+        }
+        let clock = self.follower.as_ref()?;
+        Some(match clock.drift() {
+            Some(drift) if clock.hears_leader(Instant::now(), 2 * self.tick) => {
+                format!("FOLLOWING  drift {:+.0} ms", drift * 1000.0)
+            }
+            _ => "FOLLOWING  no leader".to_string(),
+        })
+    }
+
+    /// Sends `frame`, the board at the current replay time, to the clients
+    /// of the frame server.
+    fn publish_frame(&self, frame: Option<&UpdateFrame>) {
+        let Some(frames) = &self.frames else {
+            return;
+        };
+        let time = self.current_time();
+        frames.publish(&FrameState {
+            elapsed_ms: self.duration.as_millis() as u64,
+            time,
+            playing: matches!(self.state, State::Displaying),
+            speed: self.speed,
+            session: self.source.session.clone(),
+            status: time.and_then(|time| self.race_control.status_at(time).label()),
+            leds: server::led_states(frame),
+            drivers: server::driver_states(&self.positions),
+        });
+    }
+
+    /// Carries out a command from a client of the frame server.
+    fn control(&mut self, control: Control) -> Command<Message> {
+        match control {
+            Control::Play if !matches!(self.state, State::Displaying) => {
+                return self.update(Message::Toggle);
+            }
+            Control::Pause if matches!(self.state, State::Displaying) => {
+                self.state = State::Idle;
+            }
+            Control::Play | Control::Pause => {}
+            Control::Seek { seconds } => {
+                let Some(replay) = &self.replay else {
+                    return Command::none();
+                };
+                self.duration = Duration::try_from_secs_f64(seconds)
+                    .unwrap_or_default()
+                    .min(replay.duration());
+                self.trails.clear();
+                self.update_frame = self.frame_at(self.duration);
+            }
+            Control::Speed { speed } => {
+                self.speed = speed;
+            }
+            Control::Session { key } if self.source.is_offline() => {
+                eprintln!("Cannot load session {}: the session is not from OpenF1", key);
+            }
+            Control::Session { key } => {
+                self.api = Api::new(&self.source.api_url, &key);
+                self.source.session = key;
+                self.replay = None;
+                self.reference_lap = None;
+                self.race_control = RaceControl::default();
+                self.pit_stops = PitStops::default();
+                self.standings = Standings::default();
+                self.laps = LapCount::default();
+                self.session_info = None;
+                self.telemetry = Telemetry::default();
+                self.discard_power_report();
+                self.duration = Duration::ZERO;
+                self.update_frame = None;
+                self.positions.clear();
+                self.trails.clear();
+                self.clear_track_caches();
+                self.state = State::Idle;
+                return self.update(Message::Toggle);
+            }
+        }
+        Command::none()
+    }
+
+    /// Measures the current draw of the whole session, at the rate the
+    /// replay plays it back and with the current filter and trails. A whole
+    /// race takes a while, so it is measured on a copy of the session off
+    /// the window's thread.
+    fn analyze_power(&mut self) -> Command<Message> {
+        let Some(replay) = self.replay.clone() else {
+            return Command::none();
+        };
+        let race_control = self.race_control.clone();
+        let pit_stops = self.pit_stops.clone();
+        let filter = self.filter.clone();
+        let pit_lane_leds = self.pit_lane_leds;
+        let mut trails = Trails::new(self.trail_mode());
+        let (tick, led_model, layout, budget) = (self.tick, self.led_model, self.layout.clone(), self.power_budget);
+
+        let measure = move || {
+            let board = Board {
+                replay: &replay,
+                race_control: &race_control,
+                pit_stops: &pit_stops,
+                filter: &filter,
+                pit_lane_leds,
+            };
+            let duration = replay.duration();
+            let frames = (0..)
+                .map(|step| tick * step)
+                .take_while(|elapsed| *elapsed <= duration)
+                .filter_map(|elapsed| {
+                    let (frame, _) = board.frame_at(elapsed, &mut trails)?;
+                    Some((elapsed, frame))
+                });
+            PowerReport::measure(frames, &led_model, &layout, &budget)
+        };
+
+        self.analyzing_power = true;
+        let analysis = self.power_analysis;
+        Command::perform(
+            async move { tokio::task::spawn_blocking(measure).await.ok() },
+            move |report| Message::PowerAnalyzed(analysis, report),
+        )
+    }
+
+    /// Where the camera should stay centered: on the highlighted driver, if
+    /// follow mode is on.
+    fn follow_point(&self) -> Option<iced::Point> {
+        if !self.follow_highlighted {
+            return None;
+        }
+        let highlighted = self.filter.highlighted()?;
+        self.positions
+            .iter()
+            .find(|p| p.driver_number == highlighted)
+            .map(|p| iced::Point::new(p.x, p.y))
+    }
+
+    /// The LIVE indicator: how old the newest sample is, and how far the
+    /// board is behind it when it is not showing the newest one.
+    fn live_label(&self) -> Option<String> {
+        if !self.source.is_live() {
+            return None;
+        }
+        let replay = self.replay.as_ref()?;
+        let latency = (Utc::now() - replay.latest()?).to_std().unwrap_or_default();
+
+        let mut label = format!("LIVE  latency {:.1} s", latency.as_secs_f32());
+        let behind = replay.duration().saturating_sub(self.duration);
+        if behind > self.tick + LIVE_POLL {
+            label.push_str(&format!("  {:.0} s behind", behind.as_secs_f32()));
+        }
+        Some(label)
+    }
+
+    /// Adds samples of a live session to the replay, or starts the replay
+    /// with them.
+    fn add_live_locations(&mut self, data: Vec<LocationData>) -> Command<Message> {
+        match &mut self.replay {
+            Some(replay) => {
+                replay.append(data);
+                self.laps.update(replay);
+                if self.reference_lap.is_none() {
+                    self.reference_lap = circuit::reference_lap(replay);
+                    self.clear_track_caches();
+                }
+                Command::none()
+            }
+            // Before the session has samples, keep waiting for them rather
+            // than giving up.
+            None if data.is_empty() => Command::none(),
+            None => {
+                let mut replay = Replay::default();
+                replay.append(data);
+                self.update(Message::DataFetched(Ok(replay)))
+            }
+        }
+    }
+
+    fn current_time(&self) -> Option<DateTime<Utc>> {
+        self.replay.as_ref()?.time_at(self.duration)
+    }
+
+    fn pinned_drivers(&self) -> Vec<u32> {
+        match self.pinned {
+            Some(Pinned::Led(led_number)) => self
+                .positions
+                .iter()
+                .filter(|p| p.led_number == led_number && p.on_board())
+                .filter(|p| self.filter.is_visible(p.driver_number))
+                .map(|p| p.driver_number)
+                .collect(),
+            Some(Pinned::Driver(driver_number)) => vec![driver_number],
+            None => Vec::new(),
+        }
+    }
+
+    /// Requests the laps and the car data around the replay time of every
+    /// pinned driver that has not been downloaded yet.
+    fn fetch_telemetry(&mut self) -> Command<Message> {
+        let Some(time) = self.current_time() else {
+            return Command::none();
+        };
+
+        let mut commands = Vec::new();
+        for driver_number in self.pinned_drivers() {
+            if self.telemetry.request_laps(driver_number) {
+                commands.push(Command::perform(
+                    fetch_laps(self.api.clone(), driver_number),
+                    move |laps| Message::LapsFetched(driver_number, laps),
+                ));
+            }
+            if let Some(start) = self.telemetry.request_car_data(driver_number, time) {
+                commands.push(Command::perform(
+                    fetch_car_data(self.api.clone(), driver_number, start),
+                    move |data| Message::CarDataFetched(driver_number, start, data),
+                ));
+            }
+        }
+
+        Command::batch(commands)
+    }
+
+    /// The detail card of the pinned LED or driver.
+    fn detail_view(&self) -> Element<'_, Message> {
+        let Some(pinned) = self.pinned else {
+            return column![].into();
+        };
+
+        let title = match pinned {
+            Pinned::Led(led_number) => format!("LED {}", led_number),
+            Pinned::Driver(driver_number) => format!("Driver #{}", driver_number),
+        };
+        let close = button(text("Close").size(14))
+            .style(theme::Button::Text)
+            .on_press(Message::Unpin);
+
+        let time = self.current_time();
+        let drivers = self.pinned_drivers().into_iter().filter_map(find_driver).map(|driver| {
+            let lap = time.and_then(|time| self.telemetry.lap_at(driver.number, time));
+            let car = time.and_then(|time| self.telemetry.car_data_at(driver.number, time));
+
+            column![
+                text(format!("#{} {}", driver.number, driver.name)).size(16),
+                text(driver.team).size(14),
+                text(format!(
+                    "Lap {}",
+                    lap.map_or("-".to_string(), |lap| lap.to_string())
+                ))
+                .size(14),
+                text(format!(
+                    "Speed {} km/h  Gear {}",
+                    car.map_or("-".to_string(), |car| car.speed.to_string()),
+                    car.map_or("-".to_string(), |car| car.gear.to_string()),
+                ))
+                .size(14),
+            ]
+            .spacing(2)
+            .into()
+        });
+
+        container(
+            column![row![text(title).size(18), close].spacing(20).align_items(Alignment::Center)]
+                .extend(drivers)
+                .spacing(10),
+        )
+        .padding(10)
+        .style(theme::Container::Box)
+        .into()
+    }
 
-                let color = driver.color;
+    fn trail_mode(&self) -> TrailMode {
+        let length = self.trail_length as u64;
+        match self.trail_kind {
+            TrailKind::Off => TrailMode::Off,
+            TrailKind::Time => TrailMode::Time(Duration::from_secs(length)),
+            TrailKind::Leds => TrailMode::Leds(length as usize),
+        }
+    }
+
+    fn apply_trail_mode(&mut self) {
+        self.trails.set_mode(self.trail_mode());
+        self.refresh_frame();
+    }
+
+    fn clear_track_caches(&self) {
+        self.track_cache.clear();
+        self.physical_cache.clear();
+    }
+
+    /// Drops the power report, and the one being measured, once what they
+    /// were measured with changes.
+    fn discard_power_report(&mut self) {
+        self.power_report = None;
+        self.power_analysis += 1;
+        self.analyzing_power = false;
+    }
+
+    /// Rebuilds the frame after a setting that changes it. A power report
+    /// measured with the old setting no longer applies either.
+    fn refresh_frame(&mut self) {
+        self.discard_power_report();
+        if self.update_frame.is_some() {
+            self.update_frame = self.frame_at(self.duration);
+        }
+    }
+
+    /// Downloads the drivers that were hidden when the replay was fetched
+    /// and have since been made visible.
+    fn fetch_missing_drivers(&self) -> Command<Message> {
+        let Some(replay) = &self.replay else {
+            return Command::none();
+        };
+        if self.source.is_offline() {
+            return Command::none();
+        }
+
+        let missing: Vec<u32> = self
+            .filter
+            .visible_drivers()
+            .map(|driver| driver.number)
+            .filter(|number| !replay.has_driver(*number))
+            .collect();
+        if missing.is_empty() {
+            return Command::none();
+        }
+
+        Command::perform(
+            fetch_driver_data(self.api.clone(), missing),
+            Message::DriversAdded,
+        )
+    }
+
+    /// The driver picker: show or hide single drivers, isolate a team, or
+    /// highlight one driver while the rest are dimmed.
+    fn drivers_view(&self) -> Element<'_, Message> {
+        let team = pick_list(
+            TeamChoice::options(),
+            Some(self.team_choice),
+            Message::TeamSelected,
+        )
+        .width(180);
+
+        let highlight = pick_list(
+            Highlight::options(),
+            Some(match self.filter.highlighted() {
+                Some(number) => Highlight::Driver(number),
+                None => Highlight::None,
+            }),
+            Message::HighlightSelected,
+        )
+        .width(180);
 
-                let nearest_led = LED_DATA.iter()
-                    .min_by(|a, b| {
-                        let dist_a = ((a.x_led - location.x).powi(2) + (a.y_led - location.y).powi(2)).sqrt();
-                        let dist_b = ((b.x_led - location.x).powi(2) + (b.y_led - location.y).powi(2)).sqrt();
-                        dist_a.partial_cmp(&dist_b).unwrap()
-                    })
-                    .unwrap();
+        let follow = checkbox("Follow highlighted driver", self.follow_highlighted)
+            .on_toggle(Message::ToggleFollow)
+            .size(14)
+            .text_size(14);
 
-                update_frame.set_led_state(nearest_led.led_number, color);
-                call_count += 1;
+        let drivers = DRIVERS.iter().map(|driver| {
+            let number = driver.number;
+            checkbox(format!("#{} {}", number, driver.name), self.filter.is_visible(number))
+                .on_toggle(move |visible| Message::ToggleDriver(number, visible))
+                .size(14)
+                .text_size(14)
+                .into()
+        });
+
+        let trail_length_label = match self.trail_kind {
+            TrailKind::Off => String::new(),
+            TrailKind::Time => format!("Trail: {} s", self.trail_length),
+            TrailKind::Leds => format!("Trail: {} LEDs", self.trail_length),
+        };
+        let trail_kind = pick_list(
+            &TrailKind::ALL[..],
+            Some(self.trail_kind),
+            Message::TrailKindSelected,
+        )
+        .width(180);
+        let trail_length = slider(1..=10, self.trail_length, Message::TrailLengthChanged)
+            .width(180);
+
+        let render_mode = pick_list(
+            &RenderMode::ALL[..],
+            Some(self.render_mode),
+            Message::RenderModeSelected,
+        )
+        .width(180);
+        let led_model = if self.render_mode == RenderMode::Ideal {
+            column![]
+        } else {
+            column![
+                text(format!("Gamma: {:.1}", self.led_model.gamma)).size(14),
+                slider(1.0..=3.0, self.led_model.gamma, Message::GammaChanged)
+                    .step(0.1)
+                    .width(180),
+                text(format!("Brightness: {:.0}%", self.led_model.brightness * 100.0)).size(14),
+                slider(0.05..=1.0, self.led_model.brightness, Message::BrightnessChanged)
+                    .step(0.05)
+                    .width(180),
+                text(format!("Max per LED: {:.0} mA", self.led_model.max_current_ma)).size(14),
+                slider(5.0..=60.0, self.led_model.max_current_ma, Message::MaxCurrentChanged)
+                    .step(1.0)
+                    .width(180),
+            ]
+            .spacing(4)
+        };
+
+        column![
+            team,
+            highlight,
+            follow,
+            trail_kind,
+            text(trail_length_label).size(14),
+            trail_length,
+            render_mode,
+            led_model,
+            self.power_view(),
+            scrollable(column(drivers).spacing(4)).height(Length::Fill),
+        ]
+        .spacing(10)
+        .width(Length::Shrink)
+        .into()
+    }
+
+    /// The current draw of the board now and over the session, and the
+    /// limiter that keeps it within the supply budget.
+    fn power_view(&self) -> Element<'_, Message> {
+        let amps = |ma: f32| format!("{:.2} A", ma / 1000.0);
+
+        let now = self
+            .update_frame
+            .as_ref()
+            .map(|frame| amps(self.led_model.board_current_ma(frame, &self.layout)))
+            .unwrap_or_else(|| "-".to_string());
+
+        let report = match &self.power_report {
+            Some(report) => format!(
+                "Peak {} at {}\nAverage {}\nOver budget in {} of {} frames",
+                amps(report.peak_ma),
+                format_elapsed(report.peak_at),
+                amps(report.average_ma),
+                report.over_budget,
+                report.frames,
+            ),
+            None => String::new(),
+        };
+        let analyze = match self.analyzing_power {
+            true => button(text("Analyzing...").size(14)),
+            false => button(text("Analyze session").size(14))
+                .on_press_maybe(self.replay.as_ref().map(|_| Message::AnalyzePower)),
+        };
+
+        column![
+            text(format!("Current draw: {}", now)).size(14),
+            text(format!("Per channel: {:.0} mA", self.led_model.channel_ma)).size(14),
+            slider(5.0..=30.0, self.led_model.channel_ma, Message::ChannelCurrentChanged)
+                .step(1.0)
+                .width(180),
+            checkbox("Limit total current", self.power_budget.enabled)
+                .on_toggle(Message::TogglePowerLimit)
+                .size(14)
+                .text_size(14),
+            text(format!("Budget: {}", amps(self.power_budget.limit_ma))).size(14),
+            slider(500.0..=10000.0, self.power_budget.limit_ma, Message::PowerBudgetChanged)
+                .step(100.0)
+                .width(180),
+            analyze,
+            text(report).size(14),
+        ]
+        .spacing(4)
+        .into()
+    }
+
+    /// The running order, with pit stop counts and the latest stop duration.
+    /// Cars in the pit lane are flagged, since without pit lane LEDs they
+    /// are not on the board at all.
+    fn standings_view(&self) -> Element<'_, Message> {
+        let time = self.replay.as_ref().and_then(|replay| replay.time_at(self.duration));
+
+        let mut rows: Vec<(Option<u32>, DriverPosition)> = match (&self.replay, time) {
+            (Some(replay), Some(time)) => {
+                let mut positions = replay.positions_at(self.duration);
+                self.pit_stops.place(&mut positions, time, false);
+                positions
+                    .into_iter()
+                    .map(|p| (self.standings.position_at(p.driver_number, time), p))
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+        rows.sort_by_key(|(position, p)| (position.unwrap_or(u32::MAX), p.driver_number));
+
+        let lines = rows.into_iter().map(|(position, p)| {
+            let name = find_driver(p.driver_number).map_or("", |driver| driver.name);
+            let summary = time
+                .map(|time| self.pit_stops.summary_at(p.driver_number, time))
+                .unwrap_or_default();
+            let last_stop = summary
+                .last_duration
+                .map(|d| format!("{:.1}s", d.as_secs_f32()))
+                .unwrap_or_default();
+
+            button(
+                text(format!(
+                    "{:>3} {:<18} {:<3} {} {}",
+                    position.map(|p| format!("P{}", p)).unwrap_or_default(),
+                    name,
+                    if p.in_pit { "PIT" } else { "" },
+                    summary.stops,
+                    last_stop,
+                ))
+                .size(14)
+                .font(iced::Font::MONOSPACE),
+            )
+            .style(theme::Button::Text)
+            .padding(0)
+            .on_press(Message::DriverPinned(p.driver_number))
+            .into()
+        });
+
+        container(scrollable(
+            column![text("POS DRIVER             PIT STOPS").size(14).font(iced::Font::MONOSPACE)]
+                .extend(lines)
+                .spacing(4),
+        ))
+        .width(Length::Shrink)
+        .height(Length::Fill)
+        .into()
+    }
+}
+
+async fn fetch_driver_data(api: Api, drivers: Vec<u32>) -> Result<Replay, String> {
+    let mut replay = Replay::default();
+
+    for driver in DRIVERS.iter().filter(|driver| drivers.contains(&driver.number)) {
+        let url = api.url("location", &format!("&driver_number={}", driver.number));
+        log::debug!("GET {}", url);
+        let resp = api.client().get(&url).send().await.map_err(|e| e.to_string())?;
+        if resp.status().is_success() {
+            let data: Vec<LocationData> = resp.json().await.map_err(|e| e.to_string())?;
+            if data.iter().any(|d| d.x != 0.0 && d.y != 0.0) {
+                eprintln!(
+                    "Fetched {} samples for {} ({})",
+                    data.len(),
+                    driver.name,
+                    driver.team
+                );
+
+                replay.add_driver(data);
             } else {
                 eprintln!("No valid data found for driver {}", driver.number);
             }
@@ -341,5 +1874,112 @@ async fn fetch_driver_data(
         }
     }
 
-    Ok(update_frame)
+    Ok(replay)
+}
+
+/// Where the next poll of a live session starts: `LIVE_OVERLAP` before the
+/// newest sample in `replay`, for the samples that were released late.
+fn poll_after(replay: &Replay) -> Option<DateTime<Utc>> {
+    Some(replay.latest()? - chrono::Duration::from_std(LIVE_OVERLAP).ok()?)
+}
+
+/// The location samples of every driver after `after`, or all of them
+/// when nothing has been downloaded yet.
+async fn fetch_new_locations(
+    api: Api,
+    after: Option<DateTime<Utc>>,
+) -> Result<Vec<LocationData>, String> {
+    let query = after
+        .map(|after| format!("&date>{}", after.format("%Y-%m-%dT%H:%M:%S%.3f")))
+        .unwrap_or_default();
+    api.json("location", &query).await
+}
+
+async fn fetch_race_control(api: Api) -> Result<RaceControl, String> {
+    let messages: Vec<RaceControlMessage> = api.json("race_control", "").await?;
+    Ok(RaceControl::new(messages))
+}
+
+async fn fetch_pit_stops(api: Api) -> Result<PitStops, String> {
+    let data: Vec<PitData> = api.json("pit", "").await?;
+    Ok(PitStops::new(data))
+}
+
+async fn fetch_standings(api: Api) -> Result<Standings, String> {
+    let data: Vec<PositionData> = api.json("position", "").await?;
+    Ok(Standings::new(data))
+}
+
+async fn fetch_session_info(api: Api) -> Result<SessionInfo, String> {
+    let sessions: Vec<SessionInfo> = api.json("sessions", "").await?;
+    sessions.into_iter().next().ok_or("no such session".to_string())
+}
+
+async fn fetch_laps(api: Api, driver_number: u32) -> Result<Vec<LapData>, String> {
+    api.json("laps", &format!("&driver_number={}", driver_number)).await
+}
+
+async fn fetch_car_data(
+    api: Api,
+    driver_number: u32,
+    start: DateTime<Utc>,
+) -> Result<Vec<CarData>, String> {
+    let format = "%Y-%m-%dT%H:%M:%S";
+    let query = format!(
+        "&driver_number={}&date>={}&date<{}",
+        driver_number,
+        start.format(format),
+        (start + telemetry::WINDOW).format(format),
+    );
+    api.json("car_data", &query).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Polls a live session of the mock API twice, as the app does, and
+    /// checks that every sample lands in the replay once, in order, even
+    /// though the second poll overlaps the first.
+    #[tokio::test]
+    async fn live_polls_append_each_sample_once() {
+        let start = "2023-03-05T15:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let mut recorded = Replay::default();
+        for driver_number in [1, 44] {
+            let samples = (0..600)
+                .map(|i| LocationData {
+                    x: 1.0 + i as f32,
+                    y: driver_number as f32,
+                    date: (start + chrono::Duration::milliseconds(100 * i)).to_rfc3339(),
+                    driver_number,
+                })
+                .collect();
+            recorded.add_driver(samples);
+        }
+        let recorded: &'static Replay = Box::leak(Box::new(recorded));
+        let session = LiveSession::new(recorded, Duration::ZERO, Duration::ZERO).unwrap();
+        let session: &'static LiveSession = Box::leak(Box::new(session));
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let port = server.server_addr().to_ip().unwrap().port();
+        std::thread::spawn(move || mock_api::serve_on(session, &server));
+        let api = Api::new(&format!("http://127.0.0.1:{}", port), "latest");
+
+        let mut replay = Replay::default();
+        replay.append(fetch_new_locations(api.clone(), None).await.unwrap());
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        let polled = fetch_new_locations(api, poll_after(&replay)).await.unwrap();
+        // The overlap asks again for the first sample of each driver.
+        assert_eq!(polled.iter().filter(|data| data.x == 1.0).count(), 2);
+        replay.append(polled);
+
+        let mut drivers = 0;
+        for (driver_number, samples) in replay.tracks() {
+            let xs: Vec<f32> = samples.iter().map(|sample| sample.x).collect();
+            let expected: Vec<f32> = (0..xs.len()).map(|i| 1.0 + i as f32).collect();
+            assert_eq!(xs, expected, "driver {}", driver_number);
+            assert!(xs.len() >= 10, "driver {} has {} samples", driver_number, xs.len());
+            drivers += 1;
+        }
+        assert_eq!(drivers, 2);
+    }
 }
@@ -0,0 +1,175 @@
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use std::time::Duration;
+use tiny_http::{Header, Response, Server};
+
+use crate::replay::{LocationData, Replay};
+
+/// How the `date` filters of a request bound the samples it gets.
+#[derive(Debug, Default)]
+struct Query {
+    driver_number: Option<u32>,
+    after: Option<(DateTime<Utc>, bool)>,
+    before: Option<(DateTime<Utc>, bool)>,
+}
+
+impl Query {
+    /// Reads the filters OpenF1 takes in the query string, such as
+    /// `driver_number=1&date>2023-08-27T13:03:00`. The others are ignored.
+    fn parse(query: &str) -> Result<Self, String> {
+        let mut parsed = Query::default();
+        for part in query.split('&').map(percent_decode) {
+            if let Some(number) = part.strip_prefix("driver_number=") {
+                let number = number.parse().map_err(|_| format!("invalid driver number `{}`", number))?;
+                parsed.driver_number = Some(number);
+            } else if let Some(date) = part.strip_prefix("date>=") {
+                parsed.after = Some((parse_date(date)?, true));
+            } else if let Some(date) = part.strip_prefix("date>") {
+                parsed.after = Some((parse_date(date)?, false));
+            } else if let Some(date) = part.strip_prefix("date<=") {
+                parsed.before = Some((parse_date(date)?, true));
+            } else if let Some(date) = part.strip_prefix("date<") {
+                parsed.before = Some((parse_date(date)?, false));
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn matches(&self, date: DateTime<Utc>) -> bool {
+        let after = self.after.is_none_or(|(bound, inclusive)| {
+            date > bound || (inclusive && date == bound)
+        });
+        let before = self.before.is_none_or(|(bound, inclusive)| {
+            date < bound || (inclusive && date == bound)
+        });
+        after && before
+    }
+}
+
+/// Dates with or without an offset; the ones without are UTC, as OpenF1
+/// reads them.
+fn parse_date(date: &str) -> Result<DateTime<Utc>, String> {
+    date.parse::<DateTime<Utc>>()
+        .or_else(|_| NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S%.f").map(|d| d.and_utc()))
+        .map_err(|_| format!("invalid date `{}`", date))
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|hex| {
+            u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()
+        });
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// An offline session played as if it were running now: the session clock
+/// starts at `from` into the replay when the session is created, samples
+/// are dated on that clock, and a sample is only released once it is
+/// `delay` old.
+pub struct LiveSession<'a> {
+    replay: &'a Replay,
+    shift: chrono::Duration,
+    delay: chrono::Duration,
+}
+
+impl<'a> LiveSession<'a> {
+    pub fn new(replay: &'a Replay, from: Duration, delay: Duration) -> Result<Self, String> {
+        let session_start = replay.time_at(from).ok_or("the replay is empty")?;
+        Ok(Self {
+            replay,
+            shift: Utc::now() - session_start,
+            delay: chrono::Duration::from_std(delay).map_err(|e| e.to_string())?,
+        })
+    }
+
+    /// The date up to which samples have been released.
+    pub fn released_until(&self) -> DateTime<Utc> {
+        Utc::now() - self.delay
+    }
+
+    /// The released samples in `(after, until]`, in order.
+    pub fn between(&self, after: Option<DateTime<Utc>>, until: DateTime<Utc>) -> Vec<LocationData> {
+        self.released(&Query {
+            after: after.map(|after| (after, false)),
+            before: Some((until, true)),
+            ..Query::default()
+        })
+    }
+
+    fn released(&self, query: &Query) -> Vec<LocationData> {
+        let cutoff = self.released_until();
+        let mut released: Vec<_> = self
+            .replay
+            .tracks()
+            .filter(|(driver_number, _)| query.driver_number.is_none_or(|n| n == *driver_number))
+            .flat_map(|(driver_number, samples)| {
+                samples
+                    .iter()
+                    .map(move |s| (s.date + self.shift, driver_number, s))
+                    .take_while(move |(date, _, _)| *date <= cutoff)
+            })
+            .filter(|(date, _, _)| query.matches(*date))
+            .collect();
+        released.sort_by_key(|(date, driver_number, _)| (*date, *driver_number));
+
+        released
+            .into_iter()
+            .map(|(date, driver_number, s)| LocationData {
+                x: s.x,
+                y: s.y,
+                date: date.to_rfc3339_opts(SecondsFormat::Micros, false),
+                driver_number,
+            })
+            .collect()
+    }
+}
+
+/// Serves `/v1/location` of `session` on `port`, with the filters of
+/// OpenF1. Runs until the process is stopped.
+pub fn serve(session: &LiveSession, port: u16) -> Result<(), String> {
+    let server = Server::http(("127.0.0.1", port)).map_err(|e| e.to_string())?;
+    eprintln!("Serving the session on http://127.0.0.1:{}", port);
+    serve_on(session, &server)
+}
+
+/// Serves `/v1/location` of `session` on a server that is already bound.
+pub fn serve_on(session: &LiveSession, server: &Server) -> Result<(), String> {
+    let json = Header::from_bytes("Content-Type", "application/json").expect("valid header");
+
+    for request in server.incoming_requests() {
+        let url = request.url().to_string();
+        let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+
+        let response = match (path, Query::parse(query)) {
+            ("/v1/location", Ok(query)) => {
+                let data = session.released(&query);
+                eprintln!("{} -> {} samples", url, data.len());
+                let body = serde_json::to_string(&data).map_err(|e| e.to_string())?;
+                Response::from_string(body).with_header(json.clone())
+            }
+            (_, Err(e)) => Response::from_string(e).with_status_code(400),
+            _ => Response::from_string("[]").with_status_code(404),
+        };
+        if let Err(e) = request.respond(response) {
+            eprintln!("Failed to respond to {}: {}", url, e);
+        }
+    }
+    Ok(())
+}
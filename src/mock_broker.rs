@@ -0,0 +1,187 @@
+use bytes::BytesMut;
+use rumqttc::mqttbytes::{self, v4};
+use rumqttc::{ConnAck, ConnectReturnCode, Packet, PubAck, Publish, QoS, SubAck, SubscribeReasonCode};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio::task::JoinSet;
+
+use crate::mock_api::LiveSession;
+
+const MAX_PACKET_SIZE: usize = 1024 * 1024;
+/// Interval at which newly released samples are published.
+const PUBLISH_INTERVAL: Duration = Duration::from_millis(200);
+/// Messages a client may fall behind by before it misses some.
+const BACKLOG: usize = 4096;
+
+#[derive(Debug, Clone)]
+enum Event {
+    Publish(Publish),
+    /// Drops every client, to try reconnecting.
+    Disconnect,
+}
+
+/// A minimal MQTT 3.1.1 broker on `port` that publishes the samples of
+/// `session` to `topic` as they are released, one JSON sample per message,
+/// and forwards what clients publish. QoS 0 only, no retained messages.
+///
+/// `duplicates` publishes every sample twice, and `disconnect_every` drops
+/// all clients at that interval, to try how a subscriber copes with both.
+/// Clients are dropped with the broker too, as when it restarts.
+pub async fn run(
+    session: &LiveSession<'_>,
+    port: u16,
+    topic: &str,
+    duplicates: bool,
+    disconnect_every: Option<Duration>,
+) -> Result<(), String> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).await.map_err(|e| e.to_string())?;
+    eprintln!("MQTT broker on 127.0.0.1:{}, publishing to {}", port, topic);
+    run_on(session, listener, topic, duplicates, disconnect_every).await
+}
+
+/// Runs the broker of [`run`] on a listener that is already bound.
+pub async fn run_on(
+    session: &LiveSession<'_>,
+    listener: TcpListener,
+    topic: &str,
+    duplicates: bool,
+    disconnect_every: Option<Duration>,
+) -> Result<(), String> {
+
+    let (events, _) = broadcast::channel(BACKLOG);
+    let mut publish = tokio::time::interval(PUBLISH_INTERVAL);
+    let mut disconnect = disconnect_every
+        .map(|every| tokio::time::interval_at(tokio::time::Instant::now() + every, every));
+    // Like a live feed, only what is released from now on is published.
+    let mut published_until = session.released_until();
+    let mut clients = JoinSet::new();
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, address) = accepted.map_err(|e| e.to_string())?;
+                eprintln!("Client connected from {}", address);
+                let events = events.clone();
+                clients.spawn(async move {
+                    match serve_client(stream, events).await {
+                        Ok(()) => eprintln!("Client {} disconnected", address),
+                        Err(e) => eprintln!("Client {} dropped: {}", address, e),
+                    }
+                });
+            }
+            Some(_) = clients.join_next() => {}
+            _ = publish.tick() => {
+                let until = session.released_until();
+                for data in session.between(Some(published_until), until) {
+                    let payload = serde_json::to_vec(&data).map_err(|e| e.to_string())?;
+                    let message = Publish::new(topic, QoS::AtMostOnce, payload);
+                    let copies = if duplicates { 2 } else { 1 };
+                    for _ in 0..copies {
+                        // Nobody may be subscribed yet.
+                        let _ = events.send(Event::Publish(message.clone()));
+                    }
+                }
+                published_until = until;
+            }
+            _ = tick(&mut disconnect) => {
+                eprintln!("Disconnecting all clients");
+                let _ = events.send(Event::Disconnect);
+            }
+        }
+    }
+}
+
+async fn serve_client(mut stream: TcpStream, events: broadcast::Sender<Event>) -> Result<(), String> {
+    let mut receiver = events.subscribe();
+    let mut filters: Vec<String> = Vec::new();
+    let mut incoming = BytesMut::with_capacity(4096);
+    let mut outgoing = BytesMut::new();
+
+    loop {
+        tokio::select! {
+            read = stream.read_buf(&mut incoming) => {
+                if read.map_err(|e| e.to_string())? == 0 {
+                    return Ok(());
+                }
+                loop {
+                    let packet = match v4::read(&mut incoming, MAX_PACKET_SIZE) {
+                        Ok(packet) => packet,
+                        Err(mqttbytes::Error::InsufficientBytes(_)) => break,
+                        Err(e) => return Err(e.to_string()),
+                    };
+                    match packet {
+                        Packet::Connect(_) => {
+                            write(ConnAck::new(ConnectReturnCode::Success, false).write(&mut outgoing))?;
+                        }
+                        Packet::Subscribe(subscribe) => {
+                            let codes = subscribe
+                                .filters
+                                .iter()
+                                .map(|_| SubscribeReasonCode::Success(QoS::AtMostOnce))
+                                .collect();
+                            filters.extend(subscribe.filters.into_iter().map(|filter| filter.path));
+                            write(SubAck::new(subscribe.pkid, codes).write(&mut outgoing))?;
+                        }
+                        Packet::Publish(publish) => {
+                            if publish.qos != QoS::AtMostOnce {
+                                write(PubAck::new(publish.pkid).write(&mut outgoing))?;
+                            }
+                            let forward = Publish::new(publish.topic, QoS::AtMostOnce, publish.payload.to_vec());
+                            let _ = events.send(Event::Publish(forward));
+                        }
+                        Packet::PingReq => write(v4::PingResp.write(&mut outgoing))?,
+                        Packet::Disconnect => return Ok(()),
+                        _ => {}
+                    }
+                }
+            }
+            event = receiver.recv() => match event {
+                Ok(Event::Publish(publish)) => {
+                    if filters.iter().any(|filter| topic_matches(filter, &publish.topic)) {
+                        write(publish.write(&mut outgoing))?;
+                    }
+                }
+                Ok(Event::Disconnect) | Err(broadcast::error::RecvError::Closed) => return Ok(()),
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    eprintln!("A client fell behind and missed {} messages", missed);
+                }
+            },
+        }
+
+        if !outgoing.is_empty() {
+            stream.write_all(&outgoing).await.map_err(|e| e.to_string())?;
+            outgoing.clear();
+        }
+    }
+}
+
+/// Ticks `interval`, or never without one.
+async fn tick(interval: &mut Option<tokio::time::Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+fn write(written: Result<usize, mqttbytes::Error>) -> Result<(), String> {
+    written.map(|_| ()).map_err(|e| e.to_string())
+}
+
+/// Whether `topic` matches a subscription filter, with the `+` and `#`
+/// wildcards.
+fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut levels = topic.split('/');
+    for level in filter.split('/') {
+        match (level, levels.next()) {
+            ("#", _) => return true,
+            ("+", Some(_)) => {}
+            (level, Some(topic_level)) if level == topic_level => {}
+            _ => return false,
+        }
+    }
+    levels.next().is_none()
+}
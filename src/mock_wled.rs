@@ -0,0 +1,264 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::{Json, Router};
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+use crate::wled;
+
+/// Interval at which what was received is summed up.
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What a stand-in controller received since the last report.
+#[derive(Debug, Default)]
+struct Received {
+    frames: u32,
+    packets: u32,
+    errors: u32,
+    /// First pixel and pixel count of the latest frame, and how many of
+    /// its pixels were lit.
+    last_frame: Option<(u32, u32, u32)>,
+}
+
+impl Received {
+    fn report(&mut self, protocol: &str) {
+        if self.packets == 0 && self.errors == 0 {
+            return;
+        }
+        let last = match self.last_frame {
+            Some((first, count, lit)) => {
+                format!(", last pixels {}..{} with {} lit", first, first + count, lit)
+            }
+            None => String::new(),
+        };
+        eprintln!(
+            "{}: {} frames in {} packets, {} invalid{}",
+            protocol, self.frames, self.packets, self.errors, last
+        );
+        *self = Received::default();
+    }
+}
+
+/// Stands in for a WLED controller with a strip of `leds` pixels: takes
+/// DDP on `ddp_port` and the JSON API on `http_port`, checks every packet
+/// and request the way WLED reads them, and reports what it got.
+pub async fn run(ddp_port: u16, http_port: u16, leds: u32) -> Result<(), String> {
+    let socket = UdpSocket::bind(("127.0.0.1", ddp_port)).await.map_err(|e| e.to_string())?;
+    let json = Arc::new(Mutex::new(Received::default()));
+    let app = Router::new()
+        .route("/json/state", post(state))
+        .route("/json", post(state))
+        .with_state((json.clone(), leds));
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", http_port))
+        .await
+        .map_err(|e| e.to_string())?;
+    eprintln!(
+        "WLED stand-in with {} pixels: DDP on udp://127.0.0.1:{}, JSON on http://127.0.0.1:{}",
+        leds, ddp_port, http_port
+    );
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            eprintln!("The JSON API stopped: {}", e);
+        }
+    });
+
+    let mut ddp = Received::default();
+    let mut frame = DdpFrame::default();
+    let mut report = tokio::time::interval(REPORT_INTERVAL);
+    let mut buffer = [0; 2048];
+    loop {
+        tokio::select! {
+            received = socket.recv_from(&mut buffer) => {
+                let (length, from) = received.map_err(|e| e.to_string())?;
+                ddp.packets += 1;
+                match frame.take(&buffer[..length], leds) {
+                    Ok(Some(pushed)) => {
+                        ddp.frames += 1;
+                        ddp.last_frame = Some(pushed);
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        ddp.errors += 1;
+                        eprintln!("Invalid DDP packet from {}: {}", from, e);
+                    }
+                }
+            }
+            _ = report.tick() => {
+                ddp.report("DDP");
+                json.lock().expect("stats are not poisoned").report("JSON");
+            }
+        }
+    }
+}
+
+/// The packets of the DDP frame being received.
+#[derive(Debug, Default)]
+struct DdpFrame {
+    sequence: Option<u8>,
+    /// First pixel of the frame, and the byte the next packet starts at.
+    first: Option<u32>,
+    next_byte: u32,
+    lit: u32,
+}
+
+impl DdpFrame {
+    /// Takes in one packet. Returns the first pixel, pixel count and lit
+    /// pixels of the frame when the packet pushes it.
+    fn take(&mut self, packet: &[u8], leds: u32) -> Result<Option<(u32, u32, u32)>, String> {
+        let result = self.check(packet, leds);
+        if result.is_err() {
+            // The rest of the frame cannot be checked against this packet.
+            *self = DdpFrame::default();
+        }
+        result
+    }
+
+    fn check(&mut self, packet: &[u8], leds: u32) -> Result<Option<(u32, u32, u32)>, String> {
+        let packet = wled::parse_ddp(packet)?;
+        let (offset, length, sequence) = (packet.offset, packet.data.len(), packet.sequence);
+        if offset as usize + length > leds as usize * 3 {
+            return Err(format!(
+                "pixels {}..{} run past the {} pixels of the strip",
+                offset / 3,
+                (offset as usize + length) / 3,
+                leds
+            ));
+        }
+
+        match self.first {
+            Some(_) if self.sequence != Some(sequence) => {
+                return Err(format!(
+                    "sequence {} in a frame started with {:?}",
+                    sequence, self.sequence
+                ));
+            }
+            Some(_) if offset != self.next_byte => {
+                return Err(format!("starts at byte {} where byte {} was next", offset, self.next_byte));
+            }
+            Some(_) => {}
+            None => {
+                self.sequence = Some(sequence);
+                self.first = Some(offset / 3);
+            }
+        }
+        self.next_byte = offset + length as u32;
+        self.lit += packet.data.chunks(3).filter(|pixel| pixel.iter().any(|&c| c > 0)).count() as u32;
+
+        if !packet.push {
+            return Ok(None);
+        }
+        let first = self.first.unwrap_or(offset / 3);
+        let pushed = (first, self.next_byte / 3 - first, self.lit);
+        *self = DdpFrame::default();
+        Ok(Some(pushed))
+    }
+}
+
+async fn state(
+    State((received, leds)): State<(Arc<Mutex<Received>>, u32)>,
+    Json(request): Json<Value>,
+) -> (StatusCode, Json<Value>) {
+    let checked = check_state(&request, leds);
+    let mut received = received.lock().expect("stats are not poisoned");
+    received.packets += 1;
+    match checked {
+        Ok(set) => {
+            received.frames += 1;
+            received.last_frame = Some(set);
+            (StatusCode::OK, Json(serde_json::json!({ "success": true })))
+        }
+        Err(e) => {
+            received.errors += 1;
+            eprintln!("Invalid JSON request: {}", e);
+            (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": e })))
+        }
+    }
+}
+
+/// Checks the LEDs a request sets, as `seg: {id, i: [start, color...]}`
+/// where each color is `RRGGBB` or `[r, g, b]`. Returns the first LED, the
+/// LEDs set and how many of them are lit.
+fn check_state(request: &Value, leds: u32) -> Result<(u32, u32, u32), String> {
+    let segment = request.get("seg").ok_or("no `seg`")?;
+    if !segment.get("id").is_some_and(Value::is_u64) {
+        return Err("the segment has no numeric `id`".to_string());
+    }
+    let set = segment.get("i").and_then(Value::as_array).ok_or("the segment has no `i` array")?;
+    let (start, colors) = set.split_first().ok_or("`i` is empty")?;
+    let start = start.as_u64().ok_or("`i` does not start with an LED index")? as u32;
+
+    let mut lit = 0;
+    for color in colors {
+        let channels = match color {
+            Value::String(hex) if hex.len() == 6 => u32::from_str_radix(hex, 16)
+                .map(|rgb| rgb > 0)
+                .map_err(|_| format!("invalid color `{}`", hex))?,
+            Value::Array(rgb) if rgb.len() == 3 && rgb.iter().all(|c| c.as_u64().is_some_and(|c| c < 256)) => {
+                rgb.iter().any(|c| c.as_u64() != Some(0))
+            }
+            _ => return Err(format!("invalid color {}", color)),
+        };
+        if channels {
+            lit += 1;
+        }
+    }
+    let count = colors.len() as u32;
+    if start + count > leds {
+        return Err(format!("LEDs {}..{} run past the {} pixels of the strip", start, start + count, leds));
+    }
+    Ok((start, count, lit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wled::{ColorOrder, WledConfig};
+
+    fn pixels(count: usize) -> Vec<(u8, u8, u8)> {
+        (0..count).map(|i| if i % 3 == 0 { (200, 0, 0) } else { (0, 0, 0) }).collect()
+    }
+
+    #[test]
+    fn ddp_frames_are_pushed_by_their_last_packet() {
+        let pixels = pixels(600);
+        let data: Vec<u8> = pixels.iter().flat_map(|&(r, g, b)| [r, g, b]).collect();
+        let mut frame = DdpFrame::default();
+        let packets = wled::ddp_packets(&data, 20, 3);
+        assert_eq!(frame.take(&packets[0], 700), Ok(None));
+        assert_eq!(frame.take(&packets[1], 700), Ok(Some((20, 600, 200))));
+
+        // The strip is too short for the frame.
+        let mut frame = DdpFrame::default();
+        assert!(frame.take(&packets[1], 500).is_err());
+    }
+
+    #[test]
+    fn ddp_packets_out_of_their_frame_are_rejected() {
+        let data = vec![255; 600 * 3];
+        let first = wled::ddp_packets(&data, 0, 1).remove(0);
+        let other = wled::ddp_packets(&data, 0, 2).remove(1);
+        let mut frame = DdpFrame::default();
+        assert_eq!(frame.take(&first, 700), Ok(None));
+        assert!(frame.take(&other, 700).is_err());
+    }
+
+    #[test]
+    fn json_requests_set_what_the_frame_does() {
+        let config = WledConfig {
+            segment: 1,
+            offset: 40,
+            order: ColorOrder::Bgr,
+        };
+        let requests = wled::json_requests(&pixels(600), &config);
+        assert_eq!(requests.len(), 3);
+        let set: Vec<_> = requests.iter().map(|request| check_state(request, 700).unwrap()).collect();
+        assert_eq!(set, [(40, 256, 86), (296, 256, 85), (552, 88, 29)]);
+        assert_eq!(requests[0]["seg"]["i"][1], "0000C8");
+
+        assert!(check_state(&requests[2], 600).is_err());
+        assert!(check_state(&serde_json::json!({ "seg": { "id": 0, "i": [0, "red"] } }), 700).is_err());
+    }
+}
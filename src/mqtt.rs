@@ -0,0 +1,209 @@
+use iced::futures::channel::mpsc;
+use iced::futures::never::Never;
+use iced::futures::SinkExt;
+use iced::subscription::{self, Subscription};
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::replay::LocationData;
+
+/// How long samples are gathered before they are handed to the replay, so
+/// the board is not rebuilt for every message.
+const BATCH_INTERVAL: Duration = Duration::from_millis(250);
+/// Wait before reconnecting after the connection to the broker is lost.
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+const KEEP_ALIVE: Duration = Duration::from_secs(5);
+
+/// A broker given as `host` or `host:port`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Broker {
+    pub host: String,
+    pub port: u16,
+}
+
+impl FromStr for Broker {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.rsplit_once(':') {
+            Some((host, port)) => Ok(Broker {
+                host: host.to_string(),
+                port: port.parse().map_err(|_| format!("invalid port `{}`", port))?,
+            }),
+            None => Ok(Broker {
+                host: value.to_string(),
+                port: 1883,
+            }),
+        }
+    }
+}
+
+/// Location samples published to `topic` on `broker`, as OpenF1 does on
+/// `v1/location`: one sample or an array of them per message.
+///
+/// The connection is kept up for as long as the subscription is, and the
+/// topic is subscribed to again after every reconnect. Samples a broker
+/// delivers twice are dropped when they are added to the replay.
+pub fn locations(broker: Broker, topic: String) -> Subscription<Vec<LocationData>> {
+    let id = (broker.clone(), topic.clone());
+    subscription::channel(id, 16, move |output| receive(broker, topic, output))
+}
+
+/// Sends the samples published to `topic` on `broker` to `output`, in
+/// batches, for as long as it is polled.
+async fn receive(broker: Broker, topic: String, mut output: mpsc::Sender<Vec<LocationData>>) -> Never {
+    let client_id = format!("f1-led-circuit-{}", std::process::id());
+    let mut options = MqttOptions::new(client_id, broker.host, broker.port);
+    options.set_keep_alive(KEEP_ALIVE);
+    options.set_clean_session(true);
+    let (client, mut eventloop) = AsyncClient::new(options, 16);
+
+    let mut pending: Vec<LocationData> = Vec::new();
+    let mut flush = tokio::time::interval(BATCH_INTERVAL);
+    loop {
+        tokio::select! {
+            event = eventloop.poll() => match event {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    eprintln!("Connected to the MQTT broker, subscribing to {}", topic);
+                    if let Err(e) = client.try_subscribe(&topic, QoS::AtLeastOnce) {
+                        eprintln!("Failed to subscribe to {}: {}", topic, e);
+                    }
+                }
+                Ok(Event::Incoming(Packet::Publish(publish))) => {
+                    match parse(&publish.payload) {
+                        Ok(data) => pending.extend(data),
+                        Err(e) => eprintln!("Ignoring a message on {}: {}", publish.topic, e),
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    eprintln!("MQTT connection lost: {}", e);
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                }
+            },
+            _ = flush.tick() => {
+                if !pending.is_empty() {
+                    let _ = output.send(std::mem::take(&mut pending)).await;
+                }
+            }
+        }
+    }
+}
+
+fn parse(payload: &[u8]) -> Result<Vec<LocationData>, String> {
+    serde_json::from_slice::<Vec<LocationData>>(payload)
+        .or_else(|_| serde_json::from_slice::<LocationData>(payload).map(|data| vec![data]))
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_api::LiveSession;
+    use crate::mock_broker;
+    use crate::replay::Replay;
+    use iced::futures::StreamExt;
+
+    const TOPIC: &str = "v1/location";
+
+    fn sample(second: u32) -> LocationData {
+        LocationData {
+            x: 100.0 + second as f32,
+            y: 200.0,
+            date: format!("2023-03-05T15:00:{:02}Z", second),
+            driver_number: 1,
+        }
+    }
+
+    /// Runs the broker on `port`, any free one for 0, with nothing of its
+    /// own to publish, so that only what the test publishes arrives.
+    async fn start_broker(port: u16) -> (tokio::task::JoinHandle<Result<(), String>>, u16) {
+        let mut replay = Replay::default();
+        replay.add_driver(vec![sample(0)]);
+        let replay: &'static Replay = Box::leak(Box::new(replay));
+        let session = LiveSession::new(replay, Duration::ZERO, Duration::from_secs(3600)).unwrap();
+        let session: &'static LiveSession = Box::leak(Box::new(session));
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", port)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        (tokio::spawn(mock_broker::run_on(session, listener, TOPIC, false, None)), port)
+    }
+
+    fn start_publisher(port: u16) -> AsyncClient {
+        let (client, mut eventloop) = AsyncClient::new(MqttOptions::new("test-publisher", "127.0.0.1", port), 16);
+        tokio::spawn(async move {
+            loop {
+                if eventloop.poll().await.is_err() {
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                }
+            }
+        });
+        client
+    }
+
+    async fn publish(client: &AsyncClient, samples: &[LocationData]) {
+        let payload = serde_json::to_vec(samples).unwrap();
+        client.publish(TOPIC, QoS::AtMostOnce, false, payload).await.unwrap();
+    }
+
+    /// Publishes `samples` until a batch arrives, as the subscriber may not
+    /// have subscribed yet, and adds what arrives to `replay`.
+    async fn deliver(
+        client: &AsyncClient,
+        batches: &mut mpsc::Receiver<Vec<LocationData>>,
+        replay: &mut Replay,
+        samples: &[LocationData],
+    ) {
+        loop {
+            publish(client, samples).await;
+            if let Ok(Some(batch)) = tokio::time::timeout(Duration::from_millis(500), batches.next()).await {
+                replay.append(batch);
+                return;
+            }
+        }
+    }
+
+    fn seconds(replay: &Replay) -> Vec<u32> {
+        let (_, samples) = replay.tracks().next().unwrap();
+        samples.iter().map(|sample| sample.x as u32 - 100).collect()
+    }
+
+    #[tokio::test]
+    async fn samples_are_deduplicated_across_a_broker_restart() {
+        let (broker, port) = start_broker(0).await;
+        let publisher = start_publisher(port);
+        let (output, mut batches) = mpsc::channel(16);
+        let broker_address = Broker {
+            host: "127.0.0.1".to_string(),
+            port,
+        };
+        tokio::spawn(receive(broker_address, TOPIC.to_string(), output));
+        let mut replay = Replay::default();
+
+        tokio::time::timeout(Duration::from_secs(30), async {
+            deliver(&publisher, &mut batches, &mut replay, &[sample(0)]).await;
+
+            // Out of order and twice over, in one message and in two.
+            let burst = [sample(3), sample(1), sample(2), sample(1)];
+            publish(&publisher, &burst).await;
+            publish(&publisher, &burst).await;
+            while seconds(&replay).len() < 4 {
+                replay.append(batches.next().await.unwrap());
+            }
+            tokio::time::sleep(BATCH_INTERVAL * 2).await;
+            while let Ok(Some(batch)) = batches.try_next() {
+                replay.append(batch);
+            }
+            assert_eq!(seconds(&replay), [0, 1, 2, 3]);
+
+            // The subscriber reconnects and subscribes again by itself.
+            broker.abort();
+            let _ = broker.await;
+            let _broker = start_broker(port).await;
+            deliver(&publisher, &mut batches, &mut replay, &[sample(4)]).await;
+            assert_eq!(seconds(&replay), [0, 1, 2, 3, 4]);
+        })
+        .await
+        .expect("the samples arrive");
+    }
+}
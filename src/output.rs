@@ -0,0 +1,68 @@
+use crate::chain::ChainMap;
+use crate::cli::OutputArgs;
+use crate::led_data::UpdateFrame;
+use crate::link::SerialLink;
+use crate::wled;
+
+/// Hardware that shows the board, sent every frame once it is built.
+pub trait Output {
+    /// What the output sends to, for messages.
+    fn describe(&self) -> String;
+
+    /// Shows `pixels`, the color of every pixel of the chain in wire order.
+    fn send(&mut self, pixels: &[(u8, u8, u8)]) -> Result<(), String>;
+}
+
+/// Every output the board is sent to, and how the board is wired for
+/// them.
+#[derive(Default)]
+pub struct Outputs {
+    chain: ChainMap,
+    outputs: Vec<Box<dyn Output>>,
+    /// Whether each output failed at the last frame, so a lost device is
+    /// reported once rather than at every frame.
+    failing: Vec<bool>,
+}
+
+impl Outputs {
+    pub fn new(args: &OutputArgs) -> Result<Self, String> {
+        let mut outputs = Outputs {
+            chain: args.chain_map()?,
+            ..Outputs::default()
+        };
+        for target in &args.wled {
+            outputs.add(wled::output(target, &args.wled_config())?);
+        }
+        if let Some(target) = &args.serial {
+            outputs.add(Box::new(SerialLink::new(target, outputs.chain.pixel_count(), args.baud, args.keyframe_every)?));
+        }
+        Ok(outputs)
+    }
+
+    fn add(&mut self, output: Box<dyn Output>) {
+        eprintln!("Sending {} pixels to {}", self.chain.pixel_count(), output.describe());
+        self.outputs.push(output);
+        self.failing.push(false);
+    }
+
+    pub fn send(&mut self, frame: &UpdateFrame) {
+        if self.outputs.is_empty() {
+            return;
+        }
+        let pixels = self.chain.pixels(frame);
+        for (output, failing) in self.outputs.iter_mut().zip(&mut self.failing) {
+            match output.send(&pixels) {
+                Ok(()) if *failing => {
+                    eprintln!("Sending to {} again", output.describe());
+                    *failing = false;
+                }
+                Ok(()) => {}
+                Err(e) if !*failing => {
+                    eprintln!("Failed to send to {}: {}", output.describe(), e);
+                    *failing = true;
+                }
+                Err(_) => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::circuit::START_FINISH_AFTER_LED;
+use crate::led_data::{nearest_led, LED_DATA};
+use crate::replay::Replay;
+
+/// LEDs either side of the start/finish line within which a move from one
+/// side to the other counts as crossing it, so that samples a few LEDs
+/// apart still do.
+const CROSSING_WINDOW: u32 = 8;
+
+/// A session from OpenF1 `/v1/sessions`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SessionInfo {
+    pub session_name: String,
+    pub country_name: String,
+    pub year: i32,
+}
+
+impl SessionInfo {
+    /// The session as a broadcast names it, e.g. "2023 Bahrain Race".
+    pub fn title(&self) -> String {
+        format!("{} {} {}", self.year, self.country_name, self.session_name)
+    }
+}
+
+/// The lap the race is on over time, counted from the leader crossing the
+/// start/finish line on the board, so that every source has one.
+#[derive(Debug, Clone, Default)]
+pub struct LapCount {
+    drivers: Vec<DriverLaps>,
+}
+
+/// The laps of one driver, as far as their samples have been counted.
+#[derive(Debug, Clone)]
+struct DriverLaps {
+    driver_number: u32,
+    /// Samples counted so far, which a live session adds to.
+    counted: usize,
+    previous: Option<u32>,
+    /// Whether the next crossing is the start from a grid behind the line,
+    /// which completes no lap.
+    starting: bool,
+    /// When the driver completed a lap, in order.
+    completed: Vec<DateTime<Utc>>,
+}
+
+impl LapCount {
+    pub fn new(replay: &Replay) -> Self {
+        let mut laps = LapCount::default();
+        laps.update(replay);
+        laps
+    }
+
+    /// Counts the samples added to `replay` since the last count, rather
+    /// than the whole session again.
+    pub fn update(&mut self, replay: &Replay) {
+        let leds = LED_DATA.len() as u32;
+        // Distance along the track from the line, in LEDs, both ways.
+        let before = |led: u32| (START_FINISH_AFTER_LED + leds - led) % leds;
+        let after = |led: u32| (led + leds - START_FINISH_AFTER_LED - 1) % leds;
+
+        for (driver_number, samples) in replay.tracks() {
+            let index = match self.drivers.iter().position(|laps| laps.driver_number == driver_number) {
+                Some(index) => index,
+                None => {
+                    self.drivers.push(DriverLaps {
+                        driver_number,
+                        counted: 0,
+                        previous: None,
+                        starting: false,
+                        completed: Vec::new(),
+                    });
+                    self.drivers.len() - 1
+                }
+            };
+            let laps = &mut self.drivers[index];
+            for sample in samples.get(laps.counted..).unwrap_or_default() {
+                let led = nearest_led(sample.x, sample.y).led_number;
+                match laps.previous.replace(led) {
+                    None => laps.starting = before(led) < leds / 4,
+                    Some(previous) if before(previous) < CROSSING_WINDOW && after(led) < CROSSING_WINDOW => {
+                        if !std::mem::take(&mut laps.starting) {
+                            laps.completed.push(sample.date);
+                        }
+                    }
+                    Some(_) => {}
+                }
+            }
+            laps.counted = samples.len();
+        }
+    }
+
+    /// The lap the leader is on at `time`, capped at `total` laps when the
+    /// race length is known, as the leader finishing completes one more.
+    pub fn lap_at(&self, time: DateTime<Utc>, total: Option<u32>) -> u32 {
+        let completed = self
+            .drivers
+            .iter()
+            .map(|laps| laps.completed.partition_point(|date| *date <= time) as u32)
+            .max()
+            .unwrap_or(0);
+        let lap = completed + 1;
+        total.map_or(lap, |total| lap.min(total))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::{simulate, SimulationConfig};
+    use std::time::Duration;
+
+    #[test]
+    fn laps_counted_as_samples_come_in_match_the_whole_race() {
+        let simulation = simulate(&SimulationConfig {
+            seed: 3,
+            laps: 3,
+            ..SimulationConfig::default()
+        });
+        let mut samples: Vec<_> = simulation.locations.into_iter().flatten().collect();
+        samples.sort_by(|a, b| a.date.cmp(&b.date));
+
+        let mut whole = Replay::default();
+        whole.append(samples.clone());
+        let expected = LapCount::new(&whole);
+
+        let mut live = Replay::default();
+        let mut laps = LapCount::default();
+        for batch in samples.chunks(500) {
+            live.append(batch.to_vec());
+            laps.update(&live);
+        }
+
+        let end = whole.duration();
+        for step in 0..=20 {
+            let time = whole.time_at(end * step / 20).unwrap();
+            assert_eq!(laps.lap_at(time, None), expected.lap_at(time, None), "at step {}", step);
+        }
+        // The leader finishing the third lap completes one more.
+        let finish = whole.time_at(end).unwrap();
+        assert_eq!(expected.lap_at(finish, None), 4);
+        assert_eq!(expected.lap_at(finish, Some(3)), 3);
+        assert_eq!(expected.lap_at(whole.time_at(Duration::ZERO).unwrap(), None), 1);
+    }
+}
@@ -0,0 +1,100 @@
+use std::fmt;
+
+use crate::layout::Layout;
+use crate::led_data::UpdateFrame;
+
+/// Gamma of the screen the preview is shown on.
+const DISPLAY_GAMMA: f32 = 2.2;
+
+/// Electrical and optical model of one WS2812-style LED, used to preview
+/// what a frame will look like on the physical board.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LedModel {
+    /// Gamma correction applied to each channel before PWM. 1.0 means the
+    /// raw 8-bit value drives the LED, as with no correction in firmware.
+    pub gamma: f32,
+    /// Global brightness scale, from 0.0 to 1.0.
+    pub brightness: f32,
+    /// Current of one channel at full duty, in mA.
+    pub channel_ma: f32,
+    /// Most current a single LED may draw; brighter colors are scaled down
+    /// as a whole to stay under it, in mA.
+    pub max_current_ma: f32,
+    /// Current an LED draws while dark, for its driver chip, in mA.
+    pub idle_ma: f32,
+}
+
+impl Default for LedModel {
+    fn default() -> Self {
+        Self {
+            gamma: 1.0,
+            brightness: 1.0,
+            channel_ma: 20.0,
+            max_current_ma: 60.0,
+            idle_ma: 1.0,
+        }
+    }
+}
+
+impl LedModel {
+    /// PWM duty of each channel, from 0.0 to 1.0, after gamma, brightness
+    /// and the current limit.
+    pub fn duty(&self, (r, g, b): (u8, u8, u8)) -> [f32; 3] {
+        let channel = |c: u8| (c as f32 / 255.0).powf(self.gamma) * self.brightness;
+        let duty = [channel(r), channel(g), channel(b)];
+
+        let current = duty.iter().sum::<f32>() * self.channel_ma;
+        if current > self.max_current_ma && current > 0.0 {
+            let scale = self.max_current_ma / current;
+            duty.map(|d| d * scale)
+        } else {
+            duty
+        }
+    }
+
+    /// Current drawn by one LED showing `color`, in mA, on top of its idle
+    /// current.
+    pub fn current_ma(&self, color: (u8, u8, u8)) -> f32 {
+        self.duty(color).iter().sum::<f32>() * self.channel_ma
+    }
+
+    /// Current drawn by the whole board showing `frame`, in mA, counting
+    /// the idle current of the dark LEDs too.
+    pub fn board_current_ma(&self, frame: &UpdateFrame, layout: &Layout) -> f32 {
+        let colors = frame.led_colors(layout.max_led_number());
+        let lit: f32 = layout
+            .leds()
+            .iter()
+            .filter_map(|led| colors[led.led_number as usize])
+            .map(|color| self.current_ma(color))
+            .sum();
+        lit + layout.leds().len() as f32 * self.idle_ma
+    }
+
+    /// The color a screen has to show for the light the LED emits. LED
+    /// output is linear in duty, so it is gamma-encoded for the display.
+    pub fn appearance(&self, color: (u8, u8, u8)) -> [f32; 3] {
+        self.duty(color).map(|d| d.powf(1.0 / DISPLAY_GAMMA))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+    Ideal,
+    Physical,
+    SideBySide,
+}
+
+impl RenderMode {
+    pub const ALL: [RenderMode; 3] = [RenderMode::Ideal, RenderMode::Physical, RenderMode::SideBySide];
+}
+
+impl fmt::Display for RenderMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderMode::Ideal => write!(f, "Ideal LEDs"),
+            RenderMode::Physical => write!(f, "Physical LEDs"),
+            RenderMode::SideBySide => write!(f, "Ideal vs physical"),
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::led_data::{nearest_pit_lane_led, on_pit_lane};
+use crate::replay::{DriverPosition, Replay};
+
+/// A pit stop from OpenF1 `/v1/pit`. `date` is the pit lane entry and
+/// `pit_duration` the time spent in the pit lane, in seconds.
+#[derive(Debug, Deserialize)]
+pub struct PitData {
+    pub date: String,
+    pub driver_number: u32,
+    pub pit_duration: Option<f32>,
+}
+
+#[derive(Debug, Clone)]
+struct PitStop {
+    driver_number: u32,
+    entry: DateTime<Utc>,
+    duration: Option<Duration>,
+}
+
+impl PitStop {
+    fn exit(&self) -> Option<DateTime<Utc>> {
+        let duration = chrono::Duration::from_std(self.duration?).ok()?;
+        Some(self.entry + duration)
+    }
+}
+
+/// Pit stop count and the duration of the latest completed stop.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PitSummary {
+    pub stops: usize,
+    pub last_duration: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PitStops {
+    stops: Vec<PitStop>,
+}
+
+impl PitStops {
+    pub fn new(data: Vec<PitData>) -> Self {
+        let mut stops: Vec<PitStop> = data
+            .into_iter()
+            .filter_map(|d| {
+                Some(PitStop {
+                    driver_number: d.driver_number,
+                    entry: d.date.parse::<DateTime<Utc>>().ok()?,
+                    duration: d
+                        .pit_duration
+                        .and_then(|secs| Duration::try_from_secs_f32(secs).ok()),
+                })
+            })
+            .collect();
+        stops.sort_by_key(|stop| stop.entry);
+
+        Self { stops }
+    }
+
+    /// Detects pit stops from car positions, for sessions where `/v1/pit`
+    /// has nothing. A car already in the pit lane at its first sample is
+    /// still in the garage and does not count as stopping.
+    pub fn from_replay(replay: &Replay) -> Self {
+        let mut stops = Vec::new();
+
+        for (driver_number, samples) in replay.tracks() {
+            let mut entry: Option<DateTime<Utc>> = None;
+            let mut in_garage = true;
+
+            for sample in samples {
+                match (on_pit_lane(sample.x, sample.y), entry) {
+                    (true, None) if !in_garage => entry = Some(sample.date),
+                    (false, Some(entered)) => {
+                        stops.push(PitStop {
+                            driver_number,
+                            entry: entered,
+                            duration: (sample.date - entered).to_std().ok(),
+                        });
+                        entry = None;
+                    }
+                    (false, None) => in_garage = false,
+                    _ => {}
+                }
+            }
+
+            if let Some(entered) = entry {
+                stops.push(PitStop {
+                    driver_number,
+                    entry: entered,
+                    duration: None,
+                });
+            }
+        }
+        stops.sort_by_key(|stop| stop.entry);
+
+        Self { stops }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stops.is_empty()
+    }
+
+    /// Whether `driver_number` is between pit entry and pit exit at `time`.
+    /// A stop without a duration has no known exit and lasts until the end.
+    pub fn in_pit(&self, driver_number: u32, time: DateTime<Utc>) -> bool {
+        self.stops.iter().any(|stop| {
+            stop.driver_number == driver_number
+                && stop.entry <= time
+                && stop.exit().is_none_or(|exit| time < exit)
+        })
+    }
+
+    pub fn summary_at(&self, driver_number: u32, time: DateTime<Utc>) -> PitSummary {
+        let mut summary = PitSummary::default();
+
+        for stop in self
+            .stops
+            .iter()
+            .filter(|stop| stop.driver_number == driver_number && stop.entry <= time)
+        {
+            summary.stops += 1;
+            if stop.exit().is_some_and(|exit| exit <= time) {
+                summary.last_duration = stop.duration;
+            }
+        }
+
+        summary
+    }
+
+    /// Marks the drivers that are in the pit lane at `time`. With
+    /// `pit_lane_leds` set, pitting cars also move onto the pit lane LEDs.
+    pub fn place(&self, positions: &mut [DriverPosition], time: DateTime<Utc>, pit_lane_leds: bool) {
+        for position in positions.iter_mut() {
+            position.in_pit = self.in_pit(position.driver_number, time);
+
+            if position.in_pit && pit_lane_leds {
+                position.led_number = nearest_pit_lane_led(position.x, position.y).led_number;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::led_data::{nearest_led, LedCoordinate, LED_DATA, PIT_LANE_LED_DATA};
+    use crate::replay::LocationData;
+
+    fn at(second: u32) -> DateTime<Utc> {
+        format!("2023-08-27T13:03:{:02}Z", second).parse().unwrap()
+    }
+
+    /// A replay of `driver_number` at each LED of `leds`, one a second.
+    fn drive(replay: &mut Replay, driver_number: u32, leds: &[&LedCoordinate]) {
+        let samples = leds
+            .iter()
+            .enumerate()
+            .map(|(second, led)| LocationData {
+                x: led.x_led,
+                y: led.y_led,
+                date: at(second as u32).to_rfc3339(),
+                driver_number,
+            })
+            .collect();
+        replay.add_driver(samples);
+    }
+
+    #[test]
+    fn stops_are_found_where_cars_use_the_pit_lane() {
+        let track = &LED_DATA[10];
+        let pit_lane: Vec<&LedCoordinate> = PIT_LANE_LED_DATA.iter().take(4).collect();
+        let mut replay = Replay::default();
+        // On the track for two samples, in the pit lane for four, then back.
+        let mut leds = vec![track, track];
+        leds.extend(&pit_lane);
+        leds.extend([track, track]);
+        drive(&mut replay, 1, &leds);
+
+        let stops = PitStops::from_replay(&replay);
+        assert!(!stops.in_pit(1, at(1)));
+        assert!(stops.in_pit(1, at(2)));
+        assert!(stops.in_pit(1, at(5)));
+        assert!(!stops.in_pit(1, at(6)));
+        let summary = stops.summary_at(1, at(7));
+        assert_eq!(summary.stops, 1);
+        assert_eq!(summary.last_duration, Some(Duration::from_secs(4)));
+    }
+
+    #[test]
+    fn passing_the_pit_lane_is_no_stop() {
+        // Down the main straight, on the track LEDs closest to the pit lane.
+        let leds: Vec<&LedCoordinate> = PIT_LANE_LED_DATA
+            .iter()
+            .map(|led| nearest_led(led.x_led, led.y_led))
+            .filter(|led| led.x_led != 0.0 && led.y_led != 0.0)
+            .collect();
+        assert!(leds.len() > 2);
+        let mut replay = Replay::default();
+        drive(&mut replay, 1, &leds);
+
+        assert!(PitStops::from_replay(&replay).is_empty());
+    }
+}
@@ -0,0 +1,157 @@
+use std::time::Duration;
+
+use crate::layout::Layout;
+use crate::led_data::UpdateFrame;
+use crate::physical::LedModel;
+
+/// Steps of the search for the dimming that brings a frame under budget.
+const LIMITER_STEPS: u32 = 12;
+
+/// Supply current budget of the board. When enabled, frames that would
+/// draw more are dimmed as a whole until they fit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerBudget {
+    pub enabled: bool,
+    /// Most current the whole board may draw, in mA.
+    pub limit_ma: f32,
+}
+
+impl Default for PowerBudget {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            limit_ma: 2000.0,
+        }
+    }
+}
+
+impl PowerBudget {
+    /// `frame` dimmed just enough to stay within the budget, or unchanged
+    /// if it already does or the limiter is off.
+    ///
+    /// The colors themselves are scaled, not the LED model, so the screen
+    /// and anything the frame is sent to see the same limited board.
+    pub fn limit(&self, frame: &UpdateFrame, model: &LedModel, layout: &Layout) -> UpdateFrame {
+        if !self.enabled || model.board_current_ma(frame, layout) <= self.limit_ma {
+            return frame.clone();
+        }
+
+        // Gamma and the per-LED limit make current nonlinear in the color
+        // values, so the scale is found by bisection.
+        let (mut low, mut high) = (0.0, 1.0);
+        for _ in 0..LIMITER_STEPS {
+            let scale = (low + high) / 2.0;
+            if model.board_current_ma(&scaled(frame, scale), layout) <= self.limit_ma {
+                low = scale;
+            } else {
+                high = scale;
+            }
+        }
+        scaled(frame, low)
+    }
+}
+
+fn scaled(frame: &UpdateFrame, scale: f32) -> UpdateFrame {
+    let channel = |c: u8| (c as f32 * scale) as u8;
+    UpdateFrame {
+        timestamp: frame.timestamp,
+        led_states: frame
+            .led_states
+            .iter()
+            .map(|(led_number, (r, g, b))| (*led_number, (channel(*r), channel(*g), channel(*b))))
+            .collect(),
+    }
+}
+
+/// Current draw of the board over a whole session.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PowerReport {
+    pub peak_ma: f32,
+    /// Replay time of the frame with the peak draw.
+    pub peak_at: Duration,
+    pub average_ma: f32,
+    pub frames: usize,
+    /// Frames that draw more than the budget, and would be dimmed if the
+    /// limiter is on.
+    pub over_budget: usize,
+}
+
+impl PowerReport {
+    /// Measures the unlimited draw of every frame in `frames`.
+    pub fn measure(
+        frames: impl IntoIterator<Item = (Duration, UpdateFrame)>,
+        model: &LedModel,
+        layout: &Layout,
+        budget: &PowerBudget,
+    ) -> Self {
+        let mut report = PowerReport::default();
+        let mut total = 0.0;
+        for (elapsed, frame) in frames {
+            let current = model.board_current_ma(&frame, layout);
+            if report.frames == 0 || current > report.peak_ma {
+                report.peak_ma = current;
+                report.peak_at = elapsed;
+            }
+            if current > budget.limit_ma {
+                report.over_budget += 1;
+            }
+            total += current;
+            report.frames += 1;
+        }
+        if report.frames > 0 {
+            report.average_ma = total / report.frames as f32;
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn white(layout: &Layout, leds: usize) -> UpdateFrame {
+        let mut frame = UpdateFrame::new(0);
+        for led in layout.leds().iter().take(leds) {
+            frame.set_led_state(led.led_number, (255, 255, 255));
+        }
+        frame
+    }
+
+    const BUDGET: PowerBudget = PowerBudget {
+        enabled: true,
+        limit_ma: 2000.0,
+    };
+
+    #[test]
+    fn frames_over_budget_are_dimmed_to_just_under_it() {
+        let layout = Layout::new(true);
+        let frame = white(&layout, usize::MAX);
+        for gamma in [1.0, 2.2] {
+            let model = LedModel {
+                gamma,
+                ..LedModel::default()
+            };
+            assert!(model.board_current_ma(&frame, &layout) > BUDGET.limit_ma);
+
+            let current = model.board_current_ma(&BUDGET.limit(&frame, &model, &layout), &layout);
+            assert!(current <= BUDGET.limit_ma, "{} mA at gamma {}", current, gamma);
+            assert!(current > BUDGET.limit_ma * 0.95, "{} mA at gamma {}", current, gamma);
+        }
+    }
+
+    #[test]
+    fn frames_within_budget_are_left_alone() {
+        let layout = Layout::new(true);
+        let model = LedModel::default();
+        let frame = white(&layout, 3);
+        assert!(model.board_current_ma(&frame, &layout) < BUDGET.limit_ma);
+        assert_eq!(BUDGET.limit(&frame, &model, &layout).led_states, frame.led_states);
+
+        let frame = white(&layout, usize::MAX);
+        let disabled = PowerBudget {
+            enabled: false,
+            ..BUDGET
+        };
+        assert_eq!(disabled.limit(&frame, &model, &layout).led_states, frame.led_states);
+    }
+}
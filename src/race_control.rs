@@ -0,0 +1,208 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// A message from OpenF1 `/v1/race_control`.
+#[derive(Debug, Deserialize)]
+pub struct RaceControlMessage {
+    pub date: String,
+    pub category: String,
+    pub flag: Option<String>,
+    pub scope: Option<String>,
+    pub sector: Option<u32>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectorFlag {
+    Yellow,
+    DoubleYellow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SafetyCar {
+    Deployed,
+    Virtual,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Event {
+    Sector(u32, Option<SectorFlag>),
+    Green,
+    Red,
+    Chequered,
+    SafetyCar(SafetyCar),
+    VirtualSafetyCarEnding,
+}
+
+impl Event {
+    fn from_message(message: &RaceControlMessage) -> Option<Event> {
+        match message.category.as_str() {
+            "Flag" => {
+                let flag = message.flag.as_deref()?;
+                match (message.scope.as_deref(), flag) {
+                    (Some("Sector"), "YELLOW") => {
+                        Some(Event::Sector(message.sector?, Some(SectorFlag::Yellow)))
+                    }
+                    (Some("Sector"), "DOUBLE YELLOW") => {
+                        Some(Event::Sector(message.sector?, Some(SectorFlag::DoubleYellow)))
+                    }
+                    (Some("Sector"), "CLEAR" | "GREEN") => Some(Event::Sector(message.sector?, None)),
+                    (Some("Track"), "GREEN" | "CLEAR") => Some(Event::Green),
+                    (Some("Track"), "RED") => Some(Event::Red),
+                    (_, "CHEQUERED") => Some(Event::Chequered),
+                    _ => None,
+                }
+            }
+            "SafetyCar" => {
+                let text = message.message.to_uppercase();
+                if text.contains("VIRTUAL SAFETY CAR DEPLOYED") {
+                    Some(Event::SafetyCar(SafetyCar::Virtual))
+                } else if text.contains("VIRTUAL SAFETY CAR ENDING") {
+                    Some(Event::VirtualSafetyCarEnding)
+                } else if text.contains("SAFETY CAR DEPLOYED") {
+                    Some(Event::SafetyCar(SafetyCar::Deployed))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The state of the track at one instant, folded from every race control
+/// message issued up to that instant.
+#[derive(Debug, Clone, Default)]
+pub struct TrackStatus {
+    pub sector_flags: BTreeMap<u32, SectorFlag>,
+    pub red_flag: bool,
+    pub safety_car: Option<SafetyCar>,
+    /// How long ago the chequered flag was shown, once it has been.
+    pub chequered_for: Option<Duration>,
+}
+
+impl TrackStatus {
+    pub fn label(&self) -> Option<&'static str> {
+        if self.chequered_for.is_some() {
+            Some("CHEQUERED FLAG")
+        } else if self.red_flag {
+            Some("RED FLAG")
+        } else {
+            match self.safety_car {
+                Some(SafetyCar::Deployed) => Some("SAFETY CAR"),
+                Some(SafetyCar::Virtual) => Some("VIRTUAL SAFETY CAR"),
+                None if !self.sector_flags.is_empty() => Some("YELLOW FLAG"),
+                None => None,
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RaceControl {
+    events: Vec<(DateTime<Utc>, Event)>,
+}
+
+impl RaceControl {
+    pub fn new(messages: Vec<RaceControlMessage>) -> Self {
+        let mut events: Vec<(DateTime<Utc>, Event)> = messages
+            .iter()
+            .filter_map(|message| {
+                let date = message.date.parse::<DateTime<Utc>>().ok()?;
+                Some((date, Event::from_message(message)?))
+            })
+            .collect();
+        events.sort_by_key(|(date, _)| *date);
+
+        Self { events }
+    }
+
+    pub fn status_at(&self, time: DateTime<Utc>) -> TrackStatus {
+        let mut status = TrackStatus::default();
+
+        for (date, event) in self.events.iter().take_while(|(date, _)| *date <= time) {
+            match *event {
+                Event::Sector(sector, Some(flag)) => {
+                    status.sector_flags.insert(sector, flag);
+                }
+                Event::Sector(sector, None) => {
+                    status.sector_flags.remove(&sector);
+                }
+                Event::Green => {
+                    status.sector_flags.clear();
+                    status.red_flag = false;
+                    status.safety_car = None;
+                }
+                Event::Red => status.red_flag = true,
+                Event::Chequered if status.chequered_for.is_none() => {
+                    status.chequered_for = (time - *date).to_std().ok();
+                }
+                Event::Chequered => {}
+                Event::SafetyCar(safety_car) => status.safety_car = Some(safety_car),
+                Event::VirtualSafetyCarEnding => {
+                    if status.safety_car == Some(SafetyCar::Virtual) {
+                        status.safety_car = None;
+                    }
+                }
+            }
+        }
+
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(second: u32, category: &str, flag: Option<&str>, scope: Option<&str>, sector: Option<u32>, text: &str) -> RaceControlMessage {
+        RaceControlMessage {
+            date: format!("2023-08-27T13:03:{:02}Z", second),
+            category: category.to_string(),
+            flag: flag.map(String::from),
+            scope: scope.map(String::from),
+            sector,
+            message: text.to_string(),
+        }
+    }
+
+    fn at(second: u32) -> DateTime<Utc> {
+        format!("2023-08-27T13:03:{:02}Z", second).parse().unwrap()
+    }
+
+    #[test]
+    fn safety_car_ends_at_the_green_flag() {
+        let race_control = RaceControl::new(vec![
+            // Out of order, as they may come in.
+            message(30, "Flag", Some("GREEN"), Some("Track"), None, "TRACK CLEAR"),
+            message(10, "SafetyCar", None, None, None, "SAFETY CAR DEPLOYED"),
+            message(5, "Flag", Some("YELLOW"), Some("Sector"), Some(4), "YELLOW IN TRACK SECTOR 4"),
+        ]);
+
+        assert_eq!(race_control.status_at(at(0)).label(), None);
+        assert_eq!(race_control.status_at(at(5)).label(), Some("YELLOW FLAG"));
+        let status = race_control.status_at(at(20));
+        assert_eq!(status.safety_car, Some(SafetyCar::Deployed));
+        assert_eq!(status.label(), Some("SAFETY CAR"));
+        // The green flag clears the sectors along with the safety car.
+        let status = race_control.status_at(at(30));
+        assert_eq!(status.safety_car, None);
+        assert!(status.sector_flags.is_empty());
+        assert_eq!(status.label(), None);
+    }
+
+    #[test]
+    fn sector_flags_are_cleared_one_by_one() {
+        let race_control = RaceControl::new(vec![
+            message(1, "Flag", Some("YELLOW"), Some("Sector"), Some(4), "YELLOW IN TRACK SECTOR 4"),
+            message(2, "Flag", Some("DOUBLE YELLOW"), Some("Sector"), Some(9), "DOUBLE YELLOW IN TRACK SECTOR 9"),
+            message(3, "Flag", Some("CLEAR"), Some("Sector"), Some(4), "CLEAR IN TRACK SECTOR 4"),
+        ]);
+
+        let flags = |second| race_control.status_at(at(second)).sector_flags.into_iter().collect::<Vec<_>>();
+        assert_eq!(flags(2), [(4, SectorFlag::Yellow), (9, SectorFlag::DoubleYellow)]);
+        assert_eq!(flags(3), [(9, SectorFlag::DoubleYellow)]);
+    }
+}
@@ -0,0 +1,174 @@
+use std::fs::{self, File};
+use std::path::Path;
+use std::time::Duration;
+
+use iced::widget::canvas::{self, Cache, Frame, Program, Text};
+use iced::{mouse, Color, Font, Pixels, Point, Rectangle, Size, Theme};
+use iced_tiny_skia::graphics::Viewport;
+use tiny_skia::{Mask, Pixmap};
+
+use crate::board::Board;
+use crate::graph::{Camera, Graph};
+use crate::layout::Layout;
+use crate::physical::LedModel;
+use crate::replay::format_elapsed;
+use crate::trails::{TrailMode, Trails};
+
+const CAPTION_SIZE: f32 = 24.0;
+const CAPTION_LINE_HEIGHT: f32 = 30.0;
+
+/// What part of the replay to render, and how.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    pub from: Duration,
+    pub to: Duration,
+    pub fps: u32,
+    pub width: u32,
+    pub height: u32,
+    pub physical: Option<LedModel>,
+    /// Space kept clear around the board, in pixels.
+    pub padding: f32,
+}
+
+/// Draws `graph` the way the window does, with `caption` in the top left
+/// corner, on the CPU.
+///
+/// This goes through the same software renderer iced falls back to when
+/// there is no GPU, so the image matches what `Graph::draw` puts on screen.
+pub fn render(graph: &Graph, caption: &[String], width: u32, height: u32) -> Result<Pixmap, String> {
+    let bounds = Rectangle::new(Point::ORIGIN, Size::new(width as f32, height as f32));
+    let mut renderer = iced::Renderer::TinySkia(iced_tiny_skia::Renderer::new(
+        iced_tiny_skia::Backend::new(),
+        Font::default(),
+        Pixels(16.0),
+    ));
+
+    let mut layers = graph.draw(
+        &Camera::default(),
+        &renderer,
+        &Theme::Light,
+        bounds,
+        mouse::Cursor::Unavailable,
+    );
+
+    let mut frame = Frame::new(&renderer, bounds.size());
+    let color = if graph.physical.is_some() { Color::WHITE } else { Color::BLACK };
+    for (i, line) in caption.iter().enumerate() {
+        frame.fill_text(Text {
+            content: line.clone(),
+            position: Point::new(10.0, 10.0 + i as f32 * CAPTION_LINE_HEIGHT),
+            color,
+            size: CAPTION_SIZE.into(),
+            font: Font::MONOSPACE,
+            ..Text::default()
+        });
+    }
+    layers.push(frame.into_geometry());
+    canvas::Renderer::draw(&mut renderer, layers);
+
+    let mut pixmap = Pixmap::new(width, height).ok_or("Invalid image size")?;
+    let mut mask = Mask::new(width, height).ok_or("Invalid image size")?;
+    let viewport = Viewport::with_physical_size(Size::new(width, height), 1.0);
+
+    match &mut renderer {
+        iced::Renderer::TinySkia(renderer) => renderer.with_primitives(|backend, primitives| {
+            backend.draw(
+                &mut pixmap.as_mut(),
+                &mut mask,
+                primitives,
+                &viewport,
+                &[bounds],
+                Color::WHITE,
+                &[] as &[String],
+            );
+        }),
+        _ => unreachable!("the renderer was created as tiny-skia"),
+    }
+
+    // The backend draws for a window surface, which is BGRA.
+    for pixel in pixmap.data_mut().chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+    }
+
+    Ok(pixmap)
+}
+
+/// Renders the replay from `options.from` to `options.to` into `output`:
+/// an animated GIF if it ends in `.gif`, otherwise a directory of numbered
+/// PNGs, with the LEDs where `layout` draws them and `options.padding`
+/// around them. Returns the number of frames written.
+pub fn export(
+    board: &Board,
+    layout: &Layout,
+    reference_lap: Option<&[(f32, f32)]>,
+    options: &RenderOptions,
+    output: &Path,
+) -> Result<usize, String> {
+    if options.fps == 0 || options.to < options.from {
+        return Err("Nothing to render".to_string());
+    }
+
+    let track_cache = Cache::new();
+    let interval = Duration::from_secs(1) / options.fps;
+
+    let mut trails = Trails::new(TrailMode::Off);
+
+    let mut gif = if output.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gif")) {
+        let file = File::create(output).map_err(|e| e.to_string())?;
+        let mut encoder = gif::Encoder::new(file, options.width as u16, options.height as u16, &[])
+            .map_err(|e| e.to_string())?;
+        encoder.set_repeat(gif::Repeat::Infinite).map_err(|e| e.to_string())?;
+        Some(encoder)
+    } else {
+        fs::create_dir_all(output).map_err(|e| e.to_string())?;
+        None
+    };
+
+    let mut count = 0;
+    let mut elapsed = options.from;
+    while elapsed <= options.to {
+        let (frame, positions) = board
+            .frame_at(elapsed, &mut trails)
+            .ok_or("The replay has no data")?;
+        let status = board
+            .replay
+            .time_at(elapsed)
+            .map(|time| board.race_control.status_at(time));
+
+        let graph = Graph {
+            layout,
+            track_cache: &track_cache,
+            update_frame: Some(&frame),
+            positions: &positions,
+            reference_lap,
+            physical: options.physical,
+            hovered: None,
+            follow: None,
+            padding: options.padding,
+        };
+        let caption: Vec<String> = std::iter::once(format_elapsed(elapsed))
+            .chain(status.as_ref().and_then(|status| status.label()).map(String::from))
+            .collect();
+        let pixmap = render(&graph, &caption, options.width, options.height)?;
+
+        match &mut gif {
+            Some(encoder) => {
+                let mut pixels = pixmap.take();
+                let mut gif_frame =
+                    gif::Frame::from_rgba_speed(options.width as u16, options.height as u16, &mut pixels, 10);
+                // GIF delays are in hundredths of a second.
+                gif_frame.delay = (interval.as_millis() / 10) as u16;
+                encoder.write_frame(&gif_frame).map_err(|e| e.to_string())?;
+            }
+            None => {
+                let path = output.join(format!("frame_{:05}.png", count));
+                pixmap.save_png(&path).map_err(|e| e.to_string())?;
+            }
+        }
+
+        count += 1;
+        elapsed += interval;
+    }
+
+    Ok(count)
+}
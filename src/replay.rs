@@ -0,0 +1,241 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::filter::DriverFilter;
+use crate::led_data::{is_pit_lane_led, nearest_led, UpdateFrame};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LocationData {
+    pub x: f32,
+    pub y: f32,
+    pub date: String,
+    pub driver_number: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub date: DateTime<Utc>,
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Clone)]
+struct DriverTrack {
+    driver_number: u32,
+    samples: Vec<Sample>,
+}
+
+/// Where a driver is on the board at a given point of the replay.
+#[derive(Debug, Clone, Copy)]
+pub struct DriverPosition {
+    pub driver_number: u32,
+    pub led_number: u32,
+    pub x: f32,
+    pub y: f32,
+    pub in_pit: bool,
+}
+
+impl DriverPosition {
+    /// Drivers in the pit lane are only on the board when they have been
+    /// placed on a pit lane LED.
+    pub fn on_board(&self) -> bool {
+        !self.in_pit || is_pit_lane_led(self.led_number)
+    }
+}
+
+/// The location history of every fetched driver, replayed against a clock
+/// that starts at the earliest sample of the session.
+#[derive(Debug, Clone, Default)]
+pub struct Replay {
+    tracks: Vec<DriverTrack>,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+}
+
+impl DriverTrack {
+    /// The samples of one driver, in order, leaving out the ones without a
+    /// position or with one that is not a number.
+    fn from_locations(data: Vec<LocationData>) -> Option<Self> {
+        let mut samples: Vec<(u32, Sample)> = data
+            .into_iter()
+            .filter(|d| d.x != 0.0 && d.y != 0.0 && d.x.is_finite() && d.y.is_finite())
+            .filter_map(|d| {
+                let date = d.date.parse::<DateTime<Utc>>().ok()?;
+                Some((d.driver_number, Sample { date, x: d.x, y: d.y }))
+            })
+            .collect();
+        samples.sort_by_key(|(_, s)| s.date);
+        // Feeds may deliver a sample more than once.
+        samples.dedup_by_key(|(_, s)| s.date);
+
+        let (driver_number, _) = samples.first()?;
+        let driver_number = *driver_number;
+        let samples: Vec<Sample> = samples.into_iter().map(|(_, s)| s).collect();
+
+        Some(Self {
+            driver_number,
+            samples,
+        })
+    }
+}
+
+impl Replay {
+    pub fn add_driver(&mut self, data: Vec<LocationData>) {
+        if let Some(track) = DriverTrack::from_locations(data) {
+            self.push_track(track);
+        }
+    }
+
+    /// Adds samples of any number of drivers that came in after the replay
+    /// was loaded, as a live session does. Samples that are not newer than
+    /// the latest one of their driver are already in the replay and are
+    /// dropped.
+    pub fn append(&mut self, data: Vec<LocationData>) {
+        let mut drivers: BTreeMap<u32, Vec<LocationData>> = BTreeMap::new();
+        for d in data {
+            drivers.entry(d.driver_number).or_default().push(d);
+        }
+
+        for track in drivers.into_values().filter_map(DriverTrack::from_locations) {
+            let Some(existing) = self
+                .tracks
+                .iter_mut()
+                .find(|existing| existing.driver_number == track.driver_number)
+            else {
+                self.push_track(track);
+                continue;
+            };
+
+            let latest = existing.samples[existing.samples.len() - 1].date;
+            existing
+                .samples
+                .extend(track.samples.into_iter().filter(|s| s.date > latest));
+            let last = existing.samples[existing.samples.len() - 1].date;
+            self.end = Some(self.end.map_or(last, |end| end.max(last)));
+        }
+    }
+
+    /// Reads a replay from a CSV export of location samples, with `x`, `y`,
+    /// `date` and `driver_number` columns, to work without the API.
+    pub fn from_csv(path: &Path) -> Result<Replay, String> {
+        let mut reader = csv::Reader::from_path(path).map_err(|e| e.to_string())?;
+
+        let mut drivers: BTreeMap<u32, Vec<LocationData>> = BTreeMap::new();
+        for record in reader.deserialize() {
+            let data: LocationData = record.map_err(|e| e.to_string())?;
+            drivers.entry(data.driver_number).or_default().push(data);
+        }
+
+        let mut replay = Replay::default();
+        for data in drivers.into_values() {
+            replay.add_driver(data);
+        }
+        Ok(replay)
+    }
+
+    /// Adds the drivers of `other` that are not in this replay yet.
+    pub fn merge(&mut self, other: Replay) {
+        for track in other.tracks {
+            if !self.has_driver(track.driver_number) {
+                self.push_track(track);
+            }
+        }
+    }
+
+    fn push_track(&mut self, track: DriverTrack) {
+        let first = track.samples[0].date;
+        let last = track.samples[track.samples.len() - 1].date;
+        self.start = Some(self.start.map_or(first, |start| start.min(first)));
+        self.end = Some(self.end.map_or(last, |end| end.max(last)));
+
+        self.tracks.push(track);
+    }
+
+    pub fn has_driver(&self, driver_number: u32) -> bool {
+        self.tracks.iter().any(|track| track.driver_number == driver_number)
+    }
+
+    pub fn tracks(&self) -> impl Iterator<Item = (u32, &[Sample])> {
+        self.tracks
+            .iter()
+            .map(|track| (track.driver_number, track.samples.as_slice()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+
+    /// The date of the newest sample of any driver.
+    pub fn latest(&self) -> Option<DateTime<Utc>> {
+        self.end
+    }
+
+    pub fn duration(&self) -> Duration {
+        match (self.start, self.end) {
+            (Some(start), Some(end)) => (end - start).to_std().unwrap_or_default(),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// The wall-clock session time `elapsed` into the replay.
+    pub fn time_at(&self, elapsed: Duration) -> Option<DateTime<Utc>> {
+        let elapsed = chrono::Duration::from_std(elapsed).ok()?;
+        Some(self.start? + elapsed)
+    }
+
+    pub fn positions_at(&self, elapsed: Duration) -> Vec<DriverPosition> {
+        let Some(time) = self.time_at(elapsed) else {
+            return Vec::new();
+        };
+
+        self.tracks
+            .iter()
+            .filter_map(|track| {
+                let index = track.samples.partition_point(|s| s.date <= time);
+                let sample = track.samples.get(index.checked_sub(1)?)?;
+                Some(DriverPosition {
+                    driver_number: track.driver_number,
+                    led_number: nearest_led(sample.x, sample.y).led_number,
+                    x: sample.x,
+                    y: sample.y,
+                    in_pit: false,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Replay time as shown under the board, `HH:MM:SS.cc`.
+pub fn format_elapsed(elapsed: Duration) -> String {
+    const MINUTE: u64 = 60;
+    const HOUR: u64 = 60 * MINUTE;
+
+    let seconds = elapsed.as_secs();
+    format!(
+        "{:0>2}:{:0>2}:{:0>2}.{:0>2}",
+        seconds / HOUR,
+        (seconds % HOUR) / MINUTE,
+        seconds % MINUTE,
+        elapsed.subsec_millis() / 10,
+    )
+}
+
+/// Lights the LED of each driver on the board in the color `filter` gives them.
+pub fn frame_from_positions(
+    timestamp: u64,
+    positions: &[DriverPosition],
+    filter: &DriverFilter,
+) -> UpdateFrame {
+    let mut update_frame = UpdateFrame::new(timestamp);
+
+    for position in positions.iter().filter(|p| p.on_board()) {
+        if let Some(color) = filter.color_for(position.driver_number) {
+            update_frame.set_led_state(position.led_number, color);
+        }
+    }
+
+    update_frame
+}
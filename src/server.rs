@@ -0,0 +1,280 @@
+use axum::extract::ws::{Message as WsMessage, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use iced::futures::SinkExt;
+use iced::subscription::{self, Subscription};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch};
+
+use crate::led_data::UpdateFrame;
+use crate::replay::DriverPosition;
+
+/// The fastest a replay can be played, as a multiple of the session's own
+/// pace. Faster speeds a client asks for are slowed to it.
+pub const MAX_SPEED: f32 = 64.0;
+
+/// A command a client sends to control the replay, as JSON such as
+/// `{"command": "seek", "seconds": 600}`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Control {
+    Play,
+    Pause,
+    /// Moves the replay to `seconds` after its start.
+    Seek { seconds: f64 },
+    /// Plays the replay back `speed` times as fast as the session ran, from
+    /// 0 to `MAX_SPEED`.
+    Speed { speed: f32 },
+    /// Loads another OpenF1 session.
+    Session { key: String },
+}
+
+impl Control {
+    /// The command with its speed brought within 0 to `MAX_SPEED`, or an
+    /// error if it is not a number.
+    pub fn checked(self) -> Result<Self, String> {
+        match self {
+            Control::Speed { speed } if !speed.is_finite() => Err(format!("speed {} is not a number", speed)),
+            Control::Speed { speed } => Ok(Control::Speed {
+                speed: speed.clamp(0.0, MAX_SPEED),
+            }),
+            control => Ok(control),
+        }
+    }
+}
+
+/// The board at one moment of the replay, as it is sent to clients.
+#[derive(Debug, Clone, Serialize)]
+pub struct FrameState {
+    pub elapsed_ms: u64,
+    pub time: Option<DateTime<Utc>>,
+    pub playing: bool,
+    pub speed: f32,
+    pub session: String,
+    pub status: Option<&'static str>,
+    pub leds: Vec<LedState>,
+    pub drivers: Vec<DriverState>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LedState {
+    pub led: u32,
+    pub color: [u8; 3],
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DriverState {
+    pub driver_number: u32,
+    pub led_number: u32,
+    pub in_pit: bool,
+}
+
+/// The LEDs lit in `frame`, each listed once with the color it shows.
+pub fn led_states(frame: Option<&UpdateFrame>) -> Vec<LedState> {
+    let mut leds: Vec<LedState> = Vec::new();
+    for (led, (r, g, b)) in frame.map_or(&[][..], |frame| &frame.led_states) {
+        // The first entry for an LED is the one that is shown.
+        if !leds.iter().any(|state| state.led == *led) {
+            leds.push(LedState {
+                led: *led,
+                color: [*r, *g, *b],
+            });
+        }
+    }
+    leds
+}
+
+/// The drivers of `positions` that are on the board.
+pub fn driver_states(positions: &[DriverPosition]) -> Vec<DriverState> {
+    positions
+        .iter()
+        .filter(|p| p.on_board())
+        .map(|p| DriverState {
+            driver_number: p.driver_number,
+            led_number: p.led_number,
+            in_pit: p.in_pit,
+        })
+        .collect()
+}
+
+/// Where the replay sends its frames to be served. Clients that are slower
+/// than the replay skip frames rather than fall behind.
+#[derive(Debug, Clone)]
+pub struct Frames(Arc<watch::Sender<Option<Arc<str>>>>);
+
+impl Frames {
+    pub fn publish(&self, state: &FrameState) {
+        match serde_json::to_string(state) {
+            Ok(json) => {
+                self.0.send_replace(Some(json.into()));
+            }
+            Err(e) => eprintln!("Failed to encode the frame: {}", e),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Shared {
+    frames: watch::Receiver<Option<Arc<str>>>,
+    controls: mpsc::Sender<Control>,
+}
+
+/// Serves on `port` until the process ends:
+///
+/// - `GET /frame`: the latest frame as JSON.
+/// - `GET /ws`: a WebSocket that streams every frame and takes commands.
+/// - `POST /control`: a command as JSON.
+///
+/// Commands are handed to `controls`.
+pub async fn serve(port: u16, controls: mpsc::Sender<Control>) -> Result<Frames, String> {
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", port))
+        .await
+        .map_err(|e| format!("failed to listen on port {}: {}", port, e))?;
+    eprintln!("Serving frames on http://127.0.0.1:{}", port);
+    Ok(serve_on(listener, controls))
+}
+
+/// Serves as [`serve`] does on a listener that is already bound.
+pub fn serve_on(listener: tokio::net::TcpListener, controls: mpsc::Sender<Control>) -> Frames {
+    let (sender, frames) = watch::channel(None);
+    let app = Router::new()
+        .route("/frame", get(frame))
+        .route("/ws", get(websocket))
+        .route("/control", post(control))
+        .with_state(Shared { frames, controls });
+
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            eprintln!("The frame server stopped: {}", e);
+        }
+    });
+
+    Frames(Arc::new(sender))
+}
+
+/// What the frame server hands the window.
+#[derive(Debug, Clone)]
+pub enum ServerEvent {
+    Started(Frames),
+    Control(Control),
+}
+
+/// Runs the frame server on `port` for as long as the subscription is kept.
+pub fn subscription(port: u16) -> Subscription<ServerEvent> {
+    subscription::channel(port, 16, move |mut output| async move {
+        let (controls, mut received) = mpsc::channel(16);
+        match serve(port, controls).await {
+            Ok(frames) => {
+                let _ = output.send(ServerEvent::Started(frames)).await;
+            }
+            Err(e) => eprintln!("Failed to start the frame server: {}", e),
+        }
+
+        loop {
+            match received.recv().await {
+                Some(control) => {
+                    let _ = output.send(ServerEvent::Control(control)).await;
+                }
+                None => std::future::pending().await,
+            }
+        }
+    })
+}
+
+async fn frame(State(shared): State<Shared>) -> Response {
+    match shared.frames.borrow().clone() {
+        Some(json) => ([("content-type", "application/json")], json.to_string()).into_response(),
+        None => (StatusCode::SERVICE_UNAVAILABLE, "no frame yet").into_response(),
+    }
+}
+
+async fn control(State(shared): State<Shared>, Json(control): Json<Control>) -> Response {
+    let control = match control.checked() {
+        Ok(control) => control,
+        Err(e) => return (StatusCode::UNPROCESSABLE_ENTITY, e).into_response(),
+    };
+    match shared.controls.send(control).await {
+        Ok(()) => StatusCode::ACCEPTED.into_response(),
+        Err(_) => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+    }
+}
+
+async fn websocket(State(shared): State<Shared>, upgrade: WebSocketUpgrade) -> Response {
+    upgrade.on_upgrade(move |socket| stream_frames(socket, shared))
+}
+
+async fn stream_frames(mut socket: WebSocket, shared: Shared) {
+    let mut frames = shared.frames.clone();
+    frames.mark_changed();
+
+    loop {
+        tokio::select! {
+            changed = frames.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+                let json = frames.borrow_and_update().clone();
+                if let Some(json) = json {
+                    if socket.send(WsMessage::Text(json.to_string())).await.is_err() {
+                        return;
+                    }
+                }
+            }
+            received = socket.recv() => match received {
+                Some(Ok(WsMessage::Text(text))) => {
+                    match serde_json::from_str::<Control>(&text).map_err(|e| e.to_string()).and_then(Control::checked) {
+                        Ok(control) => {
+                            let _ = shared.controls.send(control).await;
+                        }
+                        Err(e) => {
+                            let error = serde_json::json!({ "error": e }).to_string();
+                            if socket.send(WsMessage::Text(error)).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
+                }
+                Some(Ok(WsMessage::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn oversized_speeds_are_rejected_or_slowed() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (controls, mut received) = mpsc::channel(16);
+        serve_on(listener, controls);
+        let client = reqwest::Client::new();
+        let post = |body: &'static str| {
+            client
+                .post(format!("http://127.0.0.1:{}/control", port))
+                .header("content-type", "application/json")
+                .body(body)
+                .send()
+        };
+
+        // 1e39 is past the largest f32, so it arrives as infinity.
+        let response = post(r#"{"command": "speed", "speed": 1e39}"#).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(received.try_recv().is_err());
+
+        let response = post(r#"{"command": "speed", "speed": 1e30}"#).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        match received.recv().await {
+            Some(Control::Speed { speed }) => assert_eq!(speed, MAX_SPEED),
+            other => panic!("expected a speed, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,525 @@
+use chrono::{DateTime, SecondsFormat, TimeDelta, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::driver_info::DRIVERS;
+use crate::led_data::{nearest_led, sector_for_led, LedCoordinate, LED_DATA, PIT_LANE_LED_DATA};
+use crate::race_control::RaceControlMessage;
+use crate::replay::LocationData;
+
+/// Time between two location samples of a car, about the OpenF1 rate.
+const SAMPLE_INTERVAL: f32 = 0.25;
+/// Lap time of the fastest car on a clean lap, in seconds.
+const BASE_LAP_TIME: f32 = 75.0;
+/// Slowest a car goes through the tightest corner, relative to its pace.
+const MIN_CORNER_FACTOR: f32 = 0.35;
+/// Pit lane speed limit, in track units per second (60 km/h).
+const PIT_SPEED: f32 = 167.0;
+/// Distance between grid slots, in track units.
+const GRID_SPACING: f32 = 80.0;
+/// Closest a car gets to the one ahead unless it passes, in track units.
+const MIN_GAP: f32 = 60.0;
+/// Chance per second of passing the car ahead, per percent of pace
+/// advantage.
+const OVERTAKE_CHANCE: f32 = 0.15;
+/// Chance of a car retiring over the race.
+const DNF_CHANCE: f64 = 0.08;
+/// Chance of a retirement bringing out the safety car.
+const SAFETY_CAR_CHANCE: f64 = 0.5;
+/// Safety car speed relative to racing pace, and how long it stays out, in
+/// laps.
+const SAFETY_CAR_FACTOR: f32 = 0.55;
+const SAFETY_CAR_LAPS: f32 = 2.5;
+/// Gap the field closes up to behind the safety car, in track units.
+const SAFETY_CAR_GAP: f32 = 150.0;
+/// How long a retired car is waved yellow before the marshals clear it.
+const YELLOW_FLAG_TIME: f32 = 30.0;
+/// Side-to-side wander of a car around the LED line, in track units.
+const LATERAL_NOISE: f32 = 15.0;
+/// Time a car keeps driving after the chequered flag, in seconds.
+const COOL_DOWN_TIME: f32 = 20.0;
+/// Track LEDs where the pit lane branches off, before the first pit lane
+/// LED, and rejoins, after the last one.
+const PIT_ENTRY_LED: u32 = 43;
+const PIT_EXIT_LED: u32 = 55;
+
+#[derive(Debug, Clone)]
+pub struct SimulationConfig {
+    pub seed: u64,
+    pub laps: u32,
+    pub start: DateTime<Utc>,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            seed: 1,
+            laps: 10,
+            start: "2023-08-27T13:03:00Z".parse().expect("valid start time"),
+        }
+    }
+}
+
+/// A generated race: the location samples of every driver, and the race
+/// control messages for what happened in it.
+#[derive(Debug, Default)]
+pub struct Simulation {
+    pub locations: Vec<Vec<LocationData>>,
+    pub race_control: Vec<RaceControlMessage>,
+}
+
+/// A polyline the cars drive along, with the speed a car can carry at each
+/// point relative to its pace.
+struct Route {
+    points: Vec<(f32, f32)>,
+    /// Distance from the first point to each point.
+    distances: Vec<f32>,
+    /// Speed factor at each point, lower in tighter corners.
+    factors: Vec<f32>,
+    length: f32,
+}
+
+impl Route {
+    fn new(points: Vec<(f32, f32)>, closed: bool) -> Self {
+        let count = points.len();
+        let segment = |i: usize| {
+            let (a, b) = (points[i], points[(i + 1) % count]);
+            (b.0 - a.0, b.1 - a.1)
+        };
+
+        let mut distances = Vec::with_capacity(count + 1);
+        let mut length = 0.0;
+        for i in 0..count {
+            distances.push(length);
+            if closed || i + 1 < count {
+                let (dx, dy) = segment(i);
+                length += (dx * dx + dy * dy).sqrt();
+            }
+        }
+        if closed {
+            distances.push(length);
+        }
+
+        // The sharper the turn at a point, the slower the car goes there.
+        let factors = (0..count)
+            .map(|i| {
+                if !closed && (i == 0 || i + 1 == count) {
+                    return 1.0;
+                }
+                let (ax, ay) = segment((i + count - 1) % count);
+                let (bx, by) = segment(i);
+                let angle = (ax * by - ay * bx).atan2(ax * bx + ay * by).abs();
+                (1.0 - angle / std::f32::consts::PI * 1.5).max(MIN_CORNER_FACTOR)
+            })
+            .collect();
+
+        Self {
+            points,
+            distances,
+            factors,
+            length,
+        }
+    }
+
+    fn from_leds(leds: &[LedCoordinate], closed: bool) -> Self {
+        Self::new(leds.iter().map(|led| (led.x_led, led.y_led)).collect(), closed)
+    }
+
+    /// The segment `distance` falls on, and how far along it.
+    fn locate(&self, distance: f32) -> (usize, f32) {
+        let distance = distance.rem_euclid(self.length);
+        let index = self.distances.partition_point(|d| *d <= distance).saturating_sub(1);
+        let index = index.min(self.distances.len() - 2);
+        let span = self.distances[index + 1] - self.distances[index];
+        let t = if span > 0.0 { (distance - self.distances[index]) / span } else { 0.0 };
+        (index, t)
+    }
+
+    fn point_at(&self, distance: f32) -> (f32, f32) {
+        let (index, t) = self.locate(distance);
+        let a = self.points[index];
+        let b = self.points[(index + 1) % self.points.len()];
+        (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+    }
+
+    fn factor_at(&self, distance: f32) -> f32 {
+        let (index, t) = self.locate(distance);
+        let a = self.factors[index];
+        let b = self.factors[(index + 1) % self.factors.len()];
+        a + (b - a) * t
+    }
+
+    /// Time a car with a pace of one unit per second takes for a lap.
+    fn unit_lap_time(&self) -> f32 {
+        const STEP: f32 = 10.0;
+        (0..(self.length / STEP) as usize)
+            .map(|i| STEP / self.factor_at(i as f32 * STEP))
+            .sum()
+    }
+
+    fn distance_of(&self, led_number: u32) -> f32 {
+        let index = LED_DATA
+            .iter()
+            .position(|led| led.led_number == led_number)
+            .expect("LED on the track");
+        self.distances[index]
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PitVisit {
+    along: f32,
+    stop_left: f32,
+    /// Track distance of the pit entry on this lap.
+    entry: f32,
+}
+
+struct Car {
+    driver_number: u32,
+    /// Top speed of the car, in track units per second.
+    pace: f32,
+    variance: f32,
+    lap_factor: f32,
+    /// Distance covered since the start of the track polyline, in track
+    /// units. Grows past the lap length as laps are completed.
+    distance: f32,
+    pit_laps: Vec<i32>,
+    stop_time: f32,
+    pit: Option<PitVisit>,
+    retire_at: Option<f32>,
+    retired: bool,
+    finished_at: Option<f32>,
+    samples: Vec<LocationData>,
+}
+
+impl Car {
+    fn lap(&self, track: &Route) -> i32 {
+        (self.distance / track.length).floor() as i32
+    }
+
+    fn running(&self, time: f32) -> bool {
+        !self.retired && self.finished_at.is_none_or(|finished| time - finished < COOL_DOWN_TIME)
+    }
+}
+
+/// Generates a race of `config.laps` laps for the whole field around the
+/// LED layout: cars of different pace and consistency racing for position,
+/// with pit stops, retirements and safety car periods. The same seed always
+/// gives the same race.
+pub fn simulate(config: &SimulationConfig) -> Simulation {
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let track = Route::from_leds(LED_DATA, true);
+
+    let pit_lane = pit_lane(&track);
+    let pit_entry = track.distance_of(PIT_ENTRY_LED);
+    let pit_exit = track.distance_of(PIT_EXIT_LED);
+
+    let base_pace = track.unit_lap_time() / BASE_LAP_TIME;
+    let race_time = BASE_LAP_TIME * config.laps as f32;
+    let start_line = (track.distance_of(50) + track.distance_of(51)) / 2.0;
+    let finish = start_line + config.laps as f32 * track.length;
+
+    let mut cars: Vec<Car> = DRIVERS
+        .iter()
+        .map(|driver| {
+            let pit_laps = match config.laps {
+                0..=3 => Vec::new(),
+                laps if rng.gen_bool(0.3) => vec![
+                    rng.gen_range(laps / 4..laps / 2) as i32,
+                    rng.gen_range(laps / 2..laps * 3 / 4) as i32,
+                ],
+                laps => vec![rng.gen_range(laps / 3..laps * 2 / 3) as i32],
+            };
+            Car {
+                driver_number: driver.number,
+                pace: base_pace * (1.0 - rng.gen_range(0.0..0.02)),
+                variance: rng.gen_range(0.003..0.012),
+                lap_factor: 1.0,
+                distance: 0.0,
+                pit_laps,
+                stop_time: rng.gen_range(2.2..4.0),
+                pit: None,
+                retire_at: rng.gen_bool(DNF_CHANCE).then(|| rng.gen_range(0.1..0.95) * race_time),
+                retired: false,
+                finished_at: None,
+                samples: Vec::new(),
+            }
+        })
+        .collect();
+
+    // The grid is roughly in pace order, with some shuffling from
+    // qualifying.
+    let mut grid: Vec<(f32, usize)> = cars
+        .iter()
+        .enumerate()
+        .map(|(i, car)| (car.pace * (1.0 + rng.gen_range(0.0..0.01)), i))
+        .collect();
+    grid.sort_by(|a, b| b.0.total_cmp(&a.0));
+    for (slot, (_, i)) in grid.into_iter().enumerate() {
+        cars[i].distance = start_line - 100.0 - slot as f32 * GRID_SPACING;
+        cars[i].lap_factor = 1.0 - rng.gen_range(0.0..cars[i].variance);
+    }
+
+    let mut messages = Vec::new();
+    let mut message = |time: f32, category: &str, flag: Option<&str>, scope: Option<&str>, sector: Option<u32>, text: &str| {
+        messages.push(RaceControlMessage {
+            date: format_date(config.start, time),
+            category: category.to_string(),
+            flag: flag.map(String::from),
+            scope: scope.map(String::from),
+            sector,
+            message: text.to_string(),
+        });
+    };
+    message(0.0, "Flag", Some("GREEN"), Some("Track"), None, "GREEN LIGHT - PIT EXIT OPEN");
+
+    let mut safety_car: Option<(f32, f32)> = None;
+    let mut yellow_flags: Vec<(f32, u32)> = Vec::new();
+    let mut chequered = false;
+
+    let mut time = 0.0;
+    while cars.iter().any(|car| car.running(time)) {
+        time += SAMPLE_INTERVAL;
+
+        let safety_car_out = safety_car.is_some_and(|(from, to)| (from..to).contains(&time));
+        if let Some((_, to)) = safety_car {
+            if time >= to && time - SAMPLE_INTERVAL < to {
+                message(time, "Flag", Some("CLEAR"), Some("Track"), None, "TRACK CLEAR");
+            }
+        }
+        yellow_flags.retain(|(until, sector)| {
+            if time < *until {
+                return true;
+            }
+            message(time, "Flag", Some("CLEAR"), Some("Sector"), Some(*sector), &format!("CLEAR IN TRACK SECTOR {}", sector));
+            false
+        });
+
+        // Cars are moved in race order, so each one knows where the car
+        // ahead of it has got to.
+        let mut order: Vec<usize> = (0..cars.len()).collect();
+        order.sort_by(|a, b| cars[*b].distance.total_cmp(&cars[*a].distance));
+
+        let mut ahead: Option<(f32, f32)> = None;
+        for i in order {
+            let car = &mut cars[i];
+            if !car.running(time) {
+                continue;
+            }
+
+            if car.retire_at.is_some_and(|at| time >= at) {
+                car.retired = true;
+                let (x, y) = track.point_at(car.distance);
+                if let Some(sector) = sector_for_led(nearest_led(x, y).led_number) {
+                    message(time, "Flag", Some("YELLOW"), Some("Sector"), Some(sector), &format!("YELLOW IN TRACK SECTOR {}", sector));
+                    yellow_flags.push((time + YELLOW_FLAG_TIME, sector));
+                }
+                if safety_car.is_none() && rng.gen_bool(SAFETY_CAR_CHANCE) {
+                    let from = time + 5.0;
+                    safety_car = Some((from, from + SAFETY_CAR_LAPS * BASE_LAP_TIME / SAFETY_CAR_FACTOR));
+                    message(from, "SafetyCar", None, None, None, "SAFETY CAR DEPLOYED");
+                }
+                continue;
+            }
+
+            if let Some(mut visit) = car.pit {
+                if visit.along >= pit_lane.length / 2.0 && visit.stop_left > 0.0 {
+                    visit.stop_left -= SAMPLE_INTERVAL;
+                } else {
+                    visit.along += PIT_SPEED * SAMPLE_INTERVAL;
+                }
+                if visit.along >= pit_lane.length {
+                    car.distance = visit.entry + (pit_exit - pit_entry);
+                    car.pit = None;
+                } else {
+                    car.pit = Some(visit);
+                    let (x, y) = pit_lane.point_at(visit.along);
+                    car.samples.push(location(car.driver_number, config.start, time, x, y));
+                    continue;
+                }
+            }
+
+            let lap = car.lap(&track);
+            let racing_speed = car.pace * car.lap_factor * track.factor_at(car.distance);
+            let speed = if car.finished_at.is_some() {
+                racing_speed * 0.5
+            } else if safety_car_out {
+                match ahead {
+                    Some((distance, _)) if distance - car.distance > SAFETY_CAR_GAP => racing_speed * 0.8,
+                    _ => racing_speed * SAFETY_CAR_FACTOR,
+                }
+            } else {
+                racing_speed
+            };
+            let mut distance = car.distance + speed * SAMPLE_INTERVAL;
+
+            // Stuck behind the car ahead, unless the pace is there to pass.
+            if let Some((ahead_distance, ahead_pace)) = ahead {
+                if distance > ahead_distance - MIN_GAP && car.finished_at.is_none() {
+                    let advantage = (car.pace * car.lap_factor / ahead_pace - 1.0) * 100.0;
+                    let chance = (OVERTAKE_CHANCE * advantage * SAMPLE_INTERVAL).clamp(0.0, 1.0);
+                    if safety_car_out || !rng.gen_bool(chance as f64) {
+                        distance = distance.min(ahead_distance - MIN_GAP).max(car.distance);
+                    }
+                }
+            }
+
+            let entry = lap as f32 * track.length + pit_entry;
+            if car.finished_at.is_none() && car.pit_laps.contains(&lap) && car.distance < entry && distance >= entry {
+                car.pit_laps.retain(|l| *l != lap);
+                car.pit = Some(PitVisit {
+                    along: distance - entry,
+                    stop_left: car.stop_time,
+                    entry,
+                });
+                car.distance = entry;
+                let (x, y) = pit_lane.point_at(distance - entry);
+                car.samples.push(location(car.driver_number, config.start, time, x, y));
+                continue;
+            }
+
+            if car.lap(&track) != lap {
+                car.lap_factor = 1.0 - rng.gen_range(0.0..car.variance);
+            }
+            // The leader takes the flag at the end of the last lap, everyone
+            // else the next time they cross the line.
+            let crossed_line = (distance - start_line).div_euclid(track.length)
+                > (car.distance - start_line).div_euclid(track.length);
+            let takes_flag = if chequered { crossed_line } else { distance >= finish };
+            if car.finished_at.is_none() && takes_flag {
+                if !chequered {
+                    chequered = true;
+                    message(time, "Flag", Some("CHEQUERED"), Some("Track"), None, "CHEQUERED FLAG");
+                }
+                car.finished_at = Some(time);
+            }
+            car.distance = distance;
+
+            let (x, y) = track.point_at(distance);
+            let wander = rng.gen_range(-LATERAL_NOISE..LATERAL_NOISE);
+            car.samples.push(location(car.driver_number, config.start, time, x + wander, y - wander));
+            ahead = Some((car.distance, car.pace * car.lap_factor));
+        }
+    }
+
+    Simulation {
+        locations: cars.into_iter().map(|car| car.samples).collect(),
+        race_control: messages,
+    }
+}
+
+fn pit_lane(track: &Route) -> Route {
+    let mut points = vec![nearest_point(track, PIT_ENTRY_LED)];
+    points.extend(PIT_LANE_LED_DATA.iter().map(|led| (led.x_led, led.y_led)));
+    points.push(nearest_point(track, PIT_EXIT_LED));
+    Route::new(points, false)
+}
+
+fn nearest_point(track: &Route, led_number: u32) -> (f32, f32) {
+    track.point_at(track.distance_of(led_number))
+}
+
+fn format_date(start: DateTime<Utc>, time: f32) -> String {
+    let date = start + TimeDelta::milliseconds((time * 1000.0) as i64);
+    date.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn location(driver_number: u32, start: DateTime<Utc>, time: f32, x: f32, y: f32) -> LocationData {
+    LocationData {
+        x: x.round(),
+        y: y.round(),
+        date: format_date(start, time),
+        driver_number,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn race(seed: u64) -> Simulation {
+        simulate(&SimulationConfig {
+            seed,
+            ..SimulationConfig::default()
+        })
+    }
+
+    /// Everything the race is made of, to compare two of them.
+    fn outcome(simulation: &Simulation) -> Vec<String> {
+        let locations = simulation
+            .locations
+            .iter()
+            .flatten()
+            .map(|d| format!("{} {} {} {}", d.driver_number, d.date, d.x, d.y));
+        let messages = simulation.race_control.iter().map(|m| format!("{} {}", m.date, m.message));
+        locations.chain(messages).collect()
+    }
+
+    /// How far `(x, y)` is from the nearest segment of `route`.
+    fn off_route(route: &Route, closed: bool, (x, y): (f32, f32)) -> f32 {
+        let count = route.points.len() - usize::from(!closed);
+        (0..count)
+            .map(|i| {
+                let ((ax, ay), (bx, by)) = (route.points[i], route.points[(i + 1) % route.points.len()]);
+                let (dx, dy) = (bx - ax, by - ay);
+                let length = dx * dx + dy * dy;
+                let t = if length > 0.0 {
+                    (((x - ax) * dx + (y - ay) * dy) / length).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                (x - ax - dx * t).hypot(y - ay - dy * t)
+            })
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_race() {
+        let race1 = outcome(&race(1));
+        assert_eq!(race1, outcome(&race(1)));
+        assert_ne!(race1, outcome(&race(2)));
+    }
+
+    #[test]
+    fn cars_stay_on_the_track_or_in_the_pit_lane() {
+        let track = Route::from_leds(LED_DATA, true);
+        let pit_lane = pit_lane(&track);
+        // The wander, and the rounding of the samples.
+        let tolerance = LATERAL_NOISE * std::f32::consts::SQRT_2 + 1.0;
+
+        let simulation = race(1);
+        let mut pitted = 0;
+        for samples in &simulation.locations {
+            assert!(!samples.is_empty());
+            let mut in_pit_lane = false;
+            for sample in samples {
+                let point = (sample.x, sample.y);
+                let on_track = off_route(&track, true, point) <= tolerance;
+                if !on_track {
+                    assert!(off_route(&pit_lane, false, point) <= 1.0, "{:?} is off the route", sample);
+                    in_pit_lane = true;
+                }
+            }
+            pitted += usize::from(in_pit_lane);
+        }
+        // Every car stops at least once in a race of more than 3 laps,
+        // unless it retires first.
+        assert!(pitted >= simulation.locations.len() - 2, "only {} cars pitted", pitted);
+    }
+
+    #[test]
+    fn retirements_bring_out_flags() {
+        // A race with a safety car that comes in before the end.
+        let simulation = race(7);
+        let position = |text: &str| simulation.race_control.iter().position(|m| m.message.starts_with(text));
+        let deployed = position("SAFETY CAR DEPLOYED").expect("a safety car");
+        assert!(position("TRACK CLEAR").expect("the safety car comes in") > deployed);
+        let yellow = position("YELLOW IN TRACK SECTOR").expect("a yellow flag");
+        assert!(position("CLEAR IN TRACK SECTOR").expect("the yellow flag is cleared") > yellow);
+
+        // Retired cars stop well before the others finish.
+        let last = |samples: &Vec<LocationData>| samples.last().map(|s| s.date.clone()).unwrap_or_default();
+        let chequered = &simulation.race_control[position("CHEQUERED FLAG").unwrap()].date;
+        assert!(simulation.locations.iter().any(|samples| &last(samples) < chequered));
+    }
+}
@@ -0,0 +1,240 @@
+use std::fs;
+use std::path::Path;
+
+use iced::widget::canvas::Cache;
+use tiny_skia::Pixmap;
+
+use crate::driver_info::find_driver;
+use crate::graph::{Graph, PADDING};
+use crate::layout::Layout;
+use crate::led_data::UpdateFrame;
+use crate::physical::LedModel;
+use crate::render;
+
+/// A channel may differ by this much before the pixel counts as changed,
+/// to allow for antialiasing differences between machines.
+const CHANNEL_TOLERANCE: u8 = 24;
+/// Share of changed pixels a snapshot still matches with, which leaves
+/// room for text rasterized slightly differently.
+const MAX_CHANGED_PIXELS: f64 = 0.002;
+
+/// A known board to render and compare against its snapshot.
+struct Case {
+    name: &'static str,
+    frame: Option<UpdateFrame>,
+    width: u32,
+    height: u32,
+    physical: bool,
+}
+
+fn driver_color(driver_number: u32) -> (u8, u8, u8) {
+    find_driver(driver_number).map_or((255, 255, 255), |driver| driver.color)
+}
+
+fn frame(leds: &[(u32, u32)]) -> UpdateFrame {
+    let mut frame = UpdateFrame::new(0);
+    for (led_number, driver_number) in leds {
+        frame.set_led_state(*led_number, driver_color(*driver_number));
+    }
+    frame
+}
+
+fn cases() -> Vec<Case> {
+    let field = frame(&[(49, 1), (50, 11), (53, 16), (58, 44), (62, 4), (70, 14)]);
+
+    vec![
+        Case {
+            name: "no_frame",
+            frame: None,
+            width: 640,
+            height: 360,
+            physical: false,
+        },
+        Case {
+            name: "empty_frame",
+            frame: Some(UpdateFrame::new(0)),
+            width: 640,
+            height: 360,
+            physical: false,
+        },
+        Case {
+            name: "field",
+            frame: Some(field.clone()),
+            width: 640,
+            height: 360,
+            physical: false,
+        },
+        // Two drivers on one LED: the first one listed is drawn.
+        Case {
+            name: "collision",
+            frame: Some(frame(&[(49, 16), (49, 1), (50, 44), (50, 44)])),
+            width: 640,
+            height: 360,
+            physical: false,
+        },
+        // LEDs that are not on the board are left out, not drawn or
+        // panicked on.
+        Case {
+            name: "off_track",
+            frame: Some(frame(&[(0, 1), (49, 11), (500, 16), (u32::MAX, 44)])),
+            width: 640,
+            height: 360,
+            physical: false,
+        },
+        Case {
+            name: "pit_lane",
+            frame: Some(frame(&[(100, 1), (103, 11), (107, 16)])),
+            width: 640,
+            height: 360,
+            physical: false,
+        },
+        Case {
+            name: "resize_small",
+            frame: Some(field.clone()),
+            width: 200,
+            height: 120,
+            physical: false,
+        },
+        Case {
+            name: "resize_tall",
+            frame: Some(field.clone()),
+            width: 360,
+            height: 640,
+            physical: false,
+        },
+        Case {
+            name: "resize_wide",
+            frame: Some(field.clone()),
+            width: 960,
+            height: 240,
+            physical: false,
+        },
+        Case {
+            name: "physical",
+            frame: Some(field),
+            width: 640,
+            height: 360,
+            physical: true,
+        },
+    ]
+}
+
+/// Renders `case` on the board as built with the default padding, whatever
+/// `--layout` and `--padding` say, so the snapshots stay comparable from
+/// one machine to the next.
+fn render_case(case: &Case) -> Result<Pixmap, String> {
+    let layout = Layout::new(true);
+    let track_cache = Cache::new();
+    let graph = Graph {
+        layout: &layout,
+        track_cache: &track_cache,
+        update_frame: case.frame.as_ref(),
+        positions: &[],
+        reference_lap: None,
+        physical: case.physical.then(LedModel::default),
+        hovered: None,
+        follow: None,
+        padding: PADDING,
+    };
+    render::render(&graph, &[], case.width, case.height)
+}
+
+/// Renders every case and writes it to `dir` as its new snapshot.
+pub fn update(dir: &Path) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    for case in cases() {
+        let path = dir.join(format!("{}.png", case.name));
+        render_case(&case)?.save_png(&path).map_err(|e| e.to_string())?;
+        eprintln!("Updated {}", path.display());
+    }
+    Ok(())
+}
+
+/// Renders every case and compares it with its snapshot in `dir`. The
+/// renders that do not match are written to `failures`, with a mask of the
+/// changed pixels next to them.
+pub fn check(dir: &Path, failures: &Path) -> Result<(), String> {
+    let mut failed = Vec::new();
+    for case in cases() {
+        let actual = render_case(&case)?;
+        let expected = Pixmap::load_png(dir.join(format!("{}.png", case.name)))
+            .map_err(|e| format!("{}: {}", case.name, e))?;
+
+        match compare(&expected, &actual) {
+            Ok(()) => eprintln!("ok      {}", case.name),
+            Err((message, diff)) => {
+                eprintln!("FAILED  {}: {}", case.name, message);
+                fs::create_dir_all(failures).map_err(|e| e.to_string())?;
+                let save = |pixmap: &Pixmap, suffix: &str| {
+                    let path = failures.join(format!("{}{}.png", case.name, suffix));
+                    pixmap.save_png(path).map_err(|e| e.to_string())
+                };
+                save(&actual, "")?;
+                if let Some(diff) = diff {
+                    save(&diff, ".diff")?;
+                }
+                failed.push(case.name);
+            }
+        }
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} snapshot(s) do not match, see {}: {}",
+            failed.len(),
+            failures.display(),
+            failed.join(", "),
+        ))
+    }
+}
+
+/// Compares two renders, returning why they differ and, if they are the
+/// same size, a mask with the changed pixels in red.
+fn compare(expected: &Pixmap, actual: &Pixmap) -> Result<(), (String, Option<Pixmap>)> {
+    if (expected.width(), expected.height()) != (actual.width(), actual.height()) {
+        return Err((
+            format!(
+                "size is {}x{}, expected {}x{}",
+                actual.width(),
+                actual.height(),
+                expected.width(),
+                expected.height(),
+            ),
+            None,
+        ));
+    }
+
+    let mut diff = Pixmap::new(actual.width(), actual.height()).expect("size checked above");
+    let mut changed = 0;
+    let pixels = expected.data().chunks_exact(4).zip(actual.data().chunks_exact(4));
+    for ((a, b), out) in pixels.zip(diff.data_mut().chunks_exact_mut(4)) {
+        let is_changed = a.iter().zip(b).any(|(a, b)| a.abs_diff(*b) > CHANNEL_TOLERANCE);
+        let value = if is_changed { [255, 0, 0, 255] } else { [0, 0, 0, 255] };
+        out.copy_from_slice(&value);
+        changed += is_changed as usize;
+    }
+
+    let share = changed as f64 / (actual.width() * actual.height()) as f64;
+    if share > MAX_CHANGED_PIXELS {
+        Err((
+            format!("{} pixels changed ({:.2}%)", changed, share * 100.0),
+            Some(diff),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_match_the_snapshots() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("snapshots");
+        let failures = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/snapshot-failures");
+        check(&dir, &failures).unwrap();
+    }
+}
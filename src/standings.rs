@@ -0,0 +1,41 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+/// A position change from OpenF1 `/v1/position`.
+#[derive(Debug, Deserialize)]
+pub struct PositionData {
+    pub date: String,
+    pub driver_number: u32,
+    pub position: u32,
+}
+
+/// Race order over time, as the sequence of position changes of every driver.
+#[derive(Debug, Clone, Default)]
+pub struct Standings {
+    changes: Vec<(DateTime<Utc>, u32, u32)>,
+}
+
+impl Standings {
+    pub fn new(data: Vec<PositionData>) -> Self {
+        let mut changes: Vec<(DateTime<Utc>, u32, u32)> = data
+            .into_iter()
+            .filter_map(|d| {
+                let date = d.date.parse::<DateTime<Utc>>().ok()?;
+                Some((date, d.driver_number, d.position))
+            })
+            .collect();
+        changes.sort_by_key(|(date, _, _)| *date);
+
+        Self { changes }
+    }
+
+    /// The latest position of `driver_number` at `time`, if one was reported.
+    pub fn position_at(&self, driver_number: u32, time: DateTime<Utc>) -> Option<u32> {
+        self.changes
+            .iter()
+            .take_while(|(date, _, _)| *date <= time)
+            .filter(|(_, driver, _)| *driver == driver_number)
+            .last()
+            .map(|(_, _, position)| *position)
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::driver_info::find_driver;
+use crate::filter::DriverFilter;
+use crate::layout::Layout;
+use crate::led_data::UpdateFrame;
+use crate::replay::DriverPosition;
+
+/// Margin around the board, in track units.
+const MARGIN: f32 = 300.0;
+const LEGEND_WIDTH: f32 = 3200.0;
+const LEGEND_LINE_HEIGHT: f32 = 260.0;
+const FONT_SIZE: f32 = 180.0;
+
+/// The board as an SVG drawing: every LED of `layout` in its color from
+/// `frame`, the numbers of the drivers on it, and a legend of those drivers.
+///
+/// Coordinates are in track units with the y axis flipped, and numbers are
+/// rounded, so the same board always gives the same file.
+pub fn board_svg(
+    layout: &Layout,
+    frame: Option<&UpdateFrame>,
+    positions: &[DriverPosition],
+    filter: &DriverFilter,
+    caption: &str,
+) -> String {
+    let board_width = layout.max_x - layout.min_x + 2.0 * MARGIN;
+    let height = layout.max_y - layout.min_y + 2.0 * MARGIN;
+    let width = board_width + LEGEND_WIDTH;
+    let project = |x: f32, y: f32| (x - layout.min_x + MARGIN, layout.max_y - y + MARGIN);
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {:.0} {:.0}" font-family="monospace" font-size="{:.0}">"#,
+        width, height, FONT_SIZE,
+    );
+    let _ = writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#);
+    let _ = writeln!(
+        svg,
+        r#"<text x="{:.0}" y="{:.0}">{}</text>"#,
+        MARGIN / 2.0,
+        MARGIN / 2.0 + FONT_SIZE / 2.0,
+        escape(caption),
+    );
+
+    let colors = frame.map(|frame| frame.led_colors(layout.max_led_number()));
+    let _ = writeln!(svg, r#"<g id="leds" stroke="rgb(150,150,150)" stroke-width="10">"#);
+    for led in layout.leds() {
+        let (x, y) = project(led.x_led, led.y_led);
+        let fill = match colors.as_ref().and_then(|colors| colors[led.led_number as usize]) {
+            Some((r, g, b)) => format!("rgb({},{},{})", r, g, b),
+            None => "none".to_string(),
+        };
+        let _ = writeln!(
+            svg,
+            r#"<rect id="led-{}" x="{:.0}" y="{:.0}" width="{:.0}" height="{:.0}" fill="{}"/>"#,
+            led.led_number,
+            x - layout.led_size / 2.0,
+            y - layout.led_size / 2.0,
+            layout.led_size,
+            layout.led_size,
+            fill,
+        );
+    }
+    let _ = writeln!(svg, "</g>");
+
+    // Drivers sharing an LED get one label between them.
+    let mut on_leds: BTreeMap<u32, Vec<u32>> = BTreeMap::new();
+    for p in positions.iter().filter(|p| p.on_board() && filter.is_visible(p.driver_number)) {
+        on_leds.entry(p.led_number).or_default().push(p.driver_number);
+    }
+
+    let _ = writeln!(svg, r#"<g id="drivers" text-anchor="middle">"#);
+    for (led_number, drivers) in &mut on_leds {
+        let Some(led) = layout.led(*led_number) else {
+            continue;
+        };
+        drivers.sort_unstable();
+        let label: Vec<String> = drivers.iter().map(|d| d.to_string()).collect();
+        let (x, y) = project(led.x_led, led.y_led);
+        let _ = writeln!(
+            svg,
+            r#"<text x="{:.0}" y="{:.0}">{}</text>"#,
+            x,
+            y - layout.led_size,
+            label.join("/"),
+        );
+    }
+    let _ = writeln!(svg, "</g>");
+
+    let mut legend: Vec<u32> = on_leds.into_values().flatten().collect();
+    legend.sort_unstable();
+    let _ = writeln!(svg, r#"<g id="legend">"#);
+    for (i, driver) in legend.into_iter().filter_map(find_driver).enumerate() {
+        let y = MARGIN + i as f32 * LEGEND_LINE_HEIGHT;
+        let (r, g, b) = filter.color_for(driver.number).unwrap_or(driver.color);
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{:.0}" y="{:.0}" width="{:.0}" height="{:.0}" fill="rgb({},{},{})"/>"#,
+            board_width,
+            y,
+            FONT_SIZE,
+            FONT_SIZE,
+            r,
+            g,
+            b,
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text x="{:.0}" y="{:.0}">#{} {} ({})</text>"#,
+            board_width + 1.5 * FONT_SIZE,
+            y + FONT_SIZE * 0.85,
+            driver.number,
+            escape(driver.name),
+            escape(driver.team),
+        );
+    }
+    let _ = writeln!(svg, "</g>");
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
@@ -0,0 +1,338 @@
+use iced::futures::SinkExt;
+use iced::subscription::{self, Subscription};
+use serde::{Deserialize, Serialize};
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use crate::server::MAX_SPEED;
+
+/// Drift beyond which a follower jumps to the leader's time instead of
+/// catching up gradually.
+const MAX_SLEW_DRIFT: Duration = Duration::from_secs(1);
+/// How much faster or slower than the leader a follower may play to catch
+/// up, as a share of real time.
+const MAX_SLEW_RATE: f64 = 0.05;
+/// Interval at which the leader announces its clock even when nothing
+/// changes, so followers that join late or miss a beacon catch up.
+pub const BEACON_INTERVAL: Duration = Duration::from_millis(500);
+/// Longest replay a beacon may place the clock in; no session runs a day.
+const MAX_ELAPSED: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The leader's replay clock, as sent to followers in one UDP datagram of
+/// JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Beacon {
+    /// Picked at random when the leader starts, to tell a restarted leader
+    /// from old datagrams.
+    pub epoch: u64,
+    pub seq: u64,
+    pub session: String,
+    pub elapsed_us: u64,
+    /// Frames the leader has built, so followers can build theirs when the
+    /// leader does.
+    pub frame: u64,
+    pub playing: bool,
+    pub speed: f32,
+}
+
+impl Beacon {
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_micros(self.elapsed_us)
+    }
+
+    /// Decodes a datagram, and checks that its clock is one a leader could
+    /// have sent, as anyone on the network can send one.
+    pub fn parse(datagram: &[u8]) -> Result<Self, String> {
+        let beacon: Beacon = serde_json::from_slice(datagram).map_err(|e| e.to_string())?;
+        if !(beacon.speed.is_finite() && (0.0..=MAX_SPEED).contains(&beacon.speed)) {
+            return Err(format!("speed {} is not from 0 to {}", beacon.speed, MAX_SPEED));
+        }
+        if beacon.elapsed() > MAX_ELAPSED {
+            return Err(format!("{} s into the replay is past any session", beacon.elapsed().as_secs()));
+        }
+        Ok(beacon)
+    }
+}
+
+/// Sends the replay clock to every follower.
+#[derive(Debug)]
+pub struct Leader {
+    socket: UdpSocket,
+    followers: Vec<SocketAddr>,
+    epoch: u64,
+    seq: u64,
+}
+
+impl Leader {
+    /// A leader sending to `followers`, which may be broadcast addresses.
+    pub fn new(followers: Vec<SocketAddr>) -> Result<Self, String> {
+        let socket = UdpSocket::bind(("0.0.0.0", 0)).map_err(|e| e.to_string())?;
+        socket.set_broadcast(true).map_err(|e| e.to_string())?;
+        socket.set_nonblocking(true).map_err(|e| e.to_string())?;
+        Ok(Self {
+            socket,
+            followers,
+            epoch: rand::random(),
+            seq: 0,
+        })
+    }
+
+    pub fn send(&mut self, session: &str, elapsed: Duration, frame: u64, playing: bool, speed: f32) {
+        self.seq += 1;
+        let beacon = Beacon {
+            epoch: self.epoch,
+            seq: self.seq,
+            session: session.to_string(),
+            elapsed_us: elapsed.as_micros() as u64,
+            frame,
+            playing,
+            speed,
+        };
+        let datagram = serde_json::to_vec(&beacon).expect("beacons encode");
+        for follower in &self.followers {
+            if let Err(e) = self.socket.send_to(&datagram, follower) {
+                eprintln!("Failed to send the clock to {}: {}", follower, e);
+            }
+        }
+    }
+
+    pub fn followers(&self) -> usize {
+        self.followers.len()
+    }
+}
+
+/// A replay clock kept in step with a leader's: it runs on its own between
+/// beacons, and catches up with each beacon by playing slightly faster or
+/// slower, or by jumping when it is too far off.
+#[derive(Debug, Clone)]
+pub struct FollowerClock {
+    elapsed: Duration,
+    playing: bool,
+    speed: f32,
+    /// Drift still to be made up, in seconds; negative when ahead.
+    correction: f64,
+    last_update: Instant,
+    last_beacon: Option<(u64, u64)>,
+    frame: u64,
+    frame_received: Option<Instant>,
+    drift: Option<f64>,
+}
+
+impl FollowerClock {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            elapsed: Duration::ZERO,
+            playing: false,
+            speed: 1.0,
+            correction: 0.0,
+            last_update: now,
+            last_beacon: None,
+            frame: 0,
+            frame_received: None,
+            drift: None,
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn playing(&self) -> bool {
+        self.playing
+    }
+
+    /// How far behind the leader the clock was at the latest beacon, in
+    /// seconds; negative when it was ahead. One-way network delay counts
+    /// as drift, which on a local network is well below a frame.
+    pub fn drift(&self) -> Option<f64> {
+        self.drift
+    }
+
+    /// Whether the leader has announced a new frame within `within` before
+    /// `now`. While it does, the follower builds its frames when the leader
+    /// does, and on its own otherwise.
+    pub fn hears_leader(&self, now: Instant, within: Duration) -> bool {
+        self.frame_received
+            .is_some_and(|received| now.saturating_duration_since(received) <= within)
+    }
+
+    /// Runs the clock on to `now`, making up part of the drift.
+    pub fn advance(&mut self, now: Instant) -> Duration {
+        let real = now.saturating_duration_since(self.last_update).as_secs_f64();
+        self.last_update = now;
+        if !self.playing {
+            return self.elapsed;
+        }
+
+        let limit = real * MAX_SLEW_RATE;
+        let step = self.correction.clamp(-limit, limit);
+        self.correction -= step;
+        let elapsed = self.elapsed.as_secs_f64() + real * self.speed as f64 + step;
+        self.elapsed = Duration::from_secs_f64(elapsed.max(0.0));
+        self.elapsed
+    }
+
+    /// Takes in a beacon received at `now`. Returns whether the follower
+    /// should build a frame for it: when the leader built a new one or the
+    /// clock jumped. Beacons older than the latest one are ignored.
+    pub fn receive(&mut self, beacon: &Beacon, now: Instant) -> bool {
+        if let Some((epoch, seq)) = self.last_beacon {
+            if epoch == beacon.epoch && beacon.seq <= seq {
+                return false;
+            }
+        }
+        self.last_beacon = Some((beacon.epoch, beacon.seq));
+        self.advance(now);
+
+        let drift = beacon.elapsed().as_secs_f64() - self.elapsed.as_secs_f64();
+        self.drift = Some(drift);
+        self.speed = beacon.speed;
+        self.playing = beacon.playing;
+
+        // A paused leader has no time to catch up over.
+        let jumped = drift.abs() > MAX_SLEW_DRIFT.as_secs_f64() || (!beacon.playing && drift != 0.0);
+        if jumped {
+            self.elapsed = beacon.elapsed();
+            self.correction = 0.0;
+        } else {
+            self.correction = drift;
+        }
+
+        let new_frame = beacon.frame != self.frame;
+        if new_frame {
+            self.frame = beacon.frame;
+            self.frame_received = Some(now);
+        }
+        jumped || new_frame
+    }
+}
+
+/// Binds the port followers listen for beacons on.
+pub async fn bind(port: u16) -> Result<tokio::net::UdpSocket, String> {
+    tokio::net::UdpSocket::bind(("0.0.0.0", port))
+        .await
+        .map_err(|e| format!("failed to listen for the leader on port {}: {}", port, e))
+}
+
+/// The next beacon on `socket`, skipping datagrams that are not one.
+pub async fn receive(socket: &tokio::net::UdpSocket) -> Beacon {
+    let mut buffer = [0; 1024];
+    loop {
+        match socket.recv_from(&mut buffer).await {
+            Ok((length, from)) => match Beacon::parse(&buffer[..length]) {
+                Ok(beacon) => return beacon,
+                Err(e) => eprintln!("Ignoring a datagram from {}: {}", from, e),
+            },
+            Err(e) => eprintln!("Failed to receive from the leader: {}", e),
+        }
+    }
+}
+
+/// The beacons arriving on `port`, for as long as the subscription is kept.
+pub fn beacons(port: u16) -> Subscription<Beacon> {
+    subscription::channel(("sync", port), 16, move |mut output| async move {
+        let socket = match bind(port).await {
+            Ok(socket) => Some(socket),
+            Err(e) => {
+                eprintln!("{}", e);
+                None
+            }
+        };
+
+        loop {
+            match &socket {
+                Some(socket) => {
+                    let _ = output.send(receive(socket).await).await;
+                }
+                None => std::future::pending().await,
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn beacon(seq: u64, elapsed: Duration, frame: u64, playing: bool) -> Beacon {
+        Beacon {
+            epoch: 1,
+            seq,
+            session: "9158".to_string(),
+            elapsed_us: elapsed.as_micros() as u64,
+            frame,
+            playing,
+            speed: 1.0,
+        }
+    }
+
+    #[test]
+    fn follower_catches_up_with_small_drift() {
+        let start = Instant::now();
+        let mut clock = FollowerClock::new(start);
+        clock.receive(&beacon(1, Duration::from_secs(10), 1, true), start);
+        assert_eq!(clock.elapsed(), Duration::from_secs(10));
+
+        // The leader is 200 ms ahead: made up by playing faster, not by a
+        // jump.
+        let now = start + Duration::from_secs(1);
+        assert!(!clock.receive(&beacon(2, Duration::from_millis(11_200), 1, true), now));
+        assert_eq!(clock.elapsed(), Duration::from_secs(11));
+        let mut now = now;
+        let mut leader = Duration::from_millis(11_200);
+        for _ in 0..8 {
+            now += Duration::from_secs(1);
+            leader += Duration::from_secs(1);
+            clock.advance(now);
+        }
+        let behind = leader.as_secs_f64() - clock.elapsed().as_secs_f64();
+        assert!(behind.abs() < 1e-6, "still {} s behind", behind);
+    }
+
+    #[test]
+    fn follower_jumps_on_a_seek() {
+        let start = Instant::now();
+        let mut clock = FollowerClock::new(start);
+        clock.receive(&beacon(1, Duration::from_secs(10), 1, true), start);
+        let now = start + Duration::from_millis(500);
+        assert!(clock.receive(&beacon(2, Duration::from_secs(600), 2, true), now));
+        assert_eq!(clock.elapsed(), Duration::from_secs(600));
+    }
+
+    #[test]
+    fn follower_stops_with_a_paused_leader() {
+        let start = Instant::now();
+        let mut clock = FollowerClock::new(start);
+        clock.receive(&beacon(1, Duration::from_secs(10), 1, true), start);
+        let now = start + Duration::from_millis(300);
+        clock.receive(&beacon(2, Duration::from_millis(10_250), 1, false), now);
+        assert!(!clock.playing());
+        assert_eq!(clock.elapsed(), Duration::from_millis(10_250));
+        assert_eq!(clock.advance(now + Duration::from_secs(5)), Duration::from_millis(10_250));
+    }
+
+    #[test]
+    fn stale_beacons_are_ignored() {
+        let start = Instant::now();
+        let mut clock = FollowerClock::new(start);
+        clock.receive(&beacon(5, Duration::from_secs(10), 1, false), start);
+        assert!(!clock.receive(&beacon(4, Duration::from_secs(600), 2, false), start));
+        assert_eq!(clock.elapsed(), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn malformed_beacons_are_dropped() {
+        let valid = serde_json::to_vec(&beacon(1, Duration::from_secs(10), 1, true)).unwrap();
+        assert!(Beacon::parse(&valid).is_ok());
+
+        let mut fast = beacon(1, Duration::from_secs(10), 1, true);
+        fast.speed = 1e9;
+        assert!(Beacon::parse(&serde_json::to_vec(&fast).unwrap()).is_err());
+        // Past the largest f32, which JSON cannot spell as infinity.
+        let infinite = String::from_utf8(valid.clone()).unwrap().replace(r#""speed":1.0"#, r#""speed":1e39"#);
+        assert!(Beacon::parse(infinite.as_bytes()).is_err());
+        let far = beacon(1, Duration::from_secs(u64::MAX / 1_000_000), 1, true);
+        assert!(Beacon::parse(&serde_json::to_vec(&far).unwrap()).is_err());
+        assert!(Beacon::parse(b"not a beacon").is_err());
+    }
+}
@@ -0,0 +1,112 @@
+use chrono::{DateTime, DurationRound, Utc};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+/// A car telemetry sample from OpenF1 `/v1/car_data`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CarData {
+    pub date: String,
+    pub driver_number: u32,
+    pub speed: u32,
+    pub n_gear: u8,
+}
+
+/// A lap from OpenF1 `/v1/laps`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LapData {
+    pub date_start: Option<String>,
+    pub driver_number: u32,
+    pub lap_number: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CarSample {
+    pub speed: u32,
+    pub gear: u8,
+}
+
+/// Car data is fetched in windows of this length around the replay time.
+pub const WINDOW: chrono::TimeDelta = chrono::TimeDelta::seconds(60);
+
+/// Telemetry of the inspected drivers, downloaded on demand.
+#[derive(Debug, Default)]
+pub struct Telemetry {
+    car_data: HashMap<u32, Vec<(DateTime<Utc>, CarSample)>>,
+    windows: HashSet<(u32, DateTime<Utc>)>,
+    laps: HashMap<u32, Vec<(DateTime<Utc>, u32)>>,
+    laps_requested: HashSet<u32>,
+}
+
+impl Telemetry {
+    pub fn window_start(time: DateTime<Utc>) -> DateTime<Utc> {
+        time.duration_trunc(WINDOW).unwrap_or(time)
+    }
+
+    /// Claims the car data window of `driver_number` around `time`, returning
+    /// its start if it has not been requested yet.
+    pub fn request_car_data(&mut self, driver_number: u32, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = Self::window_start(time);
+        self.windows.insert((driver_number, start)).then_some(start)
+    }
+
+    /// Claims the laps of `driver_number`, returning whether they still need
+    /// to be requested.
+    pub fn request_laps(&mut self, driver_number: u32) -> bool {
+        self.laps_requested.insert(driver_number)
+    }
+
+    /// Gives up the claim on a car data window whose request failed, so
+    /// that it is requested again.
+    pub fn car_data_failed(&mut self, driver_number: u32, start: DateTime<Utc>) {
+        self.windows.remove(&(driver_number, start));
+    }
+
+    /// Gives up the claim on laps whose request failed, so that they are
+    /// requested again.
+    pub fn laps_failed(&mut self, driver_number: u32) {
+        self.laps_requested.remove(&driver_number);
+    }
+
+    pub fn add_car_data(&mut self, data: Vec<CarData>) {
+        for d in data {
+            let Ok(date) = d.date.parse::<DateTime<Utc>>() else {
+                continue;
+            };
+            self.car_data.entry(d.driver_number).or_default().push((
+                date,
+                CarSample {
+                    speed: d.speed,
+                    gear: d.n_gear,
+                },
+            ));
+        }
+        for samples in self.car_data.values_mut() {
+            samples.sort_by_key(|(date, _)| *date);
+            samples.dedup_by_key(|(date, _)| *date);
+        }
+    }
+
+    pub fn add_laps(&mut self, data: Vec<LapData>) {
+        for d in data {
+            let Some(date) = d.date_start.and_then(|date| date.parse::<DateTime<Utc>>().ok()) else {
+                continue;
+            };
+            self.laps.entry(d.driver_number).or_default().push((date, d.lap_number));
+        }
+        for laps in self.laps.values_mut() {
+            laps.sort_by_key(|(date, _)| *date);
+        }
+    }
+
+    pub fn car_data_at(&self, driver_number: u32, time: DateTime<Utc>) -> Option<CarSample> {
+        let samples = self.car_data.get(&driver_number)?;
+        let index = samples.partition_point(|(date, _)| *date <= time);
+        samples.get(index.checked_sub(1)?).map(|(_, sample)| *sample)
+    }
+
+    pub fn lap_at(&self, driver_number: u32, time: DateTime<Utc>) -> Option<u32> {
+        let laps = self.laps.get(&driver_number)?;
+        let index = laps.partition_point(|(date, _)| *date <= time);
+        laps.get(index.checked_sub(1)?).map(|(_, lap)| *lap)
+    }
+}
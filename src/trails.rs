@@ -0,0 +1,221 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::time::Duration;
+
+use crate::filter::DriverFilter;
+use crate::led_data::UpdateFrame;
+use crate::replay::DriverPosition;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrailKind {
+    Off,
+    Time,
+    Leds,
+}
+
+impl TrailKind {
+    pub const ALL: [TrailKind; 3] = [TrailKind::Off, TrailKind::Time, TrailKind::Leds];
+}
+
+impl fmt::Display for TrailKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrailKind::Off => write!(f, "No trails"),
+            TrailKind::Time => write!(f, "Fade over time"),
+            TrailKind::Leds => write!(f, "Fade over LEDs"),
+        }
+    }
+}
+
+/// How far behind each car its trail reaches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrailMode {
+    Off,
+    /// LEDs fade out over this much replay time after the car has left.
+    Time(Duration),
+    /// The last this many LEDs a car has been on stay lit, dimmer the
+    /// further back they are.
+    Leds(usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TrailLed {
+    color: (u8, u8, u8),
+    brightness: f32,
+}
+
+/// Per-LED brightness left behind by the cars, composited under them.
+#[derive(Debug, Clone)]
+pub struct Trails {
+    mode: TrailMode,
+    leds: BTreeMap<u32, TrailLed>,
+    history: HashMap<u32, VecDeque<u32>>,
+    timestamp: Option<u64>,
+}
+
+impl Trails {
+    pub fn new(mode: TrailMode) -> Self {
+        Self {
+            mode,
+            leds: BTreeMap::new(),
+            history: HashMap::new(),
+            timestamp: None,
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: TrailMode) {
+        if mode != self.mode {
+            *self = Trails::new(mode);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Trails::new(self.mode);
+    }
+
+    /// Moves the trails on to `timestamp`, the replay time in milliseconds.
+    /// Going back in time clears them, as they cannot be rewound.
+    pub fn advance(&mut self, positions: &[DriverPosition], filter: &DriverFilter, timestamp: u64) {
+        let elapsed = match self.timestamp {
+            Some(previous) if timestamp < previous => {
+                self.clear();
+                0
+            }
+            Some(previous) => timestamp - previous,
+            None => 0,
+        };
+        self.timestamp = Some(timestamp);
+
+        match self.mode {
+            TrailMode::Off => {}
+            TrailMode::Time(fade) => {
+                let decay = elapsed as f32 / fade.as_millis().max(1) as f32;
+                self.leds.retain(|_, led| {
+                    led.brightness -= decay;
+                    led.brightness > 0.0
+                });
+
+                for position in positions.iter().filter(|p| p.on_board()) {
+                    if let Some(color) = filter.color_for(position.driver_number) {
+                        self.leds.insert(
+                            position.led_number,
+                            TrailLed {
+                                color,
+                                brightness: 1.0,
+                            },
+                        );
+                    }
+                }
+            }
+            TrailMode::Leds(length) => {
+                self.leds.clear();
+
+                for position in positions.iter().filter(|p| p.on_board()) {
+                    let history = self.history.entry(position.driver_number).or_default();
+                    if history.front() != Some(&position.led_number) {
+                        history.push_front(position.led_number);
+                        history.truncate(length + 1);
+                    }
+
+                    let Some(color) = filter.color_for(position.driver_number) else {
+                        continue;
+                    };
+                    for (age, led_number) in history.iter().enumerate().skip(1) {
+                        let brightness = 1.0 - age as f32 / (length + 1) as f32;
+                        let led = self.leds.entry(*led_number).or_insert(TrailLed {
+                            color,
+                            brightness: 0.0,
+                        });
+                        if brightness > led.brightness {
+                            *led = TrailLed { color, brightness };
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Lights the trail LEDs that no car is on, scaled by their brightness.
+    pub fn composite(&self, frame: &UpdateFrame) -> UpdateFrame {
+        let mut update_frame = frame.clone();
+
+        let occupied: HashSet<u32> = frame.led_states.iter().map(|(num, _)| *num).collect();
+
+        for (led_number, led) in &self.leds {
+            if occupied.contains(led_number) {
+                continue;
+            }
+            let scale = |c: u8| (c as f32 * led.brightness).round() as u8;
+            let (r, g, b) = led.color;
+            update_frame.set_led_state(*led_number, (scale(r), scale(g), scale(b)));
+        }
+
+        update_frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Driver 1's color.
+    const BLUE: (u8, u8, u8) = (30, 65, 255);
+
+    fn at(led_number: u32) -> Vec<DriverPosition> {
+        vec![DriverPosition {
+            driver_number: 1,
+            led_number,
+            x: 0.0,
+            y: 0.0,
+            in_pit: false,
+        }]
+    }
+
+    /// The trail LEDs, with the car on `car` as the frame has it.
+    fn lit(trails: &Trails, car: u32) -> Vec<(u32, (u8, u8, u8))> {
+        let mut frame = UpdateFrame::new(0);
+        frame.set_led_state(car, BLUE);
+        trails.composite(&frame).led_states.into_iter().skip(1).collect()
+    }
+
+    #[test]
+    fn time_trails_fade_over_their_duration() {
+        let filter = DriverFilter::default();
+        let mut trails = Trails::new(TrailMode::Time(Duration::from_millis(1000)));
+        trails.advance(&at(10), &filter, 0);
+        assert_eq!(lit(&trails, 10), []);
+
+        trails.advance(&at(11), &filter, 500);
+        assert_eq!(lit(&trails, 11), [(10, (15, 33, 128))]);
+        trails.advance(&at(11), &filter, 1000);
+        assert_eq!(lit(&trails, 11), []);
+
+        // Back in time, the trails start over.
+        trails.advance(&at(12), &filter, 1200);
+        trails.advance(&at(20), &filter, 100);
+        assert_eq!(lit(&trails, 20), []);
+    }
+
+    #[test]
+    fn led_trails_keep_the_last_leds() {
+        let filter = DriverFilter::default();
+        let mut trails = Trails::new(TrailMode::Leds(2));
+        for (time, led_number) in [10, 11, 12, 12, 13].into_iter().enumerate() {
+            trails.advance(&at(led_number), &filter, time as u64 * 100);
+        }
+        // Waiting on LED 12 did not count as another LED.
+        assert_eq!(lit(&trails, 13), [(11, (10, 22, 85)), (12, (20, 43, 170))]);
+    }
+
+    #[test]
+    fn hidden_drivers_leave_no_trail() {
+        let mut filter = DriverFilter::default();
+        filter.set_visible(1, false);
+        for mode in [TrailMode::Time(Duration::from_secs(1)), TrailMode::Leds(3)] {
+            let mut trails = Trails::new(mode);
+            trails.advance(&at(10), &filter, 0);
+            trails.advance(&at(11), &filter, 100);
+            assert_eq!(lit(&trails, 11), [], "{:?}", mode);
+        }
+    }
+}
@@ -0,0 +1,380 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::sync::watch;
+
+use crate::output::Output;
+
+pub const DDP_PORT: u16 = 4048;
+/// Size of the DDP header without a timecode.
+pub const DDP_HEADER: usize = 10;
+/// Version 1, in the top bits of the first header byte.
+pub const DDP_VERSION: u8 = 0x40;
+/// Set on the last packet of a frame: the device shows what it received.
+pub const DDP_PUSH: u8 = 0x01;
+/// Set when the header carries a timecode, four more bytes.
+const DDP_TIMECODE: u8 = 0x10;
+/// 8-bit RGB pixels.
+pub const DDP_RGB: u8 = 0x0B;
+/// The device's display, as opposed to its configuration or status.
+pub const DDP_DISPLAY: u8 = 1;
+/// Most pixel data in one DDP packet, 480 pixels, so packets fit in one
+/// Ethernet frame.
+pub const DDP_MAX_DATA: usize = 1440;
+/// Most LEDs set by one request to the JSON API, which WLED parses in a
+/// small buffer.
+pub const JSON_MAX_LEDS: usize = 256;
+const JSON_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How a WLED controller is sent frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    /// DDP over UDP: the whole strip in a few packets, with no reply.
+    Ddp,
+    /// WLED's JSON API over HTTP, setting the LEDs of one segment.
+    Json,
+}
+
+/// A controller given as `ddp://HOST[:PORT]` or `json://HOST[:PORT]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WledTarget {
+    pub protocol: Protocol,
+    pub host: String,
+    pub port: u16,
+}
+
+impl FromStr for WledTarget {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (protocol, address, default_port) = if let Some(address) = value.strip_prefix("ddp://") {
+            (Protocol::Ddp, address, DDP_PORT)
+        } else if let Some(address) = value.strip_prefix("json://") {
+            (Protocol::Json, address, 80)
+        } else {
+            return Err(format!("`{}` is neither ddp://HOST[:PORT] nor json://HOST[:PORT]", value));
+        };
+        let (host, port) = match address.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| format!("invalid port `{}`", port))?),
+            None => (address, default_port),
+        };
+        Ok(WledTarget {
+            protocol,
+            host: host.to_string(),
+            port,
+        })
+    }
+}
+
+/// Order a strip takes the color channels in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorOrder {
+    #[default]
+    Rgb,
+    Rbg,
+    Grb,
+    Gbr,
+    Brg,
+    Bgr,
+}
+
+impl ColorOrder {
+    pub fn apply(self, (r, g, b): (u8, u8, u8)) -> [u8; 3] {
+        match self {
+            ColorOrder::Rgb => [r, g, b],
+            ColorOrder::Rbg => [r, b, g],
+            ColorOrder::Grb => [g, r, b],
+            ColorOrder::Gbr => [g, b, r],
+            ColorOrder::Brg => [b, r, g],
+            ColorOrder::Bgr => [b, g, r],
+        }
+    }
+}
+
+/// Where the board sits on the controller's strip.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WledConfig {
+    /// Segment the board is on; the JSON API sets LEDs within a segment,
+    /// while DDP always addresses the whole strip.
+    pub segment: u8,
+    /// Pixel the first LED of the board is on: from the start of the strip
+    /// with DDP, and of the segment with the JSON API.
+    pub offset: u32,
+    /// Channel order to send colors in. WLED reorders them for its strip
+    /// itself, so this is only needed when it is set up as RGB.
+    pub order: ColorOrder,
+}
+
+/// An output sending to `target`.
+pub fn output(target: &WledTarget, config: &WledConfig) -> Result<Box<dyn Output>, String> {
+    Ok(match target.protocol {
+        Protocol::Ddp => Box::new(Ddp::new(target, config)?),
+        Protocol::Json => Box::new(Json::new(target, config)?),
+    })
+}
+
+/// The DDP packets of one frame of `data`, RGB bytes starting at pixel
+/// `offset`. The last one is pushed.
+pub fn ddp_packets(data: &[u8], offset: u32, sequence: u8) -> Vec<Vec<u8>> {
+    let chunks: Vec<&[u8]> = data.chunks(DDP_MAX_DATA).collect();
+    let mut byte_offset = offset * 3;
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let flags = if i + 1 == chunks.len() { DDP_VERSION | DDP_PUSH } else { DDP_VERSION };
+            let mut packet = Vec::with_capacity(DDP_HEADER + chunk.len());
+            packet.extend_from_slice(&[flags, sequence, DDP_RGB, DDP_DISPLAY]);
+            packet.extend_from_slice(&byte_offset.to_be_bytes());
+            packet.extend_from_slice(&(chunk.len() as u16).to_be_bytes());
+            packet.extend_from_slice(chunk);
+            byte_offset += chunk.len() as u32;
+            packet
+        })
+        .collect()
+}
+
+/// One DDP packet as a device reads it.
+#[derive(Debug, Clone, Copy)]
+pub struct DdpPacket<'a> {
+    pub sequence: u8,
+    /// Whether the device shows what it received once this packet is in.
+    pub push: bool,
+    /// Byte of the strip the data starts at.
+    pub offset: u32,
+    pub data: &'a [u8],
+}
+
+/// Reads a DDP packet of 8-bit RGB pixels for a display, the only kind
+/// sent here, checking its header against its data.
+pub fn parse_ddp(packet: &[u8]) -> Result<DdpPacket<'_>, String> {
+    if packet.len() < DDP_HEADER {
+        return Err(format!("{} bytes is shorter than the header", packet.len()));
+    }
+    let flags = packet[0];
+    if flags & 0xC0 != DDP_VERSION {
+        return Err(format!("version {} instead of 1", flags >> 6));
+    }
+    if packet[2] != DDP_RGB {
+        return Err(format!("data type {:#04x} instead of 8-bit RGB", packet[2]));
+    }
+    if packet[3] != DDP_DISPLAY {
+        return Err(format!("destination {} instead of the display", packet[3]));
+    }
+    let offset = u32::from_be_bytes([packet[4], packet[5], packet[6], packet[7]]);
+    let length = u16::from_be_bytes([packet[8], packet[9]]) as usize;
+    let header = if flags & DDP_TIMECODE != 0 { DDP_HEADER + 4 } else { DDP_HEADER };
+    let data = packet.get(header..).unwrap_or_default();
+    if data.len() != length {
+        return Err(format!("{} bytes of data where the header says {}", data.len(), length));
+    }
+    if !offset.is_multiple_of(3) || !length.is_multiple_of(3) {
+        return Err(format!("{} bytes at byte {} split a pixel", length, offset));
+    }
+    Ok(DdpPacket {
+        sequence: packet[1] & 0x0F,
+        push: flags & DDP_PUSH != 0,
+        offset,
+        data,
+    })
+}
+
+/// Sends frames as DDP packets over UDP.
+struct Ddp {
+    socket: UdpSocket,
+    address: SocketAddr,
+    target: String,
+    config: WledConfig,
+    /// From 1 to 15; 0 would tell the device packets are not numbered.
+    sequence: u8,
+}
+
+impl Ddp {
+    fn new(target: &WledTarget, config: &WledConfig) -> Result<Self, String> {
+        let address = (target.host.as_str(), target.port)
+            .to_socket_addrs()
+            .map_err(|e| format!("failed to resolve {}: {}", target.host, e))?
+            .next()
+            .ok_or_else(|| format!("{} has no address", target.host))?;
+        let socket = UdpSocket::bind(("0.0.0.0", 0)).map_err(|e| e.to_string())?;
+        socket.set_nonblocking(true).map_err(|e| e.to_string())?;
+        Ok(Self {
+            socket,
+            address,
+            target: format!("ddp://{}:{}", target.host, target.port),
+            config: *config,
+            sequence: 0,
+        })
+    }
+}
+
+impl Output for Ddp {
+    fn describe(&self) -> String {
+        self.target.clone()
+    }
+
+    fn send(&mut self, pixels: &[(u8, u8, u8)]) -> Result<(), String> {
+        self.sequence = self.sequence % 15 + 1;
+        let data: Vec<u8> = pixels.iter().flat_map(|&color| self.config.order.apply(color)).collect();
+        for packet in ddp_packets(&data, self.config.offset, self.sequence) {
+            self.socket.send_to(&packet, self.address).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+/// The requests to WLED's `/json/state` that set `pixels` on the LEDs of
+/// `config`'s segment, a few hundred LEDs per request.
+pub fn json_requests(pixels: &[(u8, u8, u8)], config: &WledConfig) -> Vec<serde_json::Value> {
+    pixels
+        .chunks(JSON_MAX_LEDS)
+        .enumerate()
+        .map(|(i, chunk)| {
+            // A start index followed by colors sets consecutive LEDs.
+            let start = config.offset as usize + i * JSON_MAX_LEDS;
+            let mut leds = vec![serde_json::json!(start)];
+            leds.extend(chunk.iter().map(|&color| {
+                let [a, b, c] = config.order.apply(color);
+                serde_json::json!(format!("{:02X}{:02X}{:02X}", a, b, c))
+            }));
+            serde_json::json!({
+                "on": true,
+                "seg": { "id": config.segment, "i": leds },
+            })
+        })
+        .collect()
+}
+
+/// Sends frames to the JSON API. Requests are made from a thread of their
+/// own so a slow controller never holds up the replay; frames that come
+/// in while one is being sent are skipped but for the latest.
+struct Json {
+    frames: watch::Sender<Option<Vec<(u8, u8, u8)>>>,
+    target: String,
+}
+
+impl Json {
+    fn new(target: &WledTarget, config: &WledConfig) -> Result<Self, String> {
+        let url = format!("http://{}:{}/json/state", target.host, target.port);
+        let client = reqwest::Client::builder()
+            .timeout(JSON_TIMEOUT)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())?;
+        let (frames, mut received) = watch::channel::<Option<Vec<(u8, u8, u8)>>>(None);
+        let config = *config;
+        let thread_url = url.clone();
+
+        std::thread::spawn(move || {
+            runtime.block_on(async move {
+                let mut failing = false;
+                // Ends when the output is dropped.
+                while received.changed().await.is_ok() {
+                    let Some(pixels) = received.borrow_and_update().clone() else {
+                        continue;
+                    };
+                    let mut result = Ok(());
+                    for request in json_requests(&pixels, &config) {
+                        result = post(&client, &thread_url, &request).await;
+                        if result.is_err() {
+                            break;
+                        }
+                    }
+                    match result {
+                        Ok(()) if failing => {
+                            eprintln!("Sending to {} again", thread_url);
+                            failing = false;
+                        }
+                        Err(e) if !failing => {
+                            eprintln!("Failed to send to {}: {}", thread_url, e);
+                            failing = true;
+                        }
+                        _ => {}
+                    }
+                }
+            })
+        });
+
+        Ok(Self { frames, target: url })
+    }
+}
+
+async fn post(client: &reqwest::Client, url: &str, request: &serde_json::Value) -> Result<(), String> {
+    let response = client.post(url).json(request).send().await.map_err(|e| e.to_string())?;
+    match response.status().is_success() {
+        true => Ok(()),
+        false => Err(format!("the controller answered {}", response.status())),
+    }
+}
+
+impl Output for Json {
+    fn describe(&self) -> String {
+        self.target.clone()
+    }
+
+    fn send(&mut self, pixels: &[(u8, u8, u8)]) -> Result<(), String> {
+        // Failed requests are reported by the thread making them.
+        self.frames.send_replace(Some(pixels.to_vec()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads `packets` back into the bytes of the strip they set, checking
+    /// that only the last one pushes.
+    fn reassemble(packets: &[Vec<u8>], sequence: u8) -> Vec<u8> {
+        let mut strip = Vec::new();
+        for (i, packet) in packets.iter().enumerate() {
+            let packet = parse_ddp(packet).unwrap();
+            assert_eq!(packet.sequence, sequence);
+            assert_eq!(packet.push, i + 1 == packets.len(), "packet {} of {}", i + 1, packets.len());
+            let offset = packet.offset as usize;
+            if strip.len() < offset + packet.data.len() {
+                strip.resize(offset + packet.data.len(), 0);
+            }
+            strip[offset..offset + packet.data.len()].copy_from_slice(packet.data);
+        }
+        strip
+    }
+
+    #[test]
+    fn ddp_frames_round_trip_in_chunks() {
+        // 600 pixels take two packets of at most 480.
+        let data: Vec<u8> = (0..600 * 3).map(|i| (i % 251) as u8).collect();
+        let packets = ddp_packets(&data, 10, 7);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].len(), DDP_HEADER + DDP_MAX_DATA);
+
+        let strip = reassemble(&packets, 7);
+        assert_eq!(&strip[..30], &[0; 30]);
+        assert_eq!(&strip[30..], &data[..]);
+    }
+
+    #[test]
+    fn ddp_packets_that_do_not_add_up_are_rejected() {
+        let mut packet = ddp_packets(&[1, 2, 3], 0, 1).remove(0);
+        assert!(parse_ddp(&packet[..DDP_HEADER - 1]).is_err());
+        packet.push(4);
+        assert!(parse_ddp(&packet).is_err());
+        let split = ddp_packets(&[1, 2, 3, 4], 0, 1).remove(0);
+        assert!(parse_ddp(&split).is_err());
+    }
+
+    #[test]
+    fn color_orders_go_out_in_the_strips_order() {
+        let pixels = [(255, 0, 0), (0, 128, 1)];
+        let data: Vec<u8> = pixels.iter().flat_map(|&color| ColorOrder::Grb.apply(color)).collect();
+        let strip = reassemble(&ddp_packets(&data, 0, 1), 1);
+        assert_eq!(strip, [0, 255, 0, 128, 0, 1]);
+        assert_eq!(ColorOrder::Bgr.apply((1, 2, 3)), [3, 2, 1]);
+    }
+}